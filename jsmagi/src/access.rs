@@ -0,0 +1,707 @@
+//! Finds which variables and member expressions, like `exports.Thing`, are plain to access.
+//! Reading a plain one can't run any code, like a getter, and nothing can reassign it while other
+//! code runs, so a pass can read it again instead of keeping a temporary variable around.
+//!
+//! ```js
+//! exports.Thing = undefined;
+//! // ...
+//! exports.Thing = exports.Thing || {};
+//! exports.Thing.A = "A";
+//! ```
+//! `exports.Thing` is safe here. It would be unsafe if there was an
+//! `Object.defineProperty(exports, "Thing", { get: ... })`. If there was a function that could
+//! set `exports.Thing = {}` at some later point, or `exports` was passed to a function, then we
+//! can't tell when it changes, so `exports.Thing` would be unknown.
+
+use std::collections::{HashMap, HashSet};
+
+use swc_atoms::JsWord;
+use swc_ecma_ast::{
+    ArrowExpr, AssignExpr, AssignOp, BinExpr, BinaryOp, CallExpr, Callee, Class, ClassDecl,
+    DoWhileStmt, Expr, ExprStmt, FnDecl, Function, Id, IfStmt, Lit, MemberExpr, MemberProp,
+    ObjectLit, Pat, PatOrExpr, Prop, PropName, PropOrSpread, SwitchStmt, UnaryOp, VarDeclarator,
+    WhileStmt,
+};
+use swc_ecma_utils::find_pat_ids;
+use swc_ecma_visit::{noop_visit_type, Visit, VisitWith};
+
+use crate::trust::Trust;
+
+/// How plain it is to access a variable or member expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Reading it has no side effects, and it only changes where the code says so.
+    Safe,
+    /// It has a getter or setter, so reading or writing it can run code.
+    Unsafe,
+    /// It is defined somewhere we can't see, or something that could run at any point might
+    /// reassign it.
+    Unknown,
+}
+
+/// A variable followed by constant property names, like `exports.Thing` or `a["b"].c`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AccessPath {
+    pub root: Id,
+    pub props: Vec<JsWord>,
+}
+impl AccessPath {
+    pub fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Ident(ident) => Some(AccessPath {
+                root: ident.to_id(),
+                props: Vec::new(),
+            }),
+            Expr::Member(member) => Self::from_member(member),
+            _ => None,
+        }
+    }
+
+    pub fn from_member(member: &MemberExpr) -> Option<Self> {
+        let mut path = Self::from_expr(&member.obj)?;
+        path.props.push(prop_name(&member.prop)?);
+        Some(path)
+    }
+
+    fn child(&self, prop: JsWord) -> Self {
+        let mut path = self.clone();
+        path.props.push(prop);
+        path
+    }
+
+    /// The paths that this one is a property of, starting from the variable.
+    fn parents(&self) -> impl Iterator<Item = AccessPath> + '_ {
+        (0..self.props.len()).map(|len| AccessPath {
+            root: self.root.clone(),
+            props: self.props[..len].to_vec(),
+        })
+    }
+}
+
+fn prop_name(prop: &MemberProp) -> Option<JsWord> {
+    match prop {
+        MemberProp::Ident(ident) => Some(ident.sym.clone()),
+        MemberProp::Computed(computed) => match &*computed.expr {
+            Expr::Lit(Lit::Str(str)) => Some(str.value.clone()),
+            // `a[0]` is `a["0"]`
+            Expr::Lit(Lit::Num(num))
+                if num.value.fract() == 0.0 && (0.0..1e21).contains(&num.value) =>
+            {
+                Some((num.value as u128).to_string().into())
+            }
+            _ => None,
+        },
+        MemberProp::PrivateName(_) => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WriteKind {
+    /// `a = a || {}` or `a || (a = {})`, which leaves an existing value alone
+    Initializer,
+    Plain,
+    /// A getter or setter is defined for it
+    Accessor,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Write {
+    kind: WriteKind,
+    /// How many functions deep the write is
+    depth: usize,
+}
+
+/// The facts about accesses in a program, which [`AccessAnalysis::get`] decides from.
+#[derive(Debug, Default)]
+pub struct AccessAnalysis {
+    writes: HashMap<AccessPath, Vec<Write>>,
+    /// Writes to properties whose name isn't known, like `a[key] = 1`, for the object's path
+    unknown_writes: HashMap<AccessPath, Vec<usize>>,
+    /// Paths whose value is passed somewhere, which could then change its properties
+    escapes: HashSet<AccessPath>,
+    /// How many functions deep each variable is declared
+    decl_depth: HashMap<Id, usize>,
+    trust: Trust,
+}
+impl AccessAnalysis {
+    pub fn analyze<N: VisitWith<AccessCollector>>(node: &N, trust: &Trust) -> Self {
+        let mut collector = AccessCollector::default();
+        node.visit_with(&mut collector);
+        AccessAnalysis {
+            trust: trust.clone(),
+            ..collector.analysis
+        }
+    }
+
+    /// How plain it is to access the expression. Anything that isn't an [`AccessPath`] is
+    /// [`Access::Unknown`].
+    pub fn get(&self, expr: &Expr) -> Access {
+        match AccessPath::from_expr(expr) {
+            Some(path) => self.get_path(&path),
+            None => Access::Unknown,
+        }
+    }
+
+    pub fn get_path(&self, path: &AccessPath) -> Access {
+        let names: Vec<&str> = std::iter::once(&*path.root.0)
+            .chain(path.props.iter().map(|prop| &**prop))
+            .collect();
+        if self.trust.is_proxy(&names) {
+            return Access::Unsafe;
+        }
+        let plain = self.trust.is_plain(&names);
+
+        let writes = |path: &AccessPath| self.writes.get(path).into_iter().flatten();
+        if !plain && writes(path).any(|write| write.kind == WriteKind::Accessor) {
+            return Access::Unsafe;
+        }
+
+        // Code in the function that declares the variable runs in order, but a nested function
+        // could be called at any point
+        let root_depth = self.decl_depth.get(&path.root).copied().unwrap_or(0);
+        let reassigned_later = |path: &AccessPath| {
+            writes(path)
+                .any(|write| write.kind != WriteKind::Initializer && write.depth > root_depth)
+        };
+
+        let stable = !reassigned_later(path)
+            && path.parents().all(|parent| {
+                !reassigned_later(&parent)
+                    && !self.escapes.contains(&parent)
+                    && !self
+                        .unknown_writes
+                        .get(&parent)
+                        .is_some_and(|depths| depths.iter().any(|depth| *depth > root_depth))
+            });
+        // A variable is defined by its declaration, but a property could have been set up anywhere
+        let defined = if path.props.is_empty() {
+            self.decl_depth.contains_key(&path.root)
+        } else {
+            writes(path).next().is_some()
+        };
+
+        // The user can vouch for values that are set up somewhere we can't see
+        if stable && (defined || plain) {
+            Access::Safe
+        } else {
+            Access::Unknown
+        }
+    }
+
+    fn write(&mut self, path: AccessPath, kind: WriteKind, depth: usize) {
+        self.writes
+            .entry(path)
+            .or_default()
+            .push(Write { kind, depth });
+    }
+}
+
+/// Collects the facts for [`AccessAnalysis`]
+#[derive(Default)]
+pub struct AccessCollector {
+    analysis: AccessAnalysis,
+    depth: usize,
+    /// Set when the next expression is only looked at, rather than having its value passed on
+    looked_at: bool,
+}
+impl AccessCollector {
+    fn look_at<N: VisitWith<Self>>(&mut self, node: &N) {
+        self.visit_value(node, true);
+    }
+
+    /// Visit an expression whose value goes to the same place as its parent's
+    fn visit_value<N: VisitWith<Self>>(&mut self, node: &N, looked_at: bool) {
+        self.looked_at = looked_at;
+        node.visit_with(self);
+        self.looked_at = false;
+    }
+
+    fn write(&mut self, path: AccessPath, kind: WriteKind) {
+        self.analysis.write(path, kind, self.depth);
+    }
+
+    /// Record the getters and setters of an object literal assigned to `path`
+    fn write_accessors(&mut self, path: &AccessPath, object: &ObjectLit) {
+        for prop in &object.props {
+            let PropOrSpread::Prop(prop) = prop else {
+                continue;
+            };
+            let key = match &**prop {
+                Prop::Getter(getter) => &getter.key,
+                Prop::Setter(setter) => &setter.key,
+                _ => continue,
+            };
+            let name = match key {
+                PropName::Ident(ident) => ident.sym.clone(),
+                PropName::Str(str) => str.value.clone(),
+                _ => continue,
+            };
+            self.write(path.child(name), WriteKind::Accessor);
+        }
+    }
+
+    /// Record a write to the expression, which is being assigned to or deleted
+    fn write_target(&mut self, target: &Expr, kind: WriteKind) {
+        if let Some(path) = AccessPath::from_expr(target) {
+            self.write(path, kind);
+            return;
+        }
+
+        match target {
+            Expr::Member(member) => match AccessPath::from_expr(&member.obj) {
+                Some(obj) => {
+                    self.analysis
+                        .unknown_writes
+                        .entry(obj)
+                        .or_default()
+                        .push(self.depth);
+                    self.look_at(&member.prop);
+                }
+                None => self.look_at(member),
+            },
+            _ => target.visit_with(self),
+        }
+    }
+
+    fn visit_assign(&mut self, assign: &AssignExpr) {
+        let target = match &assign.left {
+            PatOrExpr::Expr(expr) => Some(&**expr),
+            PatOrExpr::Pat(pat) => match &**pat {
+                Pat::Expr(expr) => Some(&**expr),
+                Pat::Ident(ident) => {
+                    let path = AccessPath {
+                        root: ident.to_id(),
+                        props: Vec::new(),
+                    };
+                    self.write(path, assign_kind(assign));
+                    None
+                }
+                pat => {
+                    for id in find_pat_ids::<_, Id>(pat) {
+                        let path = AccessPath {
+                            root: id,
+                            props: Vec::new(),
+                        };
+                        self.write(path, WriteKind::Plain);
+                    }
+                    pat.visit_with(self);
+                    None
+                }
+            },
+        };
+
+        if let Some(target) = target {
+            self.write_target(target, assign_kind(assign));
+            if let (Some(path), Expr::Object(object)) =
+                (AccessPath::from_expr(target), &*assign.right)
+            {
+                self.write_accessors(&path, object);
+            }
+        }
+
+        match assign.right.as_bin() {
+            // The `a` in `a = a || {}` just goes back to where it came from
+            Some(bin)
+                if assign.op == AssignOp::Assign
+                    && assign_kind(assign) == WriteKind::Initializer =>
+            {
+                bin.right.visit_with(self)
+            }
+            _ => assign.right.visit_with(self),
+        }
+    }
+
+    fn visit_bin(&mut self, bin: &BinExpr, looked_at: bool) {
+        match bin.op {
+            // `a || (a = {})`
+            BinaryOp::LogicalOr => {
+                let initializer = bin
+                    .right
+                    .unwrap_parens()
+                    .as_assign()
+                    .filter(|assign| assign.op == AssignOp::Assign)
+                    .and_then(|assign| {
+                        let path = AccessPath::from_expr(&bin.left)?;
+                        let target = match &assign.left {
+                            PatOrExpr::Expr(expr) => AccessPath::from_expr(expr),
+                            PatOrExpr::Pat(pat) => match &**pat {
+                                Pat::Expr(expr) => AccessPath::from_expr(expr),
+                                Pat::Ident(ident) => {
+                                    AccessPath::from_expr(&ident.id.clone().into())
+                                }
+                                _ => None,
+                            },
+                        }?;
+                        (path == target).then_some((path, assign))
+                    });
+
+                match initializer {
+                    Some((path, assign)) => {
+                        self.write(path, WriteKind::Initializer);
+                        self.visit_value(&bin.left, looked_at);
+                        assign.right.visit_with(self);
+                    }
+                    None => {
+                        self.visit_value(&bin.left, looked_at);
+                        self.visit_value(&bin.right, looked_at);
+                    }
+                }
+            }
+            // The result is one of the values
+            BinaryOp::LogicalAnd | BinaryOp::NullishCoalescing => {
+                self.visit_value(&bin.left, looked_at);
+                self.visit_value(&bin.right, looked_at);
+            }
+            // Everything else only looks at the values to produce a new one
+            _ => {
+                self.look_at(&bin.left);
+                self.look_at(&bin.right);
+            }
+        }
+    }
+
+    fn visit_call(&mut self, call: &CallExpr) {
+        // `Object.defineProperty(a, "b", { ... })`
+        if let Some((path, descriptor)) = define_property(call) {
+            let kind = match descriptor {
+                Expr::Object(object) if !has_accessor_keys(object) => WriteKind::Plain,
+                _ => WriteKind::Accessor,
+            };
+            self.write(path, kind);
+            descriptor.visit_with(self);
+            return;
+        }
+
+        match &call.callee {
+            Callee::Expr(callee) => match AccessPath::from_expr(callee) {
+                // A method is called with the object as `this`
+                Some(path) => {
+                    if let Some(this) = path.parents().last() {
+                        self.analysis.escapes.insert(this);
+                    }
+                }
+                None => self.look_at(callee),
+            },
+            callee => callee.visit_with(self),
+        }
+
+        call.args.visit_with(self);
+        call.type_args.visit_with(self);
+    }
+}
+
+fn assign_kind(assign: &AssignExpr) -> WriteKind {
+    match assign.op {
+        AssignOp::OrAssign | AssignOp::NullishAssign => WriteKind::Initializer,
+        AssignOp::Assign => {
+            let left = match &assign.left {
+                PatOrExpr::Expr(expr) => AccessPath::from_expr(expr),
+                PatOrExpr::Pat(pat) => match &**pat {
+                    Pat::Expr(expr) => AccessPath::from_expr(expr),
+                    Pat::Ident(ident) => AccessPath::from_expr(&ident.id.clone().into()),
+                    _ => None,
+                },
+            };
+            let is_initializer = assign.right.as_bin().is_some_and(|bin| {
+                bin.op == BinaryOp::LogicalOr
+                    && left.is_some()
+                    && AccessPath::from_expr(&bin.left) == left
+            });
+
+            if is_initializer {
+                WriteKind::Initializer
+            } else {
+                WriteKind::Plain
+            }
+        }
+        _ => WriteKind::Plain,
+    }
+}
+
+/// If the call is `Object.defineProperty(a, "b", descriptor)`, get `a.b` and the descriptor
+fn define_property(call: &CallExpr) -> Option<(AccessPath, &Expr)> {
+    let callee = AccessPath::from_expr(call.callee.as_expr()?)?;
+    if &*callee.root.0 != "Object" || callee.props != ["defineProperty"] {
+        return None;
+    }
+
+    let [obj, key, descriptor] = &call.args[..] else {
+        return None;
+    };
+    if obj.spread.is_some() || key.spread.is_some() || descriptor.spread.is_some() {
+        return None;
+    }
+
+    let Expr::Lit(Lit::Str(key)) = &*key.expr else {
+        return None;
+    };
+    let path = AccessPath::from_expr(&obj.expr)?.child(key.value.clone());
+
+    Some((path, &descriptor.expr))
+}
+
+fn has_accessor_keys(object: &ObjectLit) -> bool {
+    object.props.iter().any(|prop| match prop {
+        PropOrSpread::Prop(prop) => {
+            let key = match &**prop {
+                Prop::KeyValue(kv) => &kv.key,
+                Prop::Method(method) => &method.key,
+                Prop::Shorthand(ident) => return &*ident.sym == "get" || &*ident.sym == "set",
+                _ => return true,
+            };
+            match key {
+                PropName::Ident(ident) => &*ident.sym == "get" || &*ident.sym == "set",
+                PropName::Str(str) => &*str.value == "get" || &*str.value == "set",
+                _ => true,
+            }
+        }
+        PropOrSpread::Spread(_) => true,
+    })
+}
+
+impl Visit for AccessCollector {
+    noop_visit_type!();
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        let looked_at = std::mem::take(&mut self.looked_at);
+
+        if let Some(path) = AccessPath::from_expr(expr) {
+            if !looked_at {
+                self.analysis.escapes.insert(path);
+            }
+            return;
+        }
+
+        match expr {
+            Expr::Assign(assign) => self.visit_assign(assign),
+            Expr::Update(update) => self.write_target(&update.arg, WriteKind::Plain),
+            Expr::Unary(unary) => match unary.op {
+                UnaryOp::Delete => self.write_target(&unary.arg, WriteKind::Plain),
+                _ => self.look_at(&unary.arg),
+            },
+            Expr::Bin(bin) => self.visit_bin(bin, looked_at),
+            Expr::Call(call) => self.visit_call(call),
+            Expr::Cond(cond) => {
+                self.look_at(&cond.test);
+                self.visit_value(&cond.cons, looked_at);
+                self.visit_value(&cond.alt, looked_at);
+            }
+            Expr::Paren(paren) => self.visit_value(&paren.expr, looked_at),
+            Expr::Seq(seq) => {
+                if let Some((last, rest)) = seq.exprs.split_last() {
+                    for expr in rest {
+                        self.look_at(expr);
+                    }
+                    self.visit_value(last, looked_at);
+                }
+            }
+            _ => expr.visit_children_with(self),
+        }
+    }
+
+    fn visit_member_expr(&mut self, member: &MemberExpr) {
+        // This is only reached for member expressions that aren't paths
+        self.look_at(&member.obj);
+        self.look_at(&member.prop);
+    }
+
+    fn visit_expr_stmt(&mut self, stmt: &ExprStmt) {
+        self.look_at(&stmt.expr);
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) {
+        self.look_at(&stmt.test);
+        stmt.cons.visit_with(self);
+        stmt.alt.visit_with(self);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) {
+        self.look_at(&stmt.test);
+        stmt.body.visit_with(self);
+    }
+
+    fn visit_do_while_stmt(&mut self, stmt: &DoWhileStmt) {
+        stmt.body.visit_with(self);
+        self.look_at(&stmt.test);
+    }
+
+    fn visit_switch_stmt(&mut self, stmt: &SwitchStmt) {
+        self.look_at(&stmt.discriminant);
+        stmt.cases.visit_with(self);
+    }
+
+    fn visit_var_declarator(&mut self, decl: &VarDeclarator) {
+        decl.name.visit_with(self);
+        if let (Some(ident), Some(init)) = (decl.name.as_ident(), &decl.init) {
+            let path = AccessPath {
+                root: ident.to_id(),
+                props: Vec::new(),
+            };
+            self.write(path.clone(), WriteKind::Plain);
+            if let Expr::Object(object) = &**init {
+                self.write_accessors(&path, object);
+            }
+        }
+        decl.init.visit_with(self);
+    }
+
+    fn visit_binding_ident(&mut self, ident: &swc_ecma_ast::BindingIdent) {
+        self.analysis
+            .decl_depth
+            .entry(ident.to_id())
+            .or_insert(self.depth);
+    }
+
+    fn visit_fn_decl(&mut self, decl: &FnDecl) {
+        self.analysis
+            .decl_depth
+            .entry(decl.ident.to_id())
+            .or_insert(self.depth);
+        decl.function.visit_with(self);
+    }
+
+    fn visit_class_decl(&mut self, decl: &ClassDecl) {
+        self.analysis
+            .decl_depth
+            .entry(decl.ident.to_id())
+            .or_insert(self.depth);
+        decl.class.visit_with(self);
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        self.depth += 1;
+        function.visit_children_with(self);
+        self.depth -= 1;
+    }
+
+    fn visit_arrow_expr(&mut self, arrow: &ArrowExpr) {
+        self.depth += 1;
+        arrow.visit_children_with(self);
+        self.depth -= 1;
+    }
+
+    // Field initializers run whenever the class is constructed
+    fn visit_class(&mut self, class: &Class) {
+        self.depth += 1;
+        class.visit_children_with(self);
+        self.depth -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_common::{sync::Lrc, FileName, SourceMap};
+    use swc_ecma_parser::{parse_file_as_module, EsConfig, Syntax};
+
+    use super::{Access, AccessAnalysis};
+    use crate::trust::Trust;
+
+    fn access(code: &str, expr: &str) -> Access {
+        access_with(code, expr, &Trust::default())
+    }
+
+    fn access_with(code: &str, expr: &str, trust: &Trust) -> Access {
+        let cm: Lrc<SourceMap> = Default::default();
+        let parse = |code: &str| {
+            let fm = cm.new_source_file(FileName::Anon, code.to_owned());
+            parse_file_as_module(
+                &fm,
+                Syntax::Es(EsConfig::default()),
+                Default::default(),
+                None,
+                &mut vec![],
+            )
+            .unwrap()
+        };
+
+        let module = parse(code);
+        let expr = parse(expr);
+        let expr = &expr.body[0].as_stmt().unwrap().as_expr().unwrap().expr;
+        AccessAnalysis::analyze(&module, trust).get(expr)
+    }
+
+    #[test]
+    fn test_safe() {
+        let code = r#"
+            exports.Thing = undefined;
+            exports.Thing = exports.Thing || {};
+            exports.Thing.A = "A";
+            Object.defineProperty(exports, "__esModule", { value: true });
+        "#;
+        assert_eq!(access(code, "exports.Thing"), Access::Safe);
+        assert_eq!(access(code, "exports.Thing.A"), Access::Safe);
+        assert_eq!(
+            access("(function (e) {})(a.b || (a.b = {}));", "a.b"),
+            Access::Safe
+        );
+        assert_eq!(access("var a = {}; a.b = 1;", "a"), Access::Safe);
+    }
+
+    #[test]
+    fn test_unsafe() {
+        assert_eq!(
+            access(
+                r#"Object.defineProperty(exports, "Thing", { get: function () { return 1; } });"#,
+                "exports.Thing"
+            ),
+            Access::Unsafe
+        );
+        assert_eq!(
+            access("var a = { get b() { return 1; } };", "a.b"),
+            Access::Unsafe
+        );
+    }
+
+    #[test]
+    fn test_unknown() {
+        assert_eq!(access("console.log(a.b);", "a.b"), Access::Unknown);
+        assert_eq!(access("a.b = {}; f(a);", "a.b"), Access::Unknown);
+        assert_eq!(access("a.b = {}; var c = a;", "a.b"), Access::Unknown);
+        assert_eq!(access("a.b = {}; a.reset();", "a.b"), Access::Unknown);
+        assert_eq!(access("a.b = {}; f(a.b);", "a.b"), Access::Safe);
+        assert_eq!(access("f();", "a"), Access::Unknown);
+        assert_eq!(
+            access("a.b = {}; function reset() { a.b = {}; }", "a.b"),
+            Access::Unknown
+        );
+        assert_eq!(
+            access("a.b = {}; function reset(k) { a[k] = {}; }", "a.b"),
+            Access::Unknown
+        );
+        // Initializers don't replace an existing value
+        assert_eq!(
+            access("a.b = {}; function init() { a.b = a.b || {}; }", "a.b"),
+            Access::Safe
+        );
+    }
+
+    #[test]
+    fn test_trust() {
+        let trust = Trust {
+            plain: vec!["e.exports".into()],
+            proxies: vec!["store".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            access_with("console.log(e.exports.a);", "e.exports.a", &trust),
+            Access::Safe
+        );
+        assert_eq!(
+            access_with(
+                r#"Object.defineProperty(e.exports, "a", { get: f });"#,
+                "e.exports.a",
+                &trust
+            ),
+            Access::Safe
+        );
+        // Being plain doesn't stop it from being changed later
+        assert_eq!(
+            access_with("function f() { e.exports.a = 1; }", "e.exports.a", &trust),
+            Access::Unknown
+        );
+        assert_eq!(
+            access_with("var store = {}; store.a = 1;", "store.a", &trust),
+            Access::Unsafe
+        );
+    }
+}
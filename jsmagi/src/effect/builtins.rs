@@ -0,0 +1,180 @@
+//! Effects of the functions that browsers and Node provide.
+//!
+//! Like most minifiers, these assume that any value which gets converted to a string or number is
+//! a primitive, or at least doesn't have its own `toString` or `valueOf`. Otherwise `String(x)`
+//! could run any code at all.
+//!
+//! They also assume that the numbers they are given aren't a `BigInt` or a `Symbol`, which the
+//! `Math` functions, `Number(x)`, `isNaN(x)`, `parseInt(x)` and the like throw a `TypeError`
+//! for. So `Math.abs(1n)` counts as pure, even though it throws.
+
+use super::Effects;
+
+const PURE: Effects = Effects::PURE;
+/// Pure, except that it throws for some arguments
+const THROWS: Effects = Effects {
+    throws: true,
+    ..Effects::PURE
+};
+/// Looks at the properties of its arguments, and throws if they are `null` or `undefined`
+const READS: Effects = Effects {
+    reads_args: true,
+    throws: true,
+    ..Effects::PURE
+};
+
+/// The functions, by the path that they are called through.
+const FUNCTIONS: &[(&str, Effects)] = &[
+    ("String", PURE),
+    ("Number", PURE),
+    ("Boolean", PURE),
+    ("isNaN", PURE),
+    ("isFinite", PURE),
+    ("parseInt", PURE),
+    ("parseFloat", PURE),
+    ("encodeURI", THROWS),
+    ("encodeURIComponent", THROWS),
+    ("decodeURI", THROWS),
+    ("decodeURIComponent", THROWS),
+    ("Array.isArray", PURE),
+    ("Array.of", PURE),
+    ("Number.isNaN", PURE),
+    ("Number.isFinite", PURE),
+    ("Number.isInteger", PURE),
+    ("Number.isSafeInteger", PURE),
+    ("Number.parseInt", PURE),
+    ("Number.parseFloat", PURE),
+    ("String.fromCharCode", PURE),
+    ("String.fromCodePoint", THROWS),
+    ("Object.is", PURE),
+    ("Object.keys", READS),
+    ("Object.values", READS),
+    ("Object.entries", READS),
+    ("Object.getOwnPropertyNames", READS),
+    ("Object.getPrototypeOf", READS),
+    ("JSON.parse", THROWS),
+    ("JSON.stringify", READS),
+    ("Math.abs", PURE),
+    ("Math.acos", PURE),
+    ("Math.acosh", PURE),
+    ("Math.asin", PURE),
+    ("Math.asinh", PURE),
+    ("Math.atan", PURE),
+    ("Math.atan2", PURE),
+    ("Math.atanh", PURE),
+    ("Math.cbrt", PURE),
+    ("Math.ceil", PURE),
+    ("Math.clz32", PURE),
+    ("Math.cos", PURE),
+    ("Math.cosh", PURE),
+    ("Math.exp", PURE),
+    ("Math.expm1", PURE),
+    ("Math.floor", PURE),
+    ("Math.fround", PURE),
+    ("Math.hypot", PURE),
+    ("Math.imul", PURE),
+    ("Math.log", PURE),
+    ("Math.log10", PURE),
+    ("Math.log1p", PURE),
+    ("Math.log2", PURE),
+    ("Math.max", PURE),
+    ("Math.min", PURE),
+    ("Math.pow", PURE),
+    ("Math.round", PURE),
+    ("Math.sign", PURE),
+    ("Math.sin", PURE),
+    ("Math.sinh", PURE),
+    ("Math.sqrt", PURE),
+    ("Math.tan", PURE),
+    ("Math.tanh", PURE),
+    ("Math.trunc", PURE),
+    // The result changes over time, but calling it doesn't change anything
+    (
+        "Date.now",
+        Effects {
+            reads_globals: true,
+            ..Effects::PURE
+        },
+    ),
+];
+
+/// Constructors which only create a new object, and the most arguments they can be given
+/// without them being read. `new Map(x)` would run the iterator of `x`.
+const CONSTRUCTORS: &[(&str, usize)] = &[
+    ("Error", 2),
+    ("TypeError", 2),
+    ("RangeError", 2),
+    ("SyntaxError", 2),
+    ("ReferenceError", 2),
+    ("Object", 0),
+    ("Array", 0),
+    ("Map", 0),
+    ("Set", 0),
+    ("WeakMap", 0),
+    ("WeakSet", 0),
+];
+
+/// Globals which always exist and can't be reassigned, so reading them does nothing
+const CONSTANTS: &[&str] = &["undefined", "NaN", "Infinity"];
+
+/// Globals which always exist, so reading them won't throw
+const GLOBALS: &[&str] = &[
+    "globalThis",
+    "Object",
+    "Function",
+    "Array",
+    "String",
+    "Number",
+    "Boolean",
+    "Symbol",
+    "BigInt",
+    "Math",
+    "JSON",
+    "Date",
+    "RegExp",
+    "Error",
+    "TypeError",
+    "RangeError",
+    "Map",
+    "Set",
+    "WeakMap",
+    "WeakSet",
+    "Promise",
+    "Reflect",
+    "Proxy",
+    "isNaN",
+    "isFinite",
+    "parseInt",
+    "parseFloat",
+    "encodeURI",
+    "encodeURIComponent",
+    "decodeURI",
+    "decodeURIComponent",
+];
+
+/// The effects of calling the builtin function at `path`, like `["Object", "keys"]`
+pub fn function(path: &[&str]) -> Option<Effects> {
+    let name = path.join(".");
+    FUNCTIONS
+        .iter()
+        .find(|(other, _)| *other == name)
+        .map(|(_, effects)| *effects)
+}
+
+/// The effects of `new name(...)` with `args` arguments
+pub fn constructor(name: &str, args: usize) -> Option<Effects> {
+    CONSTRUCTORS
+        .iter()
+        .any(|(other, max_args)| *other == name && args <= *max_args)
+        .then_some(PURE)
+}
+
+/// Whether the global is a constant, like `undefined`
+pub fn is_constant(name: &str) -> bool {
+    CONSTANTS.contains(&name)
+}
+
+/// Whether the global always exists
+pub fn is_global(name: &str) -> bool {
+    is_constant(name) || GLOBALS.contains(&name)
+}
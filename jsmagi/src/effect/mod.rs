@@ -1 +1,1159 @@
 //! Implementation which tracks the effects of various functions.
+//!
+//! Every function in a file that is declared once and never reassigned gets its [`Effects`]
+//! inferred from its body. Calls to other functions in the file use their inferred effects, and
+//! calls to functions like `String(x)` or `Object.keys(x)` use the table in [`builtins`].
+//! Anything else that gets called is assumed to do anything at all.
+//!
+//! ```js
+//! function add(a, b) {
+//!     return a + b;
+//! }
+//! function push(list, x) {
+//!     list.push(x);
+//! }
+//! ```
+//! `add` is pure, so a call to it can be moved or deleted. `push` calls a method that we know
+//! nothing about, so it could do anything.
+//!
+//! Property accesses are assumed to not run getters, and values that get converted to a string
+//! or number are assumed to not have their own `toString` or `valueOf`. The exception is the
+//! objects that a function creates itself, since it can see them: the bodies of their getters and
+//! setters, and of the functions it stores as their `toString` and the like, count as running
+//! in the function that creates them.
+
+use std::collections::{HashMap, HashSet};
+
+use swc_atoms::JsWord;
+use swc_ecma_ast::{
+    ArrowExpr, AssignExpr, AssignOp, BinaryOp, CallExpr, Callee, CatchClause, Class, ClassDecl,
+    ClassExpr, Expr, ExprOrSpread, FnDecl, FnExpr, ForHead, ForInStmt, ForOfStmt, Function,
+    GetterProp, Id, Ident, KeyValueProp, Lit, MemberExpr, MemberProp, MethodProp, OptChainBase,
+    Param, Pat, PatOrExpr, Prop, PropName, SetterProp, SpreadElement, Stmt, TaggedTpl, UnaryOp,
+    UpdateExpr, VarDeclarator, WithStmt,
+};
+use swc_ecma_utils::find_pat_ids;
+use swc_ecma_visit::{noop_visit_type, Visit, VisitWith};
+
+use crate::trust::{expr_path, Trust};
+
+pub mod builtins;
+
+/// What calling a function can do, besides returning a value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Effects {
+    /// It reads variables or properties which aren't its own, so it could return something
+    /// different if it is moved.
+    pub reads_globals: bool,
+    /// It assigns to variables or properties which aren't its own.
+    pub writes_globals: bool,
+    /// It reads properties of the objects passed to it.
+    pub reads_args: bool,
+    /// It changes properties of the objects passed to it.
+    pub mutates_args: bool,
+    /// It might throw an exception.
+    pub throws: bool,
+}
+impl Effects {
+    pub const PURE: Effects = Effects {
+        reads_globals: false,
+        writes_globals: false,
+        reads_args: false,
+        mutates_args: false,
+        throws: false,
+    };
+
+    /// A function that we know nothing about
+    pub const UNKNOWN: Effects = Effects {
+        reads_globals: true,
+        writes_globals: true,
+        reads_args: true,
+        mutates_args: true,
+        throws: true,
+    };
+
+    /// Whether the call only depends on the values of its arguments, and does nothing besides
+    /// return. It can be moved, deleted if its result is unused, or evaluated twice.
+    pub fn is_pure(self) -> bool {
+        self == Self::PURE
+    }
+
+    /// Whether deleting a call whose result is unused could change what the program does
+    pub fn has_side_effects(self) -> bool {
+        self.writes_globals || self.mutates_args || self.throws
+    }
+
+    fn union(self, other: Effects) -> Effects {
+        Effects {
+            reads_globals: self.reads_globals || other.reads_globals,
+            writes_globals: self.writes_globals || other.writes_globals,
+            reads_args: self.reads_args || other.reads_args,
+            mutates_args: self.mutates_args || other.mutates_args,
+            throws: self.throws || other.throws,
+        }
+    }
+}
+
+/// The effects of the functions in a program, which [`EffectAnalysis::callee`] decides from.
+#[derive(Debug, Default)]
+pub struct EffectAnalysis {
+    /// The functions which are declared once and never reassigned
+    functions: HashMap<Id, Effects>,
+    /// Every variable declared in the program
+    declared: HashSet<Id>,
+    /// The names of every variable declared in the program, which could hide a builtin
+    declared_names: HashSet<JsWord>,
+    /// Variables which are assigned to after their declaration
+    reassigned: HashSet<Id>,
+    trust: Trust,
+}
+impl EffectAnalysis {
+    pub fn analyze<N>(node: &N, trust: &Trust) -> Self
+    where
+        N: VisitWith<Bindings> + for<'a> VisitWith<Inference<'a>>,
+    {
+        let mut bindings = Bindings::default();
+        node.visit_with(&mut bindings);
+
+        let mut analysis = EffectAnalysis {
+            functions: bindings
+                .functions
+                .into_iter()
+                .filter(|id| {
+                    bindings.declared.get(id) == Some(&1) && !bindings.reassigned.contains(id)
+                })
+                .map(|id| (id, Effects::PURE))
+                .collect(),
+            declared_names: bindings.declared.keys().map(|id| id.0.clone()).collect(),
+            declared: bindings.declared.into_keys().collect(),
+            reassigned: bindings.reassigned,
+            trust: trust.clone(),
+        };
+
+        // Functions can call each other, so start by assuming that they are all pure and then
+        // add effects until nothing changes
+        loop {
+            let mut inference = Inference {
+                analysis: &analysis,
+                functions: HashMap::new(),
+            };
+            node.visit_with(&mut inference);
+            let functions = inference.functions;
+            if functions == analysis.functions {
+                break;
+            }
+            analysis.functions = functions;
+        }
+
+        analysis
+    }
+
+    /// The effects of the function declared as `id`, if it is one that could be inferred
+    pub fn function(&self, id: &Id) -> Option<Effects> {
+        self.functions.get(id).copied()
+    }
+
+    /// The effects of calling the callee, not including evaluating the callee or its arguments.
+    /// Anything that isn't a known function is [`Effects::UNKNOWN`].
+    pub fn callee(&self, callee: &Callee) -> Effects {
+        let mut effects = BodyEffects::new(self, &self.functions, Bindings::default());
+        let callee = match callee {
+            Callee::Expr(expr) => effects.callee(expr).unwrap_or(Effects::UNKNOWN),
+            Callee::Super(_) | Callee::Import(_) => Effects::UNKNOWN,
+        };
+        effects.effects.union(callee)
+    }
+
+    /// The effects of the call, if the callee is one of the functions declared in the program
+    pub fn declared_callee(&self, call: &CallExpr) -> Option<Effects> {
+        let ident = call.callee.as_expr()?.as_ident()?;
+        self.function(&ident.to_id())
+    }
+
+    /// Whether the expression is a path that the user listed as a proxy
+    pub fn is_proxy(&self, expr: &Expr) -> bool {
+        expr_path(expr).is_some_and(|path| self.trust.is_proxy(&path))
+    }
+
+    /// The effects of calling a function that isn't declared in the program, from the user's
+    /// list or the builtins like `Object.keys`
+    fn external(&self, expr: &Expr) -> Option<Effects> {
+        let path = expr_path(expr)?;
+        if self.trust.is_proxy(&path) {
+            return None;
+        }
+        if self.trust.is_pure(&path) {
+            return Some(Effects::PURE);
+        }
+
+        if self.declared_names.contains(&JsWord::from(path[0])) {
+            return None;
+        }
+        builtins::function(&path)
+    }
+}
+
+/// Collects the variables declared in a program or function, and which of them are functions.
+#[derive(Debug, Default)]
+pub struct Bindings {
+    /// How many times each variable is declared
+    declared: HashMap<Id, usize>,
+    /// Variables initialized to a function
+    functions: HashSet<Id>,
+    /// Variables initialized to a new object or array
+    fresh: HashSet<Id>,
+    reassigned: HashSet<Id>,
+}
+impl Bindings {
+    fn declare(&mut self, id: Id) {
+        *self.declared.entry(id).or_default() += 1;
+    }
+
+    fn declare_pat(&mut self, pat: &Pat) {
+        for id in find_pat_ids::<_, Id>(pat) {
+            self.declare(id);
+        }
+    }
+}
+impl Visit for Bindings {
+    noop_visit_type!();
+
+    fn visit_var_declarator(&mut self, decl: &VarDeclarator) {
+        self.declare_pat(&decl.name);
+        if let (Pat::Ident(name), Some(init)) = (&decl.name, &decl.init) {
+            match &**init {
+                Expr::Fn(_) | Expr::Arrow(_) => {
+                    self.functions.insert(name.to_id());
+                }
+                Expr::Object(_) | Expr::Array(_) => {
+                    self.fresh.insert(name.to_id());
+                }
+                _ => {}
+            }
+        }
+
+        decl.visit_children_with(self);
+    }
+
+    fn visit_fn_decl(&mut self, decl: &FnDecl) {
+        self.declare(decl.ident.to_id());
+        self.functions.insert(decl.ident.to_id());
+        decl.visit_children_with(self);
+    }
+
+    fn visit_fn_expr(&mut self, expr: &FnExpr) {
+        if let Some(ident) = &expr.ident {
+            self.declare(ident.to_id());
+        }
+        expr.visit_children_with(self);
+    }
+
+    fn visit_class_decl(&mut self, decl: &ClassDecl) {
+        self.declare(decl.ident.to_id());
+        decl.visit_children_with(self);
+    }
+
+    fn visit_class_expr(&mut self, expr: &ClassExpr) {
+        if let Some(ident) = &expr.ident {
+            self.declare(ident.to_id());
+        }
+        expr.visit_children_with(self);
+    }
+
+    fn visit_param(&mut self, param: &Param) {
+        self.declare_pat(&param.pat);
+        param.visit_children_with(self);
+    }
+
+    fn visit_arrow_expr(&mut self, arrow: &ArrowExpr) {
+        for param in &arrow.params {
+            self.declare_pat(param);
+        }
+        arrow.visit_children_with(self);
+    }
+
+    fn visit_catch_clause(&mut self, clause: &CatchClause) {
+        if let Some(param) = &clause.param {
+            self.declare_pat(param);
+        }
+        clause.visit_children_with(self);
+    }
+
+    fn visit_import_named_specifier(&mut self, spec: &swc_ecma_ast::ImportNamedSpecifier) {
+        self.declare(spec.local.to_id());
+    }
+
+    fn visit_import_default_specifier(&mut self, spec: &swc_ecma_ast::ImportDefaultSpecifier) {
+        self.declare(spec.local.to_id());
+    }
+
+    fn visit_import_star_as_specifier(&mut self, spec: &swc_ecma_ast::ImportStarAsSpecifier) {
+        self.declare(spec.local.to_id());
+    }
+
+    fn visit_assign_expr(&mut self, assign: &AssignExpr) {
+        match &assign.left {
+            PatOrExpr::Pat(pat) => self.reassigned.extend(find_pat_ids::<_, Id>(&**pat)),
+            PatOrExpr::Expr(expr) => {
+                if let Expr::Ident(ident) = &**expr {
+                    self.reassigned.insert(ident.to_id());
+                }
+            }
+        }
+        assign.visit_children_with(self);
+    }
+
+    fn visit_update_expr(&mut self, update: &UpdateExpr) {
+        if let Expr::Ident(ident) = &*update.arg {
+            self.reassigned.insert(ident.to_id());
+        }
+        update.visit_children_with(self);
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForInStmt) {
+        if let ForHead::Pat(pat) = &stmt.left {
+            self.reassigned.extend(find_pat_ids::<_, Id>(&**pat));
+        }
+        stmt.visit_children_with(self);
+    }
+
+    fn visit_for_of_stmt(&mut self, stmt: &ForOfStmt) {
+        if let ForHead::Pat(pat) = &stmt.left {
+            self.reassigned.extend(find_pat_ids::<_, Id>(&**pat));
+        }
+        stmt.visit_children_with(self);
+    }
+}
+
+/// Works out the effects of every function in the program, using the effects that the
+/// functions they call were last thought to have.
+pub struct Inference<'a> {
+    analysis: &'a EffectAnalysis,
+    functions: HashMap<Id, Effects>,
+}
+impl Inference<'_> {
+    fn infer<'p>(&mut self, id: Id, params: impl Iterator<Item = &'p Pat>, body: &dyn Body) {
+        if !self.analysis.functions.contains_key(&id) {
+            return;
+        }
+
+        let params: Vec<&Pat> = params.collect();
+        let mut bindings = Bindings::default();
+        for param in &params {
+            bindings.declare_pat(param);
+        }
+        body.visit_bindings(&mut bindings);
+
+        let mut effects = BodyEffects::new(self.analysis, &self.analysis.functions, bindings);
+        effects.params = params
+            .iter()
+            .flat_map(|param| find_pat_ids(*param))
+            .collect();
+        effects.enter(&params);
+        body.visit_effects(&mut effects);
+        self.functions.insert(id, effects.effects);
+    }
+}
+impl Visit for Inference<'_> {
+    noop_visit_type!();
+
+    fn visit_fn_decl(&mut self, decl: &FnDecl) {
+        let function = &decl.function;
+        self.infer(
+            decl.ident.to_id(),
+            function.params.iter().map(|param| &param.pat),
+            &function.body,
+        );
+        decl.visit_children_with(self);
+    }
+
+    fn visit_var_declarator(&mut self, decl: &VarDeclarator) {
+        if let (Pat::Ident(name), Some(init)) = (&decl.name, &decl.init) {
+            match &**init {
+                Expr::Fn(expr) => {
+                    let function = &expr.function;
+                    self.infer(
+                        name.to_id(),
+                        function.params.iter().map(|param| &param.pat),
+                        &function.body,
+                    );
+                }
+                Expr::Arrow(arrow) => {
+                    self.infer(name.to_id(), arrow.params.iter(), &*arrow.body);
+                }
+                _ => {}
+            }
+        }
+        decl.visit_children_with(self);
+    }
+}
+
+/// The body of a function or arrow function
+trait Body {
+    fn visit_bindings(&self, bindings: &mut Bindings);
+
+    fn visit_effects(&self, effects: &mut BodyEffects<'_>);
+}
+impl<T: for<'a> VisitWith<BodyEffects<'a>> + VisitWith<Bindings>> Body for T {
+    fn visit_bindings(&self, bindings: &mut Bindings) {
+        self.visit_with(bindings);
+    }
+
+    fn visit_effects(&self, effects: &mut BodyEffects<'_>) {
+        self.visit_with(effects);
+    }
+}
+
+/// What an expression is a property of, which decides whose state accessing it touches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Root {
+    /// An object or primitive created by this function, which nothing else can see
+    Fresh,
+    /// An argument of the function
+    Param,
+    /// A variable outside of the function, or `this`
+    Outer,
+    /// Something that could be any of the above
+    Unknown,
+}
+
+/// Finds the effects of running a function body, without running the nested functions.
+pub struct BodyEffects<'a> {
+    analysis: &'a EffectAnalysis,
+    functions: &'a HashMap<Id, Effects>,
+    /// The variables declared in the function, including its parameters
+    locals: HashSet<Id>,
+    params: HashSet<Id>,
+    /// Variables which only ever hold an object or array that the function created
+    fresh: HashSet<Id>,
+    effects: Effects,
+}
+impl<'a> BodyEffects<'a> {
+    fn new(
+        analysis: &'a EffectAnalysis,
+        functions: &'a HashMap<Id, Effects>,
+        bindings: Bindings,
+    ) -> Self {
+        Self {
+            analysis,
+            functions,
+            fresh: bindings
+                .fresh
+                .into_iter()
+                .filter(|id| !analysis.reassigned.contains(id))
+                .collect(),
+            locals: bindings.declared.into_keys().collect(),
+            params: HashSet::new(),
+            effects: Effects::PURE,
+        }
+    }
+
+    fn add(&mut self, effects: Effects) {
+        self.effects = self.effects.union(effects);
+    }
+
+    /// Binding the arguments to the parameters
+    fn enter(&mut self, params: &[&Pat]) {
+        for param in params {
+            let pat = match param {
+                Pat::Assign(assign) => {
+                    assign.right.visit_with(self);
+                    &*assign.left
+                }
+                pat => pat,
+            };
+            // Destructuring reads the properties of the argument, and throws if it is `null`
+            if !matches!(pat, Pat::Ident(_)) {
+                self.add(Effects {
+                    reads_args: true,
+                    throws: true,
+                    ..Effects::PURE
+                });
+                pat.visit_with(self);
+            }
+        }
+    }
+
+    fn root(&self, expr: &Expr, depth: usize) -> Root {
+        match expr {
+            Expr::Paren(paren) => self.root(&paren.expr, depth),
+            Expr::Ident(ident) => {
+                let id = ident.to_id();
+                if self.fresh.contains(&id) && depth == 0 {
+                    Root::Fresh
+                } else if self.params.contains(&id) || self.is_arguments(ident) {
+                    Root::Param
+                } else if self.locals.contains(&id) {
+                    Root::Unknown
+                } else {
+                    Root::Outer
+                }
+            }
+            Expr::This(_) => Root::Outer,
+            Expr::Member(member) => self.root(&member.obj, depth + 1),
+            Expr::Lit(Lit::Null(_)) => Root::Unknown,
+            Expr::Lit(_) if depth == 0 => Root::Fresh,
+            Expr::Object(_) | Expr::Array(_) if depth == 0 => Root::Fresh,
+            _ => Root::Unknown,
+        }
+    }
+
+    fn is_arguments(&self, ident: &Ident) -> bool {
+        &*ident.sym == "arguments" && !self.analysis.declared.contains(&ident.to_id())
+    }
+
+    fn read(&mut self, root: Root) {
+        self.add(match root {
+            Root::Fresh => Effects::PURE,
+            Root::Param => Effects {
+                reads_args: true,
+                throws: true,
+                ..Effects::PURE
+            },
+            Root::Outer => Effects {
+                reads_globals: true,
+                throws: true,
+                ..Effects::PURE
+            },
+            Root::Unknown => Effects {
+                reads_globals: true,
+                reads_args: true,
+                throws: true,
+                ..Effects::PURE
+            },
+        });
+    }
+
+    fn write(&mut self, root: Root) {
+        self.add(match root {
+            Root::Fresh => Effects::PURE,
+            Root::Param => Effects {
+                mutates_args: true,
+                throws: true,
+                ..Effects::PURE
+            },
+            Root::Outer => Effects {
+                writes_globals: true,
+                throws: true,
+                ..Effects::PURE
+            },
+            Root::Unknown => Effects {
+                writes_globals: true,
+                mutates_args: true,
+                throws: true,
+                ..Effects::PURE
+            },
+        });
+    }
+
+    fn read_ident(&mut self, ident: &Ident, in_typeof: bool) {
+        let id = ident.to_id();
+        if self.locals.contains(&id) || self.is_arguments(ident) {
+            return;
+        }
+
+        if self.analysis.declared.contains(&id) {
+            self.add(Effects {
+                reads_globals: true,
+                ..Effects::PURE
+            });
+        } else if !builtins::is_constant(&ident.sym) {
+            // Reading a global that doesn't exist throws, except in `typeof`
+            self.add(Effects {
+                reads_globals: true,
+                throws: !in_typeof
+                    && !builtins::is_global(&ident.sym)
+                    && !self.analysis.trust.is_plain(&[&ident.sym]),
+                ..Effects::PURE
+            });
+        }
+    }
+
+    fn write_ident(&mut self, ident: &Ident) {
+        if !self.locals.contains(&ident.to_id()) {
+            self.add(Effects {
+                writes_globals: true,
+                ..Effects::PURE
+            });
+        }
+    }
+
+    /// Assigning to the expression, which is also read if `read` is set
+    fn write_target(&mut self, expr: &Expr, read: bool) {
+        match expr {
+            Expr::Paren(paren) => self.write_target(&paren.expr, read),
+            Expr::Ident(ident) => {
+                if read {
+                    self.read_ident(ident, false);
+                }
+                self.write_ident(ident);
+            }
+            Expr::Member(member) => {
+                if self.analysis.is_proxy(&member.obj) {
+                    self.add(Effects::UNKNOWN);
+                }
+                let root = self.root(&member.obj, 0);
+                if read {
+                    self.read(root);
+                }
+                self.write(root);
+                member.obj.visit_with(self);
+                member.prop.visit_with(self);
+            }
+            _ => self.add(Effects::UNKNOWN),
+        }
+    }
+
+    fn assign(&mut self, assign: &AssignExpr) {
+        let read = assign.op != AssignOp::Assign;
+        let target = match &assign.left {
+            PatOrExpr::Expr(expr) => Some(&**expr),
+            PatOrExpr::Pat(pat) => pat.as_expr().map(|expr| &**expr),
+        };
+        if let Some(Expr::Member(member)) = target {
+            if is_implicit_member(&member.prop) {
+                self.stored(&assign.right);
+            }
+        }
+
+        match &assign.left {
+            PatOrExpr::Expr(expr) => self.write_target(expr, read),
+            PatOrExpr::Pat(pat) => match &**pat {
+                Pat::Ident(ident) => self.write_target(&Expr::Ident(ident.id.clone()), read),
+                Pat::Expr(expr) => self.write_target(expr, read),
+                // Destructuring could assign to anything
+                _ => self.add(Effects::UNKNOWN),
+            },
+        }
+        assign.right.visit_with(self);
+    }
+
+    /// The effects of calling the expression, if it is a known function.
+    /// The effects of an IIFE are added directly, since it can see this function's variables.
+    fn callee(&mut self, callee: &Expr) -> Option<Effects> {
+        match callee {
+            Expr::Paren(paren) => self.callee(&paren.expr),
+            // The user knows better than what we can infer
+            Expr::Ident(ident) => self.analysis.external(callee).or_else(|| {
+                let effects = self.functions.get(&ident.to_id()).copied();
+                effects.filter(|_| !self.analysis.is_proxy(callee))
+            }),
+            Expr::Member(_) => self.analysis.external(callee),
+            // An IIFE runs its body right away
+            Expr::Fn(expr) => {
+                let params: Vec<&Pat> = expr
+                    .function
+                    .params
+                    .iter()
+                    .map(|param| &param.pat)
+                    .collect();
+                self.nested(&params, &expr.function.body);
+                Some(Effects::PURE)
+            }
+            Expr::Arrow(arrow) => {
+                let params: Vec<&Pat> = arrow.params.iter().collect();
+                self.nested(&params, &*arrow.body);
+                Some(Effects::PURE)
+            }
+            _ => None,
+        }
+    }
+
+    /// Add the effects of running a nested function's body, from the point of view of this
+    /// function
+    fn nested(&mut self, params: &[&Pat], body: &dyn Body) {
+        let mut bindings = Bindings::default();
+        for param in params {
+            bindings.declare_pat(param);
+        }
+        body.visit_bindings(&mut bindings);
+
+        let mut nested = BodyEffects::new(self.analysis, self.functions, bindings);
+        nested.locals.extend(self.locals.iter().cloned());
+        nested.fresh.extend(self.fresh.iter().cloned());
+        // Its own parameters are left as locals, since they could hold anything
+        nested.params = self.params.clone();
+        nested.enter(params);
+        body.visit_effects(&mut nested);
+        self.add(nested.effects);
+    }
+
+    /// Storing the value as a method that could be called without being called directly, like
+    /// `toString`. Its effects are counted right away, as the object could be converted anywhere
+    /// after this.
+    fn stored(&mut self, value: &Expr) {
+        let effects = self.callee(value).unwrap_or(Effects::UNKNOWN);
+        self.add(effects);
+    }
+
+    /// A call to `callee`, which is `None` for `super()` and `import()`
+    fn call(&mut self, callee: Option<&Expr>, args: &[ExprOrSpread]) {
+        let effects = match callee {
+            Some(expr) => {
+                let effects = self.callee(expr);
+                if effects.is_none() {
+                    expr.visit_with(self);
+                    // A method could change the object it is called on
+                    if let Expr::Member(member) = expr {
+                        let root = self.root(&member.obj, 1);
+                        self.read(root);
+                        self.write(root);
+                    }
+                }
+                effects.unwrap_or(Effects::UNKNOWN)
+            }
+            None => Effects::UNKNOWN,
+        };
+        self.add(Effects {
+            reads_args: false,
+            mutates_args: false,
+            ..effects
+        });
+
+        for arg in args {
+            arg.visit_with(self);
+
+            // The argument's properties can be seen by the function
+            let root = match &*arg.expr {
+                Expr::Lit(Lit::Null(_)) => Root::Unknown,
+                Expr::Lit(_) => Root::Fresh,
+                Expr::Object(object) if object.props.is_empty() => Root::Fresh,
+                Expr::Array(array) if array.elems.is_empty() => Root::Fresh,
+                expr => self.root(expr, 1),
+            };
+            if effects.reads_args {
+                self.read(root);
+            }
+            if effects.mutates_args {
+                self.write(root);
+            }
+        }
+    }
+}
+impl Visit for BodyEffects<'_> {
+    noop_visit_type!();
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Ident(ident) => self.read_ident(ident, false),
+            Expr::Assign(assign) => self.assign(assign),
+            Expr::Update(update) => self.write_target(&update.arg, true),
+            Expr::Unary(unary) => match (unary.op, &*unary.arg) {
+                (UnaryOp::Delete, arg) => self.write_target(arg, false),
+                (UnaryOp::TypeOf, Expr::Ident(ident)) => self.read_ident(ident, true),
+                _ => unary.visit_children_with(self),
+            },
+            Expr::Bin(bin) => {
+                // These throw if the right side isn't an object
+                if matches!(bin.op, BinaryOp::In | BinaryOp::InstanceOf) {
+                    self.add(Effects {
+                        throws: true,
+                        ..Effects::PURE
+                    });
+                }
+                bin.visit_children_with(self);
+            }
+            Expr::Call(call) => self.call(call.callee.as_expr().map(|expr| &**expr), &call.args),
+            Expr::OptChain(opt) => match &*opt.base {
+                OptChainBase::Member(member) => member.visit_with(self),
+                OptChainBase::Call(call) => self.call(Some(&call.callee), &call.args),
+            },
+            Expr::New(new) => {
+                let args = new.args.as_deref().unwrap_or_default();
+                let known = match &*new.callee {
+                    Expr::Ident(ident) if !self.analysis.declared_names.contains(&ident.sym) => {
+                        builtins::constructor(&ident.sym, args.len())
+                    }
+                    _ => None,
+                };
+                self.add(known.unwrap_or(Effects::UNKNOWN));
+                args.visit_with(self);
+            }
+            _ => expr.visit_children_with(self),
+        }
+    }
+
+    fn visit_prop(&mut self, prop: &Prop) {
+        match prop {
+            Prop::Shorthand(ident) => {
+                self.read_ident(ident, false);
+                if is_implicit_name(&ident.sym) {
+                    self.stored(&Expr::Ident(ident.clone()));
+                }
+            }
+            Prop::KeyValue(KeyValueProp { key, value }) if is_implicit_key(key) => {
+                prop.visit_children_with(self);
+                self.stored(value);
+            }
+            Prop::Method(MethodProp { key, function }) if is_implicit_key(key) => {
+                key.visit_with(self);
+                let params: Vec<&Pat> = function.params.iter().map(|param| &param.pat).collect();
+                self.nested(&params, &function.body);
+            }
+            _ => prop.visit_children_with(self),
+        }
+    }
+
+    fn visit_member_expr(&mut self, member: &MemberExpr) {
+        if self.analysis.is_proxy(&member.obj) {
+            self.add(Effects::UNKNOWN);
+        }
+        let root = self.root(&member.obj, 0);
+        self.read(root);
+        member.visit_children_with(self);
+    }
+
+    fn visit_member_prop(&mut self, prop: &MemberProp) {
+        if let MemberProp::Computed(computed) = prop {
+            computed.visit_with(self);
+        }
+    }
+
+    fn visit_spread_element(&mut self, spread: &SpreadElement) {
+        let root = self.root(&spread.expr, 0);
+        self.read(root);
+        spread.visit_children_with(self);
+    }
+
+    fn visit_expr_or_spread(&mut self, arg: &ExprOrSpread) {
+        // Spreading into an array or arguments runs the value's iterator
+        if arg.spread.is_some() {
+            self.add(Effects::UNKNOWN);
+        }
+        arg.visit_children_with(self);
+    }
+
+    fn visit_var_declarator(&mut self, decl: &VarDeclarator) {
+        if !matches!(decl.name, Pat::Ident(_)) {
+            self.add(Effects::UNKNOWN);
+        }
+        decl.init.visit_with(self);
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        if let Stmt::Throw(_) = stmt {
+            self.add(Effects {
+                throws: true,
+                ..Effects::PURE
+            });
+        }
+        stmt.visit_children_with(self);
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForInStmt) {
+        match &stmt.left {
+            ForHead::VarDecl(decl) => decl.visit_with(self),
+            ForHead::UsingDecl(_) => self.add(Effects::UNKNOWN),
+            ForHead::Pat(pat) => match &**pat {
+                Pat::Ident(ident) => self.write_ident(&ident.id),
+                Pat::Expr(expr) => self.write_target(expr, false),
+                _ => self.add(Effects::UNKNOWN),
+            },
+        }
+        let root = self.root(&stmt.right, 0);
+        self.read(root);
+        stmt.right.visit_with(self);
+        stmt.body.visit_with(self);
+    }
+
+    // These can run code that we don't know about
+    fn visit_for_of_stmt(&mut self, _: &ForOfStmt) {
+        self.add(Effects::UNKNOWN);
+    }
+
+    fn visit_with_stmt(&mut self, _: &WithStmt) {
+        self.add(Effects::UNKNOWN);
+    }
+
+    fn visit_tagged_tpl(&mut self, _: &TaggedTpl) {
+        self.add(Effects::UNKNOWN);
+    }
+
+    fn visit_await_expr(&mut self, _: &swc_ecma_ast::AwaitExpr) {
+        self.add(Effects::UNKNOWN);
+    }
+
+    fn visit_yield_expr(&mut self, _: &swc_ecma_ast::YieldExpr) {
+        self.add(Effects::UNKNOWN);
+    }
+
+    fn visit_class(&mut self, _: &Class) {
+        self.add(Effects::UNKNOWN);
+    }
+
+    // Creating a function does nothing until it is called
+    fn visit_function(&mut self, _: &Function) {}
+
+    fn visit_arrow_expr(&mut self, _: &ArrowExpr) {}
+
+    // Accessors run whenever the property is used, so they count as running right away
+    fn visit_getter_prop(&mut self, prop: &GetterProp) {
+        prop.key.visit_with(self);
+        self.nested(&[], &prop.body);
+    }
+
+    fn visit_setter_prop(&mut self, prop: &SetterProp) {
+        prop.key.visit_with(self);
+        self.nested(&[&prop.param], &prop.body);
+    }
+}
+
+/// Whether a method with this name can be called without being called directly, like `toString`
+/// when the object is converted to a string
+fn is_implicit_name(name: &str) -> bool {
+    matches!(name, "toString" | "valueOf" | "toJSON")
+}
+
+/// Whether the key is an [implicit name](is_implicit_name) or a symbol like `Symbol.toPrimitive`
+fn is_implicit_key(key: &PropName) -> bool {
+    match key {
+        PropName::Ident(ident) => is_implicit_name(&ident.sym),
+        PropName::Str(str) => is_implicit_name(&str.value),
+        PropName::Num(_) | PropName::BigInt(_) => false,
+        PropName::Computed(computed) => is_implicit_expr(&computed.expr),
+    }
+}
+
+fn is_implicit_member(prop: &MemberProp) -> bool {
+    match prop {
+        MemberProp::Ident(ident) => is_implicit_name(&ident.sym),
+        MemberProp::Computed(computed) => is_implicit_expr(&computed.expr),
+        MemberProp::PrivateName(_) => false,
+    }
+}
+
+fn is_implicit_expr(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(Lit::Str(str)) => is_implicit_name(&str.value),
+        expr => expr_path(expr).is_some_and(|path| path[0] == "Symbol"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_common::{sync::Lrc, FileName, SourceMap};
+    use swc_ecma_parser::{parse_file_as_module, EsConfig, Syntax};
+
+    use super::{EffectAnalysis, Effects};
+    use crate::trust::Trust;
+
+    fn effects(code: &str, name: &str) -> Option<Effects> {
+        effects_with(code, name, &Trust::default())
+    }
+
+    fn effects_with(code: &str, name: &str, trust: &Trust) -> Option<Effects> {
+        let cm: Lrc<SourceMap> = Default::default();
+        let fm = cm.new_source_file(FileName::Anon, code.to_owned());
+        let module = parse_file_as_module(
+            &fm,
+            Syntax::Es(EsConfig::default()),
+            Default::default(),
+            None,
+            &mut vec![],
+        )
+        .unwrap();
+
+        let analysis = EffectAnalysis::analyze(&module, trust);
+        analysis
+            .functions
+            .iter()
+            .find(|(id, _)| &*id.0 == name)
+            .map(|(_, effects)| *effects)
+    }
+
+    #[test]
+    fn test_pure() {
+        let pure = Some(Effects::PURE);
+        assert_eq!(effects("function f(a, b) { return a + b; }", "f"), pure);
+        assert_eq!(
+            effects("var f = (a) => String(a) + Math.max(a, 1);", "f"),
+            pure
+        );
+        assert_eq!(
+            effects("function f(a) { var o = {}; o.a = a; return o; }", "f"),
+            pure
+        );
+        assert_eq!(
+            effects(
+                "function f(a) { return g(a) * 2; } function g(a) { return -a; }",
+                "f"
+            ),
+            pure
+        );
+        assert_eq!(
+            effects(
+                "function f(a) { return typeof a === 'undefined' ? undefined : a; }",
+                "f"
+            ),
+            pure
+        );
+        assert_eq!(effects("function f(a) { return f(a - 1); }", "f"), pure);
+    }
+
+    #[test]
+    fn test_effects() {
+        assert_eq!(
+            effects("function f(a) { return a.length; }", "f"),
+            Some(Effects {
+                reads_args: true,
+                throws: true,
+                ..Effects::PURE
+            })
+        );
+        assert_eq!(
+            effects("function f(a) { a.x = 1; }", "f"),
+            Some(Effects {
+                mutates_args: true,
+                throws: true,
+                ..Effects::PURE
+            })
+        );
+        assert_eq!(
+            effects("var n = 0; function f() { n++; }", "f"),
+            Some(Effects {
+                reads_globals: true,
+                writes_globals: true,
+                ..Effects::PURE
+            })
+        );
+        assert_eq!(
+            effects("function f(a) { if (!a) throw new Error(); }", "f"),
+            Some(Effects {
+                throws: true,
+                ..Effects::PURE
+            })
+        );
+        assert_eq!(
+            effects("function f(a) { if (!a) throw 'bad'; }", "f"),
+            Some(Effects {
+                throws: true,
+                ..Effects::PURE
+            })
+        );
+        // The effects of the functions that it calls are included
+        assert_eq!(
+            effects("function f(a) { g(a); } function g(b) { b.x = 1; }", "f"),
+            Some(Effects {
+                mutates_args: true,
+                throws: true,
+                ..Effects::PURE
+            })
+        );
+        assert_eq!(
+            effects("function f() { g({}); } function g(b) { b.x = 1; }", "f"),
+            Some(Effects {
+                throws: true,
+                ..Effects::PURE
+            })
+        );
+        assert_eq!(
+            effects("function f() { return Object.keys(window); }", "f"),
+            Some(Effects {
+                reads_globals: true,
+                reads_args: false,
+                throws: true,
+                ..Effects::PURE
+            })
+        );
+        assert_eq!(
+            effects("function f(a) { return a.push(1); }", "f"),
+            Some(Effects::UNKNOWN)
+        );
+        assert_eq!(
+            effects(
+                "function f(a) { return (function() { return a + 1; })(); }",
+                "f"
+            ),
+            Some(Effects::PURE)
+        );
+    }
+
+    #[test]
+    fn test_own_objects() {
+        // The getter and `toString` run when `o` is used
+        let writes = Some(Effects {
+            writes_globals: true,
+            ..Effects::PURE
+        });
+        assert_eq!(
+            effects(
+                "function f() { var o = { get x() { g = 1; return 2; } }; return o.x; }",
+                "f"
+            ),
+            writes
+        );
+        assert_eq!(
+            effects(
+                "function h() { var o = {}; o.toString = function() { g = 2; }; return '' + o; }",
+                "h"
+            ),
+            writes
+        );
+        assert_eq!(
+            effects(
+                "function f() { var o = { valueOf() { g = 3; } }; return +o; }",
+                "f"
+            ),
+            writes
+        );
+        assert_eq!(
+            effects(
+                "function f(a) { var o = {}; o[Symbol.toPrimitive] = a; return o; }",
+                "f"
+            ),
+            Some(Effects::UNKNOWN)
+        );
+        // Other methods only run when they are called
+        assert_eq!(
+            effects(
+                "function f() { return { x() { g = 1; }, get y() { return 1; } }; }",
+                "f"
+            ),
+            Some(Effects::PURE)
+        );
+    }
+
+    #[test]
+    fn test_unknown() {
+        // Shadowed builtins aren't known
+        assert_eq!(
+            effects("var String = g; function f(a) { return String(a); }", "f"),
+            Some(Effects::UNKNOWN)
+        );
+        // Functions that can be replaced aren't inferred
+        assert_eq!(effects("function f() {} f = g;", "f"), None);
+        assert_eq!(effects("var f = function() {}; var f;", "f"), None);
+    }
+
+    #[test]
+    fn test_trust() {
+        let trust = Trust {
+            pure: vec!["n.d".into(), "log".into()],
+            plain: vec!["config".into()],
+            proxies: vec!["store".into()],
+        };
+        assert_eq!(
+            effects_with("function f(a) { return n.d(a, 1); }", "f", &trust),
+            Some(Effects::PURE)
+        );
+        assert_eq!(
+            effects_with(
+                "function log(a) { console.log(a); } function f(a) { log(a); }",
+                "f",
+                &trust
+            ),
+            Some(Effects::PURE)
+        );
+        assert_eq!(
+            effects_with("function f() { return config; }", "f", &trust),
+            Some(Effects {
+                reads_globals: true,
+                ..Effects::PURE
+            })
+        );
+        assert_eq!(
+            effects_with("function f() { return store.count; }", "f", &trust),
+            Some(Effects::UNKNOWN)
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    fmt, io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use swc_common::{
+    errors::{DiagnosticBuilder, Emitter, Level},
+    SourceMap, Span,
+};
+
+#[derive(Debug)]
+pub enum MagiError {
+    /// Reading the input or writing the output failed
+    Io {
+        /// The file being read or written, if it was a file rather than stdin/stdout
+        path: Option<PathBuf>,
+        error: io::Error,
+    },
+    /// The input is not valid JavaScript
+    Parse {
+        name: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+    /// Something went wrong while running the passes or generating the output code.
+    /// This is a bug in jsmagi.
+    Transform { name: String, message: String },
+    /// A configuration file, like the one for [`crate::trust::Trust`], is not valid
+    Config { path: PathBuf, message: String },
+}
+impl MagiError {
+    pub fn io(path: impl Into<PathBuf>, error: io::Error) -> MagiError {
+        MagiError::Io {
+            path: Some(path.into()),
+            error,
+        }
+    }
+
+    /// The exit code that the command line uses for this kind of error.
+    /// These follow the BSD `sysexits.h` codes.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // EX_IOERR
+            MagiError::Io { .. } => 74,
+            // EX_DATAERR
+            MagiError::Parse { .. } => 65,
+            // EX_SOFTWARE
+            MagiError::Transform { .. } => 70,
+            // EX_CONFIG
+            MagiError::Config { .. } => 78,
+        }
+    }
+}
+impl fmt::Display for MagiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MagiError::Io {
+                path: Some(path),
+                error,
+            } => write!(f, "error: {}: {}", path.display(), error),
+            MagiError::Io { path: None, error } => write!(f, "error: {}", error),
+            MagiError::Parse { name, diagnostics } => {
+                for diagnostic in diagnostics {
+                    writeln!(f, "{}", diagnostic)?;
+                }
+                write!(f, "error: could not parse `{}`", name)
+            }
+            MagiError::Transform { name, message } => {
+                write!(f, "error: failed to transform `{}`: {}", name, message)
+            }
+            MagiError::Config { path, message } => {
+                write!(f, "error: invalid config `{}`: {}", path.display(), message)
+            }
+        }
+    }
+}
+impl std::error::Error for MagiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MagiError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// An error or warning about the input, with its location resolved.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    pub file: String,
+    /// 1-based line number
+    pub line: usize,
+    /// 0-based column
+    pub column: usize,
+    /// The text of the line that the error is on
+    pub source_line: String,
+}
+impl Diagnostic {
+    pub(crate) fn new(
+        source_map: &SourceMap,
+        file: &str,
+        severity: Severity,
+        message: String,
+        span: Span,
+    ) -> Self {
+        let loc = source_map.lookup_char_pos(span.lo);
+        let source_line = loc
+            .file
+            .get_line(loc.line - 1)
+            .map(|line| line.into_owned())
+            .unwrap_or_default();
+
+        Diagnostic {
+            severity,
+            message,
+            span,
+            file: file.to_owned(),
+            line: loc.line,
+            column: loc.col_display,
+            source_line,
+        }
+    }
+}
+impl fmt::Display for Diagnostic {
+    /// Formatted like rustc's diagnostics
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line_no = self.line.to_string();
+        let pad = " ".repeat(line_no.len());
+        writeln!(f, "{}: {}", self.severity, self.message)?;
+        writeln!(
+            f,
+            "{}--> {}:{}:{}",
+            pad,
+            self.file,
+            self.line,
+            self.column + 1
+        )?;
+        writeln!(f, "{} |", pad)?;
+        writeln!(f, "{} | {}", line_no, self.source_line)?;
+        writeln!(f, "{} | {}^", pad, " ".repeat(self.column))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Collects the errors and warnings that swc and the passes report, so that they can be returned
+/// rather than printed.
+#[derive(Clone, Default)]
+pub(crate) struct CollectingEmitter {
+    pub(crate) errors: Arc<Mutex<Vec<(String, Span)>>>,
+    pub(crate) warnings: Arc<Mutex<Vec<(String, Span)>>>,
+}
+impl Emitter for CollectingEmitter {
+    fn emit(&mut self, db: &DiagnosticBuilder<'_>) {
+        let span = db.span.primary_span().unwrap_or_default();
+        match db.level {
+            Level::Bug | Level::Fatal | Level::PhaseFatal | Level::Error => {
+                self.errors.lock().unwrap().push((db.message(), span));
+            }
+            Level::Warning => {
+                self.warnings.lock().unwrap().push((db.message(), span));
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,5 +1,8 @@
-// This is from SWC, and so is under their license.
+// `contains_eval` and `EvalFinder` are from SWC, and so are under their license.
+use std::{fmt, str::FromStr};
+
 use swc_atoms::js_word;
+use swc_common::{errors::HANDLER, Span};
 use swc_ecma_ast::*;
 use swc_ecma_visit::{noop_visit_type, visit_obj_and_computed, Visit, VisitWith};
 
@@ -35,10 +38,11 @@ impl Visit for EvalFinder {
     fn visit_callee(&mut self, c: &Callee) {
         c.visit_children_with(self);
 
-        let eval = js_word!("eval");
         if let Callee::Expr(e) = c {
-            if let Expr::Ident(Ident { sym: eval, .. }) = &**e {
-                self.found = true
+            if let Expr::Ident(Ident { sym, .. }) = &**e {
+                if *sym == js_word!("eval") {
+                    self.found = true
+                }
             }
         }
     }
@@ -65,3 +69,306 @@ impl Visit for EvalFinder {
         }
     }
 }
+
+/// How careful the passes are with variables that an `eval` or `with` might use.  
+/// Those can refer to a variable by its name, so moving or renaming it can break the code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvalSafety {
+    /// Transform the code as if there was no `eval` or `with`
+    Ignore,
+    /// Transform the code, but warn about each change made in a scope that uses `eval` or `with`
+    Warn,
+    /// Don't move or rename the variables of a scope that uses `eval` or `with`
+    #[default]
+    Strict,
+}
+impl EvalSafety {
+    pub const ALL: &'static [EvalSafety] =
+        &[EvalSafety::Ignore, EvalSafety::Warn, EvalSafety::Strict];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EvalSafety::Ignore => "ignore",
+            EvalSafety::Warn => "warn",
+            EvalSafety::Strict => "strict",
+        }
+    }
+}
+impl fmt::Display for EvalSafety {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+impl FromStr for EvalSafety {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EvalSafety::ALL
+            .iter()
+            .copied()
+            .find(|safety| safety.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = EvalSafety::ALL.iter().map(|x| x.name()).collect();
+                format!(
+                    "unknown eval safety `{}`, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Report a warning about the input, if there is a handler to report it to.
+pub(crate) fn warn(span: Span, message: &str) {
+    if HANDLER.is_set() {
+        HANDLER.with(|handler| handler.struct_span_warn(span, message).emit());
+    }
+}
+
+/// Keeps track of whether the scope being visited is eval-tainted, for the passes that move or
+/// rename variables. See [`track_eval_scopes`].  
+/// A function is tainted when it contains a direct `eval` or a `with`, including inside of its
+/// nested functions, since those can see all of its variables.
+#[derive(Default)]
+pub(crate) struct EvalGuard {
+    safety: EvalSafety,
+    /// Whether each of the scopes being visited is tainted, innermost last
+    scopes: Vec<bool>,
+}
+impl EvalGuard {
+    pub(crate) fn new(safety: EvalSafety) -> Self {
+        Self {
+            safety,
+            scopes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn enter<N>(&mut self, node: &N)
+    where
+        N: VisitWith<EvalFinder>,
+    {
+        let tainted = self.safety != EvalSafety::Ignore && contains_eval(node, true);
+        self.scopes.push(tainted);
+    }
+
+    pub(crate) fn exit(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Whether [`EvalGuard::allows`] would refuse every change to the current scope, for passes
+    /// that have to check before doing any work. This doesn't warn.
+    pub(crate) fn forbids(&self) -> bool {
+        self.safety == EvalSafety::Strict && self.scopes.last() == Some(&true)
+    }
+
+    /// Whether a pass may move or rename the variables of the current scope.
+    /// `change` describes what the pass does, for the warning.
+    pub(crate) fn allows(&self, span: Span, change: &str) -> bool {
+        if self.scopes.last() != Some(&true) {
+            return true;
+        }
+
+        match self.safety {
+            EvalSafety::Ignore => true,
+            EvalSafety::Warn => {
+                warn(
+                    span,
+                    &format!("{} in a scope that uses `eval` or `with`", change),
+                );
+                true
+            }
+            EvalSafety::Strict => false,
+        }
+    }
+}
+
+/// Implements the `VisitMut` methods that keep an [`EvalGuard`] field up to date with the
+/// scope being visited.
+/// The optional block is run with the module or script before it is visited, for passes that
+/// need to look over the whole program first.
+macro_rules! track_eval_scopes {
+    ($guard:ident) => {
+        $crate::eval::track_eval_scopes!($guard, |_this, _program| {});
+    };
+    ($guard:ident, |$this:ident, $program:ident| $on_program:block) => {
+        fn visit_mut_module(&mut self, m: &mut swc_ecma_ast::Module) {
+            {
+                let $this = &mut *self;
+                let $program = &*m;
+                $on_program
+            }
+            self.$guard.enter(m);
+            m.visit_mut_children_with(self);
+            self.$guard.exit();
+        }
+
+        fn visit_mut_script(&mut self, s: &mut swc_ecma_ast::Script) {
+            {
+                let $this = &mut *self;
+                let $program = &*s;
+                $on_program
+            }
+            self.$guard.enter(s);
+            s.visit_mut_children_with(self);
+            self.$guard.exit();
+        }
+
+        fn visit_mut_function(&mut self, f: &mut swc_ecma_ast::Function) {
+            self.$guard.enter(f);
+            f.visit_mut_children_with(self);
+            self.$guard.exit();
+        }
+
+        fn visit_mut_arrow_expr(&mut self, a: &mut swc_ecma_ast::ArrowExpr) {
+            self.$guard.enter(a);
+            a.visit_mut_children_with(self);
+            self.$guard.exit();
+        }
+    };
+}
+pub(crate) use track_eval_scopes;
+
+/// Find the scopes that use `eval` or `with`, along with the variables that they declare.
+pub(crate) fn tainted_scopes<N>(node: &N) -> Vec<TaintedScope>
+where
+    N: VisitWith<EvalScopes>,
+{
+    let mut scopes = EvalScopes::default();
+    node.visit_with(&mut scopes);
+    scopes.tainted
+}
+
+/// A function, or the top level of the program, that uses `eval` or `with` somewhere inside it
+pub(crate) struct TaintedScope {
+    pub(crate) span: Span,
+    /// The variables declared directly in this scope
+    pub(crate) bindings: Vec<Id>,
+}
+
+/// Finds the scopes that use `eval` or `with`, and the variables that they declare.  
+/// A scope is tainted by an `eval` in any of its nested functions as well, since the `eval` can
+/// see all of the variables in the scopes around it.
+#[derive(Default)]
+pub(crate) struct EvalScopes {
+    tainted: Vec<TaintedScope>,
+    /// The index in `tainted` of the scope that is being visited, if it is tainted
+    current: Option<usize>,
+}
+impl EvalScopes {
+    fn enter<N>(&mut self, span: Span, node: &N)
+    where
+        N: VisitWith<EvalFinder> + VisitWith<Self>,
+    {
+        let outer = self.current;
+        self.current = if contains_eval(node, true) {
+            self.tainted.push(TaintedScope {
+                span,
+                bindings: Vec::new(),
+            });
+            Some(self.tainted.len() - 1)
+        } else {
+            None
+        };
+
+        node.visit_children_with(self);
+        self.current = outer;
+    }
+
+    fn declare(&mut self, ident: &Ident) {
+        if let Some(idx) = self.current {
+            self.tainted[idx].bindings.push(ident.to_id());
+        }
+    }
+}
+impl Visit for EvalScopes {
+    noop_visit_type!();
+
+    fn visit_module(&mut self, m: &Module) {
+        self.enter(m.span, m);
+    }
+
+    fn visit_script(&mut self, s: &Script) {
+        self.enter(s.span, s);
+    }
+
+    fn visit_function(&mut self, f: &Function) {
+        self.enter(f.span, f);
+    }
+
+    fn visit_arrow_expr(&mut self, a: &ArrowExpr) {
+        self.enter(a.span, a);
+    }
+
+    fn visit_binding_ident(&mut self, i: &BindingIdent) {
+        self.declare(&i.id);
+    }
+
+    // The names of function and class expressions are only visible inside of them, but putting
+    // them in the outer scope is fine, as it is tainted whenever the inner one is.
+    fn visit_fn_expr(&mut self, f: &FnExpr) {
+        if let Some(ident) = &f.ident {
+            self.declare(ident);
+        }
+        f.function.visit_with(self);
+    }
+
+    fn visit_fn_decl(&mut self, f: &FnDecl) {
+        self.declare(&f.ident);
+        f.function.visit_with(self);
+    }
+
+    fn visit_class_expr(&mut self, c: &ClassExpr) {
+        if let Some(ident) = &c.ident {
+            self.declare(ident);
+        }
+        c.class.visit_with(self);
+    }
+
+    fn visit_class_decl(&mut self, c: &ClassDecl) {
+        self.declare(&c.ident);
+        c.class.visit_with(self);
+    }
+
+    fn visit_import_default_specifier(&mut self, s: &ImportDefaultSpecifier) {
+        self.declare(&s.local);
+    }
+
+    fn visit_import_named_specifier(&mut self, s: &ImportNamedSpecifier) {
+        self.declare(&s.local);
+    }
+
+    fn visit_import_star_as_specifier(&mut self, s: &ImportStarAsSpecifier) {
+        self.declare(&s.local);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_common::{sync::Lrc, FileName, SourceMap};
+    use swc_ecma_parser::{parse_file_as_module, EsConfig, Syntax};
+
+    use super::contains_eval;
+
+    fn has_eval(code: &str) -> bool {
+        let cm: Lrc<SourceMap> = Default::default();
+        let fm = cm.new_source_file(FileName::Anon, code.to_owned());
+        let module = parse_file_as_module(
+            &fm,
+            Syntax::Es(EsConfig::default()),
+            Default::default(),
+            None,
+            &mut vec![],
+        )
+        .unwrap();
+        contains_eval(&module, true)
+    }
+
+    #[test]
+    fn test_contains_eval() {
+        assert!(has_eval("eval(a);"));
+        assert!(has_eval("function f() { return eval(a); }"));
+        // Every call used to count as `eval`
+        assert!(!has_eval("foo();"));
+        assert!(!has_eval("a.eval(b);"));
+    }
+}
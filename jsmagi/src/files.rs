@@ -0,0 +1,245 @@
+//! Collecting the input files for a transform session, and deciding where their output goes.
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Extensions of the files that are picked up when a directory is given as input.
+const JS_EXTENSIONS: &[&str] = &["js", "mjs", "cjs", "jsx"];
+
+/// A file to transform, along with its path relative to the root of the input tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputFile {
+    pub path: PathBuf,
+    pub relative: PathBuf,
+}
+
+/// Expand the paths given by the user into the list of files to transform.
+/// - A file is used as-is.
+/// - A directory is walked recursively for JavaScript files.
+/// - Anything else is treated as a glob pattern, for shells that don't expand them.
+///
+/// The relative paths are relative to the deepest directory containing every input, so that
+/// the output tree mirrors the input tree.
+pub fn collect_inputs(paths: &[PathBuf]) -> io::Result<Vec<InputFile>> {
+    // (root that the file should be relative to, file)
+    let mut files: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut found = Vec::new();
+            walk_dir(path, &mut found)?;
+            found.sort();
+            files.extend(found.into_iter().map(|file| (path.clone(), file)));
+        } else if path.is_file() {
+            let parent = path.parent().map(ToOwned::to_owned).unwrap_or_default();
+            files.push((parent, path.clone()));
+        } else {
+            let pattern = path.to_string_lossy();
+            let entries = glob::glob(&pattern)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            let mut found = Vec::new();
+            for entry in entries {
+                let entry = entry.map_err(glob::GlobError::into_error)?;
+                if entry.is_file() {
+                    found.push(entry);
+                }
+            }
+
+            if found.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no input files found at `{}`", path.display()),
+                ));
+            }
+
+            for file in found {
+                let parent = file.parent().map(ToOwned::to_owned).unwrap_or_default();
+                files.push((parent, file));
+            }
+        }
+    }
+
+    let Some(base) = common_base(files.iter().map(|(root, _)| root.as_path())) else {
+        return Ok(Vec::new());
+    };
+
+    let mut inputs: Vec<InputFile> = Vec::with_capacity(files.len());
+    for (_, path) in files {
+        if inputs.iter().any(|input| input.path == path) {
+            continue;
+        }
+
+        let relative = path
+            .strip_prefix(&base)
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|_| PathBuf::from(path.file_name().unwrap_or_default()));
+        inputs.push(InputFile { path, relative });
+    }
+
+    Ok(inputs)
+}
+
+fn walk_dir(dir: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk_dir(&path, found)?;
+        } else if is_js_file(&path) {
+            found.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn is_js_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| JS_EXTENSIONS.contains(&ext))
+}
+
+/// The longest path that is a prefix of all the given paths
+fn common_base<'a>(mut paths: impl Iterator<Item = &'a Path>) -> Option<PathBuf> {
+    let mut base = paths.next()?.to_owned();
+    for path in paths {
+        while !path.starts_with(&base) {
+            if !base.pop() {
+                return Some(PathBuf::new());
+            }
+        }
+    }
+
+    Some(base)
+}
+
+/// Find the source map that belongs to the input file at `path`, with contents `code`.
+/// This uses the `sourceMappingURL` comment if it points to a file, and otherwise looks for
+/// `<file>.map` next to the input.
+/// Inline source maps are left for swc to pick up from the code itself.
+pub fn find_input_source_map(path: &Path, code: &str) -> Option<PathBuf> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    const URL_MARKER: &str = "sourceMappingURL=";
+    if let Some(idx) = code.rfind(URL_MARKER) {
+        let url = code[idx + URL_MARKER.len()..]
+            .split_whitespace()
+            .next()
+            .unwrap_or_default();
+        if url.starts_with("data:") {
+            return None;
+        }
+
+        let map_path = dir.join(url);
+        if !url.is_empty() && map_path.is_file() {
+            return Some(map_path);
+        }
+    }
+
+    let mut map_path = path.as_os_str().to_owned();
+    map_path.push(".map");
+    let map_path = PathBuf::from(map_path);
+    map_path.is_file().then_some(map_path)
+}
+
+/// The path that the transformed version of `input` is written to, inside of `out_dir`.
+pub fn output_path(out_dir: &Path, input: &InputFile, typescript: bool) -> PathBuf {
+    let mut path = out_dir.join(&input.relative);
+    path.set_extension(if typescript { "ts" } else { "js" });
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{collect_inputs, common_base, find_input_source_map, output_path, InputFile};
+
+    #[test]
+    fn test_common_base() {
+        let paths = [
+            Path::new("dist/chunks/a"),
+            Path::new("dist/chunks"),
+            Path::new("dist/b/c"),
+        ];
+        assert_eq!(common_base(paths.into_iter()), Some(PathBuf::from("dist")));
+        assert_eq!(
+            common_base([Path::new("a"), Path::new("b")].into_iter()),
+            Some(PathBuf::new())
+        );
+        assert_eq!(common_base(std::iter::empty()), None);
+    }
+
+    #[test]
+    fn test_collect_inputs_mirrors_tree() {
+        let dir = std::env::temp_dir().join(format!("jsmagi-files-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("chunks")).unwrap();
+        std::fs::write(dir.join("main.js"), "a").unwrap();
+        std::fs::write(dir.join("chunks/1.js"), "b").unwrap();
+        std::fs::write(dir.join("chunks/notes.txt"), "c").unwrap();
+
+        let inputs = collect_inputs(std::slice::from_ref(&dir)).unwrap();
+        let relative: Vec<&Path> = inputs.iter().map(|x| x.relative.as_path()).collect();
+        assert_eq!(
+            relative,
+            vec![Path::new("chunks/1.js"), Path::new("main.js")]
+        );
+
+        // Files given directly are relative to the directory that contains all of them
+        let inputs = collect_inputs(&[dir.join("chunks/1.js"), dir.join("main.js")]).unwrap();
+        let relative: Vec<&Path> = inputs.iter().map(|x| x.relative.as_path()).collect();
+        assert_eq!(
+            relative,
+            vec![Path::new("chunks/1.js"), Path::new("main.js")]
+        );
+
+        let pattern = dir.join("chunks/*.js");
+        let inputs = collect_inputs(&[pattern]).unwrap();
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].relative, Path::new("1.js"));
+
+        assert!(collect_inputs(&[dir.join("missing/*.js")]).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_input_source_map() {
+        let dir = std::env::temp_dir().join(format!("jsmagi-maps-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("maps")).unwrap();
+        std::fs::write(dir.join("maps/app.map"), "{}").unwrap();
+        std::fs::write(dir.join("other.js.map"), "{}").unwrap();
+
+        let code = "a();\n//# sourceMappingURL=maps/app.map\n";
+        assert_eq!(
+            find_input_source_map(&dir.join("app.js"), code),
+            Some(dir.join("maps/app.map"))
+        );
+        assert_eq!(
+            find_input_source_map(&dir.join("other.js"), "a();"),
+            Some(dir.join("other.js.map"))
+        );
+        let inline = "a();\n//# sourceMappingURL=data:application/json;base64,e30=";
+        assert_eq!(find_input_source_map(&dir.join("other.js"), inline), None);
+        assert_eq!(find_input_source_map(&dir.join("none.js"), "a();"), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_output_path() {
+        let input = InputFile {
+            path: PathBuf::from("dist/chunks/1.js"),
+            relative: PathBuf::from("chunks/1.js"),
+        };
+        assert_eq!(
+            output_path(Path::new("out"), &input, true),
+            Path::new("out/chunks/1.ts")
+        );
+        assert_eq!(
+            output_path(Path::new("out"), &input, false),
+            Path::new("out/chunks/1.js")
+        );
+    }
+}
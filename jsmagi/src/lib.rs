@@ -1,31 +1,39 @@
-use std::{cell::Cell, path::Path, rc::Rc, sync::Arc};
+use std::{
+    cell::Cell,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::Path,
+    rc::Rc,
+    sync::Arc,
+};
 
 use swc::{
-    config::{Config, Options, SourceMapsConfig},
+    config::{Config, InputSourceMap, Options, SourceMapsConfig},
     BoolConfig, Compiler, TransformOutput,
 };
-use swc_common::{
-    chain,
-    errors::{ColorConfig, Handler},
-    FileName, SourceMap,
-};
+use swc_common::{chain, comments::SingleThreadedComments, errors::Handler, FileName, SourceMap};
 use swc_ecma_ast::EsVersion;
 use swc_ecma_parser::{EsConfig, Syntax};
 use swc_ecma_transforms_base::pass::noop;
-use swc_ecma_visit::{as_folder, Fold};
-
-use crate::passes::{
-    es_module::EsModuleRenameVisitor, iife_expand::IifeExpandVisitor,
-    init_assignment::InitAssignmentVisitor, nested_assignment::NestedAssignmentVisitor,
-    not_iife::NotIifeVisitor, not_lit::NotLitVisitor, seq_expand::SeqExpandVisitor,
-    ts::enum_convert::EnumConvert, var_decl_expand::VarDeclExpand,
-    void_to_undefined::VoidToUndefinedVisitor,
+use swc_ecma_visit::as_folder;
+
+use crate::{
+    error::{CollectingEmitter, Diagnostic, MagiError, Severity},
+    eval::EvalSafety,
+    passes::{disable_pass, enable_pass, PassId, Pipeline, PipelineReport},
+    rename::OriginalNames,
+    safety::Safety,
+    trust::Trust,
 };
 
+pub mod access;
+pub mod error;
 pub mod eval;
+pub mod files;
 
 pub mod passes;
 pub mod rename;
+pub mod safety;
+pub mod trust;
 
 pub mod effect;
 pub mod util;
@@ -36,28 +44,88 @@ pub struct MagiConfig {
     pub typescript: bool,
     /// Whether it should assume that the file is compiled as ES Modules.
     pub assume_es_modules: bool,
-    // TODO: Option to be more careful about eval
-    // TODO: Option to be more careful about property accessing, potentially due to getters/setters/proxies.
-    //   Though, it would be good to allow the user to specify a whitelist/blacklist of functions
-    //   that they believe are likely 'safe'
+    /// How careful the passes are with variables that an `eval` or `with` might use.
+    pub eval_safety: EvalSafety,
+    /// Whether the passes should keep every property access the same as in the input, in case
+    /// there are getters, setters or proxies. Passes which would read or write a property more
+    /// or less often, or in a different order, use a temporary variable or are skipped.
+    pub strict_semantics: bool,
+    /// The least safe level of pass that is allowed to run. Passes above it are skipped.
+    pub safety: Safety,
+    /// Whether the passes that aren't semantics-preserving leave a comment with their code at
+    /// each site that they rewrite.
+    pub annotate: bool,
+    /// Functions and properties that the user knows to be pure, plain, or proxies.
+    pub trust: Trust,
     pub random_name: RandomName,
+    /// The passes to run, in the order that they are run.
+    pub passes: Vec<PassId>,
+    /// The maximum number of times to run the passes while waiting for them to stop making
+    /// changes. Passes often set up code that earlier passes can then simplify.
+    pub max_iterations: usize,
+    /// Whether to generate a source map from the output back to the input.
+    pub source_map: bool,
+    /// The contents of a source map for the input, like the one a site ships alongside its
+    /// bundle. The generated source map then points back to the original sources.
+    /// Unlike the other options, this is specific to the file being transformed.
+    pub input_source_map: Option<String>,
+    /// Whether to rename variables to the original names recorded in the input source map.
+    pub original_names: bool,
+    /// The comments of the file being transformed, which passes can add to.
+    /// [`transform_source`] sets this for each file.
+    pub comments: SingleThreadedComments,
+}
+impl Default for MagiConfig {
+    fn default() -> Self {
+        Self {
+            typescript: true,
+            assume_es_modules: false,
+            eval_safety: EvalSafety::default(),
+            strict_semantics: false,
+            safety: Safety::default(),
+            annotate: false,
+            trust: Trust::default(),
+            random_name: RandomName::default(),
+            passes: PassId::default_pipeline(),
+            max_iterations: 10,
+            source_map: false,
+            input_source_map: None,
+            original_names: true,
+            comments: SingleThreadedComments::default(),
+        }
+    }
 }
 impl MagiConfig {
-    pub(crate) fn get_passes(&self) -> impl Fold {
-        as_folder(chain!(
-            // resolver(unresolved_mark, top_level_mark, false),
-            SeqExpandVisitor::from_config(self),
-            VoidToUndefinedVisitor::from_config(self),
-            NotLitVisitor::from_config(self),
-            NotIifeVisitor::from_config(self),
-            InitAssignmentVisitor::from_config(self),
-            NestedAssignmentVisitor::from_config(self),
-            VarDeclExpand::from_config(self),
-            IifeExpandVisitor::from_config(self),
-            // TODO: make toggleable
-            EsModuleRenameVisitor::from_config(self),
-            EnumConvert::from_config(self),
-        ))
+    pub fn get_passes(&self) -> Pipeline {
+        // resolver(unresolved_mark, top_level_mark, false),
+        Pipeline::new(
+            self.passes
+                .iter()
+                .filter(|pass| pass.safety(self.strict_semantics) <= self.safety)
+                .map(|pass| pass.make(self))
+                .collect(),
+            self.max_iterations,
+        )
+    }
+
+    /// Add the pass to the pipeline, at its default position, if it is not already enabled.
+    pub fn enable(&mut self, pass: PassId) {
+        enable_pass(&mut self.passes, pass);
+    }
+
+    /// Remove the pass from the pipeline.
+    pub fn disable(&mut self, pass: PassId) {
+        disable_pass(&mut self.passes, pass);
+    }
+
+    /// Run only the given passes, in the given order.
+    pub fn only(&mut self, passes: impl IntoIterator<Item = PassId>) {
+        self.passes.clear();
+        for pass in passes {
+            if !self.passes.contains(&pass) {
+                self.passes.push(pass);
+            }
+        }
     }
 
     pub fn random_name(&self) -> RandomName {
@@ -89,56 +157,264 @@ impl RandomName {
     }
 }
 
+/// The version of JavaScript that the code is parsed and generated as
+pub(crate) const TARGET: EsVersion = EsVersion::Es2022;
+
+/// The settings that the code is parsed with
+pub(crate) fn syntax() -> Syntax {
+    Syntax::Es(EsConfig {
+        jsx: true,
+        ..Default::default()
+    })
+}
+
 pub trait FromMagiConfig {
     fn from_config(conf: &MagiConfig) -> Self;
 }
 
-pub fn transform(filename: impl AsRef<Path>, conf: MagiConfig) -> String {
+#[derive(Debug, Clone)]
+pub struct MagiOutput {
+    pub code: String,
+    /// The source map for `code`, if [`MagiConfig::source_map`] was enabled.
+    pub map: Option<String>,
+    /// Things that the passes left alone because transforming them might not be safe.
+    pub warnings: Vec<Diagnostic>,
+    pub report: PipelineReport,
+}
+
+pub fn transform(filename: impl AsRef<Path>, conf: MagiConfig) -> Result<MagiOutput, MagiError> {
     let filename = filename.as_ref();
     let filename_text = filename.to_string_lossy().into_owned();
-    let code = std::fs::read_to_string(filename).unwrap();
+    let code = std::fs::read_to_string(filename).map_err(|err| MagiError::io(filename, err))?;
+
+    transform_source(&code, &filename_text, conf)
+}
 
+/// Transform the given source code, without touching the filesystem.
+/// `name` is used for diagnostics.
+pub fn transform_source(
+    code: &str,
+    name: &str,
+    mut conf: MagiConfig,
+) -> Result<MagiOutput, MagiError> {
+    let comments = SingleThreadedComments::default();
+    conf.comments = comments.clone();
     let passes = conf.get_passes();
+    let report = passes.report();
 
     let source_map: Arc<SourceMap> = Default::default();
     let source_file =
-        source_map.new_source_file(FileName::Custom(filename_text.clone()), code.to_string());
-    let handler =
-        Handler::with_tty_emitter(ColorConfig::Auto, true, false, Some(source_map.clone()));
-
-    let compiler = Compiler::new(source_map);
-
-    let transformed = compiler.process_js_with_custom_pass(
-        source_file,
-        None,
-        &handler,
-        &Options {
-            config: Config {
-                jsc: swc::config::JscConfig {
-                    target: Some(EsVersion::Es2022),
-                    syntax: Some(Syntax::Es(EsConfig {
-                        jsx: true,
+        source_map.new_source_file(FileName::Custom(name.to_owned()), code.to_string());
+    let emitter = CollectingEmitter::default();
+    let errors = emitter.errors.clone();
+    let warnings = emitter.warnings.clone();
+    let handler = Handler::with_emitter(true, false, Box::new(emitter));
+
+    let compiler = Compiler::new(source_map.clone());
+
+    let original_names = OriginalNames::new(
+        source_map.clone(),
+        conf.input_source_map
+            .as_deref()
+            .filter(|_| conf.original_names),
+        conf.eval_safety,
+    );
+
+    // A bug in a single pass shouldn't take down the rest of a batch of files
+    let transformed = catch_unwind(AssertUnwindSafe(|| {
+        compiler.process_js_with_custom_pass(
+            source_file,
+            None,
+            &handler,
+            &Options {
+                config: Config {
+                    jsc: swc::config::JscConfig {
+                        target: Some(TARGET),
+                        syntax: Some(syntax()),
+                        loose: BoolConfig::new(Some(false)),
+                        external_helpers: BoolConfig::new(Some(false)),
+                        keep_class_names: BoolConfig::new(Some(false)),
                         ..Default::default()
-                    })),
-                    loose: BoolConfig::new(Some(false)),
-                    external_helpers: BoolConfig::new(Some(false)),
-                    keep_class_names: BoolConfig::new(Some(false)),
+                    },
+                    input_source_map: Some(match &conf.input_source_map {
+                        Some(map) => InputSourceMap::Str(map.clone()),
+                        // Use an inline source map if there is one
+                        None => InputSourceMap::Bool(true),
+                    }),
                     ..Default::default()
                 },
+                source_file_name: Some(name.to_owned()),
+                source_maps: Some(SourceMapsConfig::Bool(conf.source_map)),
                 ..Default::default()
             },
-            source_file_name: Some(filename_text.clone()),
-            source_maps: Some(SourceMapsConfig::Bool(true)),
-            ..Default::default()
-        },
-        Default::default(),
-        |_| passes,
-        |_| noop(),
-    );
+            comments,
+            // The names are restored first, so the passes work with readable code and any
+            // identifiers that they generate don't affect the lookup.
+            |_| chain!(as_folder(original_names), as_folder(passes)),
+            |_| noop(),
+        )
+    }));
+
+    let errors = std::mem::take(&mut *errors.lock().unwrap());
+    if !errors.is_empty() {
+        return Err(MagiError::Parse {
+            name: name.to_owned(),
+            diagnostics: errors
+                .into_iter()
+                .map(|(message, span)| {
+                    Diagnostic::new(&source_map, name, Severity::Error, message, span)
+                })
+                .collect(),
+        });
+    }
+
+    let transformed = match transformed {
+        Ok(transformed) => transformed,
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|x| x.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "a pass panicked".to_owned());
+            return Err(MagiError::Transform {
+                name: name.to_owned(),
+                message,
+            });
+        }
+    };
+
+    let TransformOutput { code, map } = transformed.map_err(|err| MagiError::Transform {
+        name: name.to_owned(),
+        message: format!("{:#}", err),
+    })?;
 
-    handler.abort_if_errors();
+    let warnings = std::mem::take(&mut *warnings.lock().unwrap())
+        .into_iter()
+        .map(|(message, span)| Diagnostic::new(&source_map, name, Severity::Warning, message, span))
+        .collect();
 
-    let TransformOutput { code, map: _ } = transformed.unwrap();
+    Ok(MagiOutput {
+        code,
+        map,
+        warnings,
+        report: report.get(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_common::{Globals, GLOBALS};
+
+    use crate::{error::MagiError, eval::EvalSafety, safety::Safety, transform_source, MagiConfig};
+
+    #[test]
+    fn test_transform_source() {
+        GLOBALS.set(&Globals::new(), || {
+            let output = transform_source("a, void 0", "input.js", MagiConfig::default()).unwrap();
+            assert_eq!(output.code, "a;\nundefined;\n");
+            assert!(output.report.converged);
+
+            let err = transform_source("let = ;", "input.js", MagiConfig::default()).unwrap_err();
+            let MagiError::Parse { diagnostics, .. } = &err else {
+                panic!("expected a parse error, got {:?}", err);
+            };
+            let last = diagnostics.last().unwrap();
+            assert_eq!(last.message, "Expression expected");
+            assert_eq!((last.line, last.column), (1, 6));
+            assert_eq!(last.source_line, "let = ;");
+            assert_eq!(err.exit_code(), 65);
+            assert!(err.to_string().contains(" --> input.js:1:7\n"));
+        })
+    }
 
-    code
+    #[test]
+    fn test_eval_safety() {
+        let code = "var a = (function() { return 5; })(); eval(input);";
+        GLOBALS.set(&Globals::new(), || {
+            let output = transform_source(code, "input.js", MagiConfig::default()).unwrap();
+            assert!(output.code.contains("function()"));
+            assert!(output.warnings.is_empty());
+
+            let conf = MagiConfig {
+                eval_safety: EvalSafety::Warn,
+                ..MagiConfig::default()
+            };
+            let output = transform_source(code, "input.js", conf).unwrap();
+            assert_eq!(output.code, "var a = 5;\neval(input);\n");
+            assert_eq!(output.warnings.len(), 1);
+            assert_eq!(
+                output.warnings[0].message,
+                "expanding an IIFE in a scope that uses `eval` or `with`"
+            );
+            assert_eq!((output.warnings[0].line, output.warnings[0].column), (1, 4));
+        })
+    }
+
+    #[test]
+    fn test_safety() {
+        let code = "var a = void 0; var o = {}; o.x = 1;";
+        GLOBALS.set(&Globals::new(), || {
+            let conf = MagiConfig {
+                annotate: true,
+                ..MagiConfig::default()
+            };
+            let output = transform_source(code, "input.js", conf).unwrap();
+            assert_eq!(
+                output.code,
+                "var a = /* MCode(P002) */ undefined;\n/* MCode(P007) */ var o = {\n    x: 1\n};\n"
+            );
+
+            let conf = MagiConfig {
+                safety: Safety::Preserving,
+                annotate: true,
+                ..MagiConfig::default()
+            };
+            let output = transform_source(code, "input.js", conf).unwrap();
+            assert_eq!(output.code, "var a = void 0;\nvar o = {};\no.x = 1;\n");
+        })
+    }
+
+    #[test]
+    fn test_number_comments() {
+        GLOBALS.set(&Globals::new(), || {
+            let code = "var a = 864e5, b = 1e3;";
+            let output = transform_source(code, "input.js", MagiConfig::default()).unwrap();
+            assert_eq!(
+                output.code,
+                "var a = /* 864e5 */ 86400000;\nvar b = 1000;\n"
+            );
+        })
+    }
+
+    #[test]
+    fn test_original_names() {
+        let code = "(function(){function n(n,r){var t=n+r;return t}console.log(n(1,2))})();";
+        let mut builder = swc::sourcemap::SourceMapBuilder::new(None);
+        for (col, name) in [(21, "add"), (23, "first"), (25, "second"), (32, "first")] {
+            builder.add(0, col, 0, col, Some("src.js"), Some(name));
+        }
+        let mut map = Vec::new();
+        builder.into_sourcemap().to_writer(&mut map).unwrap();
+        let map = String::from_utf8(map).unwrap();
+
+        GLOBALS.set(&Globals::new(), || {
+            let conf = MagiConfig {
+                input_source_map: Some(map.clone()),
+                ..MagiConfig::default()
+            };
+            let output = transform_source(code, "min.js", conf).unwrap();
+            assert_eq!(
+                output.code,
+                "(function() {\n    function add(first, second) {\n        var t = first + second;\n        return t;\n    }\n    console.log(add(1, 2));\n})();\n"
+            );
+
+            let conf = MagiConfig {
+                input_source_map: Some(map),
+                original_names: false,
+                ..MagiConfig::default()
+            };
+            let output = transform_source(code, "min.js", conf).unwrap();
+            assert!(output.code.contains("function n(n, r)"));
+        })
+    }
 }
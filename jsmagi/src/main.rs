@@ -1,9 +1,18 @@
-use std::{cell::Cell, path::PathBuf, rc::Rc};
+use std::{io::Write, path::PathBuf};
 
-use jsmagi::{transform, MagiConfig, RandomName};
+use jsmagi::{
+    error::MagiError,
+    eval::EvalSafety,
+    files::{collect_inputs, find_input_source_map, output_path},
+    passes::PassId,
+    safety::Safety,
+    transform_source,
+    trust::Trust,
+    MagiConfig, RandomName,
+};
 use swc_common::{Globals, GLOBALS};
 
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Parser, Subcommand};
 
 #[derive(Debug, Parser)]
 #[command(name = "jsmagi")]
@@ -15,66 +24,450 @@ struct Cli {
     command: Commands,
 }
 
+// It is only parsed once, so the size doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand)]
 pub enum Commands {
-    // TODO: Support multiple files at once, also allowing them to share globals
     #[command(
-        about = "Applies transformations to a JavaScript file",
+        about = "Applies transformations to JavaScript files",
         arg_required_else_help = true
     )]
     Transform {
-        // TODO: Let the user request output to stdout
-        file: PathBuf,
-        /// Path to output to. Default: `./output.{js,ts}`
-        #[arg(long, short)]
+        /// Files, directories or glob patterns to transform, or `-` to read from stdin.
+        /// All of the files are transformed in one session, so generated names don't clash
+        /// between the chunks of a bundle.
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Path to output to, when transforming a single file. Default: `./output.{js,ts}`
+        #[arg(long, short, conflicts_with = "out_dir")]
         output: Option<PathBuf>,
+        /// Directory to output to, mirroring the layout of the input files.
+        /// Required when there are multiple input files.
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+        /// Write the output to stdout rather than to a file.
+        /// This is the default when reading from stdin without `--output`.
+        #[arg(long, conflicts_with_all = ["output", "out_dir"])]
+        stdout: bool,
         /// Whether it should output the code as TypeScript. Default: true
         #[arg(long, default_value_t = true)]
         typescript: bool,
         /// Whether it should assume that the file is compiled as ES Modules. Default: false
         #[arg(long, short, default_value_t = false)]
         assume_es_modules: bool,
+        /// How careful to be with variables that an `eval` or `with` might use:
+        /// `strict` leaves them alone, `warn` changes them but warns about it,
+        /// and `ignore` changes them as if there was no `eval`.
+        #[arg(long, default_value_t = EvalSafety::Strict)]
+        eval_safety: EvalSafety,
+        /// Keep every property access the same as in the input, in case there are getters,
+        /// setters or proxies. Passes that would read or write a property more or less often,
+        /// or in a different order, use a temporary variable or are skipped.
+        #[arg(long)]
+        strict_semantics: bool,
+        /// The least safe passes that may run: `preserving` only runs passes that keep the code
+        /// doing exactly the same thing, `probable` also runs passes that only differ for
+        /// unusual code like getters, and `readable` runs everything.
+        /// `preserving` implies `--strict-semantics`.
+        #[arg(long, default_value_t = Safety::Readable)]
+        safety: Safety,
+        /// Leave a `/* MCode(P002) */` comment at each site rewritten by a pass that isn't
+        /// semantics-preserving. `jsmagi explain P002` says what it could have broken.
+        #[arg(long)]
+        annotate: bool,
+        /// A JSON file listing the functions that are `pure`, the objects that are `plain` and
+        /// have no getters, and the `proxies` whose accesses must never be moved.
+        /// Ex: `{ "pure": ["n.d"], "plain": ["e.exports"], "proxies": ["store"] }`
+        #[arg(long)]
+        trust: Option<PathBuf>,
+        /// Passes to run in addition to the default ones. Comma separated.
+        #[arg(long, value_delimiter = ',')]
+        enable: Vec<PassId>,
+        /// Passes to not run. Comma separated.
+        #[arg(long, value_delimiter = ',')]
+        disable: Vec<PassId>,
+        /// Run only these passes, in the given order. Comma separated.
+        #[arg(long, value_delimiter = ',', conflicts_with = "enable")]
+        only: Option<Vec<PassId>>,
+        /// The maximum number of times to rerun the passes until they stop making changes.
+        #[arg(long, default_value_t = 10)]
+        max_iterations: usize,
+        /// Write a source map next to each output file, mapping the output back to the input.
+        #[arg(long)]
+        source_map: bool,
+        /// A source map for the input, such as one shipped alongside a minified bundle, so that
+        /// the generated source map points back to the original sources.
+        /// Without a path, each input's `sourceMappingURL` or `<file>.map` is used if it exists.
+        /// The path has to be given as `--input-source-map=<path>`, so that an input file after
+        /// the flag isn't taken for it.
+        /// Variables are also renamed to the original names that the source map records.
+        #[arg(long, num_args = 0..=1, require_equals = true)]
+        input_source_map: Option<Option<PathBuf>>,
+        /// Keep the minified variable names even if the input source map has the original ones.
+        #[arg(long = "no-original-names", action = ArgAction::SetFalse)]
+        original_names: bool,
+    },
+    #[command(about = "Lists the available passes, in the order that they run by default")]
+    Passes,
+    #[command(
+        about = "Explains what a pass code left by `--annotate` means, and what it could break",
+        arg_required_else_help = true
+    )]
+    Explain {
+        /// The code, like `P002`
+        code: String,
     },
     // TODO: command to generate a typescript config file which matches our loose
     // application. Obviously, we can't generate good types in many cases, so allowing implicit-any
     // is a must. Etc.
 }
 
+/// Where the code to transform is read from
+enum Source {
+    Stdin,
+    File(PathBuf),
+}
+
+/// Where the transformed code is written to
+enum Sink {
+    Stdout,
+    File(PathBuf),
+}
+
+/// Where to get the source map for an input from
+enum InputSourceMap {
+    None,
+    /// Use the same source map for every input
+    File(PathBuf),
+    /// Look for the source map of each input next to it
+    Discover,
+}
+
+/// Exit because of a problem with the command line arguments
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(2);
+}
+
+/// Decide which inputs get written to which outputs
+fn plan_jobs(
+    files: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    out_dir: Option<PathBuf>,
+    stdout: bool,
+    typescript: bool,
+) -> Vec<(Source, Sink)> {
+    let reads_stdin = files.iter().any(|file| file.as_os_str() == "-");
+    if reads_stdin {
+        if files.len() != 1 {
+            exit_with_error("`-` cannot be combined with other input files");
+        }
+        if out_dir.is_some() {
+            exit_with_error("`--out-dir` cannot be used when reading from stdin");
+        }
+
+        let sink = output.map(Sink::File).unwrap_or(Sink::Stdout);
+        return vec![(Source::Stdin, sink)];
+    }
+
+    let inputs = match collect_inputs(&files) {
+        Ok(inputs) if inputs.is_empty() => exit_with_error("no input files found"),
+        Ok(inputs) => inputs,
+        Err(err) => exit_with_error(&err.to_string()),
+    };
+
+    if let Some(out_dir) = out_dir {
+        inputs
+            .into_iter()
+            .map(|input| {
+                let output = output_path(&out_dir, &input, typescript);
+                (Source::File(input.path), Sink::File(output))
+            })
+            .collect()
+    } else if let [input] = inputs.as_slice() {
+        let sink = if stdout {
+            Sink::Stdout
+        } else {
+            Sink::File(output.unwrap_or_else(|| {
+                let mut path = input
+                    .path
+                    .parent()
+                    .map(ToOwned::to_owned)
+                    .unwrap_or_else(|| PathBuf::from("./"));
+                path.push("output");
+                if typescript {
+                    path.set_extension("ts");
+                } else {
+                    path.set_extension("js");
+                }
+
+                path
+            }))
+        };
+
+        vec![(Source::File(input.path.clone()), sink)]
+    } else {
+        exit_with_error(&format!(
+            "found {} input files, `--out-dir` is required to transform more than one file",
+            inputs.len()
+        ))
+    }
+}
+
+fn run_job(
+    source: Source,
+    sink: Sink,
+    conf: &MagiConfig,
+    input_source_map: &InputSourceMap,
+    verbose: bool,
+) -> Result<(), MagiError> {
+    let (name, code) = match &source {
+        Source::Stdin => {
+            let code = std::io::read_to_string(std::io::stdin())
+                .map_err(|error| MagiError::Io { path: None, error })?;
+            ("stdin".to_owned(), code)
+        }
+        Source::File(path) => {
+            let code = std::fs::read_to_string(path).map_err(|err| MagiError::io(path, err))?;
+            (path.to_string_lossy().into_owned(), code)
+        }
+    };
+
+    let input_map_path = match (input_source_map, &source) {
+        (InputSourceMap::File(path), _) => Some(path.clone()),
+        (InputSourceMap::Discover, Source::File(path)) => find_input_source_map(path, &code),
+        _ => None,
+    };
+    let mut conf = conf.clone();
+    if let Some(path) = input_map_path {
+        let map = std::fs::read_to_string(&path).map_err(|err| MagiError::io(path, err))?;
+        conf.input_source_map = Some(map);
+    }
+
+    let output_data = transform_source(&code, &name, conf)?;
+    for warning in &output_data.warnings {
+        eprintln!("{}", warning);
+    }
+    if verbose {
+        let report = output_data.report;
+        if report.converged {
+            eprintln!("{}: converged after {} rounds", name, report.rounds);
+        } else {
+            eprintln!(
+                "{}: stopped after {} rounds without converging",
+                name, report.rounds
+            );
+        }
+    }
+
+    match sink {
+        Sink::Stdout => {
+            let mut stdout = std::io::stdout().lock();
+            stdout
+                .write_all(output_data.code.as_bytes())
+                .and_then(|_| stdout.flush())
+                .map_err(|error| MagiError::Io { path: None, error })?;
+        }
+        Sink::File(output) => {
+            if let Some(parent) = output.parent() {
+                std::fs::create_dir_all(parent).map_err(|err| MagiError::io(parent, err))?;
+            }
+
+            let mut code = output_data.code;
+            if let Some(map) = output_data.map {
+                let mut map_path = output.as_os_str().to_owned();
+                map_path.push(".map");
+                let map_path = PathBuf::from(map_path);
+                std::fs::write(&map_path, map).map_err(|err| MagiError::io(&map_path, err))?;
+
+                let map_name = map_path.file_name().unwrap_or_default().to_string_lossy();
+                code.push_str(&format!("//# sourceMappingURL={}\n", map_name));
+            }
+
+            std::fs::write(&output, code).map_err(|err| MagiError::io(&output, err))?;
+        }
+    }
+
+    Ok(())
+}
+
 fn main() {
     let cli = Cli::parse();
 
     match cli.command {
         Commands::Transform {
-            file,
+            files,
             output,
+            out_dir,
+            stdout,
             typescript,
             assume_es_modules,
+            eval_safety,
+            strict_semantics,
+            safety,
+            annotate,
+            trust,
+            enable,
+            disable,
+            only,
+            max_iterations,
+            source_map,
+            input_source_map,
+            original_names,
         } => {
-            let conf = MagiConfig {
+            let trust = match trust {
+                Some(path) => Trust::from_file(path).unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    std::process::exit(err.exit_code());
+                }),
+                None => Trust::default(),
+            };
+            let mut conf = MagiConfig {
                 typescript,
                 assume_es_modules,
+                eval_safety,
+                // The passes that only change how often properties are accessed are
+                // semantics-preserving when they keep the accesses intact
+                strict_semantics: strict_semantics || safety == Safety::Preserving,
+                safety,
+                annotate,
+                trust,
                 random_name: RandomName::default(),
+                passes: PassId::default_pipeline(),
+                max_iterations,
+                source_map,
+                input_source_map: None,
+                original_names,
+                comments: Default::default(),
             };
-            let output = output.unwrap_or_else(|| {
-                let mut path = file
-                    .parent()
-                    .map(ToOwned::to_owned)
-                    .unwrap_or_else(|| PathBuf::from("./"));
-                path.push("output");
-                if typescript {
-                    path.set_extension("ts");
-                } else {
-                    path.set_extension("js");
+            if let Some(only) = only {
+                conf.only(only);
+            }
+            for pass in enable {
+                conf.enable(pass);
+            }
+            for pass in disable {
+                conf.disable(pass);
+            }
+
+            let jobs = plan_jobs(files, output, out_dir, stdout, typescript);
+            if source_map && jobs.iter().any(|(_, sink)| matches!(sink, Sink::Stdout)) {
+                exit_with_error("`--source-map` needs an output file to put the map next to");
+            }
+
+            let input_source_map = match input_source_map {
+                None => InputSourceMap::None,
+                Some(None) => InputSourceMap::Discover,
+                Some(Some(_)) if jobs.len() > 1 => exit_with_error(
+                    "a single `--input-source-map` can't be used for multiple input files, \
+                     leave out the path to find each file's source map",
+                ),
+                Some(Some(path)) => InputSourceMap::File(path),
+            };
+
+            // The files share globals and the `RandomName` counter in `conf`, so that marks and
+            // generated names are unique across all of them.
+            let globals = Globals::new();
+            let exit_code = GLOBALS.set(&globals, || {
+                let mut exit_code = 0;
+                for (source, sink) in jobs {
+                    // Keep going, so that one bad chunk doesn't stop the rest of the batch
+                    if let Err(err) = run_job(source, sink, &conf, &input_source_map, cli.verbose) {
+                        eprintln!("{}", err);
+                        if exit_code == 0 {
+                            exit_code = err.exit_code();
+                        }
+                    }
                 }
 
-                path
+                exit_code
             });
+            std::process::exit(exit_code);
+        }
+        Commands::Passes => {
+            for pass in PassId::ALL {
+                let default = if pass.enabled_by_default() {
+                    ""
+                } else {
+                    " (disabled by default)"
+                };
+                println!(
+                    "{:<20} {} {}{}",
+                    pass.name(),
+                    pass.code(),
+                    pass.description(),
+                    default
+                );
+            }
+        }
+        Commands::Explain { code } => {
+            let Some(pass) = PassId::from_code(&code) else {
+                let codes: Vec<&str> = PassId::ALL.iter().map(|pass| pass.code()).collect();
+                exit_with_error(&format!(
+                    "unknown code `{}`, expected one of: {}",
+                    code,
+                    codes.join(", ")
+                ));
+            };
 
-            let globals = Globals::new();
-            GLOBALS.set(&globals, || {
-                let code = transform(&file, conf);
-                std::fs::write(output, code).unwrap();
-            })
+            let safety = pass.safety(false);
+            println!(
+                "{}: {} ({})",
+                pass.code(),
+                pass.name(),
+                safety.description()
+            );
+            println!();
+            println!("{}", pass.explanation());
+            if pass.safety(true) < safety {
+                println!();
+                println!(
+                    "With `--strict-semantics` it is {}.",
+                    pass.safety(true).description()
+                );
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap::Parser;
+
+    use super::{Cli, Commands};
+
+    fn input_source_map(args: &[&str]) -> (Vec<PathBuf>, Option<Option<PathBuf>>) {
+        let cli = Cli::try_parse_from(["jsmagi", "transform"].iter().chain(args)).unwrap();
+        let Commands::Transform {
+            files,
+            input_source_map,
+            ..
+        } = cli.command
+        else {
+            panic!("expected the transform command");
+        };
+        (files, input_source_map)
+    }
+
+    #[test]
+    fn test_input_source_map_arg() {
+        // The file after the flag is an input, not the map
+        assert_eq!(
+            input_source_map(&["--input-source-map", "a.js"]),
+            (vec![PathBuf::from("a.js")], Some(None))
+        );
+        assert_eq!(
+            input_source_map(&["--input-source-map=a.js.map", "a.js"]),
+            (
+                vec![PathBuf::from("a.js")],
+                Some(Some(PathBuf::from("a.js.map")))
+            )
+        );
+        assert_eq!(
+            input_source_map(&["a.js"]),
+            (vec![PathBuf::from("a.js")], None)
+        );
+    }
+}
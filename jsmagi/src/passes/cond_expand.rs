@@ -0,0 +1,238 @@
+//! Expand conditions that are used as statements into `if` statements.
+//! Example:
+//! ```js
+//! s && (a = s.type, c = s.handler);
+//! this.a || (this.a = !0, this.b && this.b.fire());
+//! ```
+//! becomes
+//! ```js
+//! if (s) {
+//!     a = s.type;
+//!     c = s.handler;
+//! }
+//! if (!this.a) {
+//!     this.a = !0;
+//!     if (this.b) {
+//!         this.b.fire();
+//!     }
+//! }
+//! ```
+//! Ternaries become `if`-`else` statements.
+
+use swc_common::{util::take::Take, Spanned};
+use swc_ecma_ast::{
+    BinaryOp, BlockStmt, CondExpr, Expr, ExprStmt, IfStmt, ParenExpr, Stmt, UnaryExpr, UnaryOp,
+};
+use swc_ecma_transforms_testing::test;
+#[cfg(test)]
+use swc_ecma_visit::as_folder;
+use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
+
+use crate::{
+    passes::{seq_expand::SeqExpandVisitor, Pass},
+    FromMagiConfig, MagiConfig,
+};
+
+/// Converts `a && b`, `a || b` and `a ? b : c` statements into `if` statements
+#[derive(Default)]
+pub struct CondExpandVisitor {
+    changed: bool,
+}
+impl FromMagiConfig for CondExpandVisitor {
+    fn from_config(_conf: &MagiConfig) -> Self {
+        Self::default()
+    }
+}
+impl Pass for CondExpandVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// `!a` for use as a condition, which is `a` if it is already negated like `!a`
+fn negate(expr: Expr) -> Box<Expr> {
+    match expr {
+        Expr::Unary(UnaryExpr {
+            op: UnaryOp::Bang,
+            arg,
+            ..
+        }) => arg,
+        // `a !== b` rather than `!(a === b)`. Unlike `<`, these are always the opposite
+        Expr::Bin(mut bin) if negated_op(bin.op).is_some() => {
+            bin.op = negated_op(bin.op).unwrap();
+            Box::new(Expr::Bin(bin))
+        }
+        expr => {
+            let span = expr.span();
+            let arg = match expr {
+                expr @ (Expr::Ident(_)
+                | Expr::Member(_)
+                | Expr::Call(_)
+                | Expr::Lit(_)
+                | Expr::This(_)
+                | Expr::Paren(_)
+                | Expr::Unary(_)) => Box::new(expr),
+                expr => Box::new(Expr::Paren(ParenExpr {
+                    span,
+                    expr: Box::new(expr),
+                })),
+            };
+            Box::new(Expr::Unary(UnaryExpr {
+                span,
+                op: UnaryOp::Bang,
+                arg,
+            }))
+        }
+    }
+}
+
+fn negated_op(op: BinaryOp) -> Option<BinaryOp> {
+    match op {
+        BinaryOp::EqEqEq => Some(BinaryOp::NotEqEq),
+        BinaryOp::NotEqEq => Some(BinaryOp::EqEqEq),
+        BinaryOp::EqEq => Some(BinaryOp::NotEq),
+        BinaryOp::NotEq => Some(BinaryOp::EqEq),
+        _ => None,
+    }
+}
+
+/// Whether the statement `expr;` does nothing, like the `void 0` in `a ? b() : void 0`
+fn is_noop(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(_) => true,
+        Expr::Unary(UnaryExpr {
+            op: UnaryOp::Void,
+            arg,
+            ..
+        }) => matches!(**arg, Expr::Lit(_)),
+        Expr::Paren(paren) => is_noop(&paren.expr),
+        _ => false,
+    }
+}
+
+/// A block containing `expr;`, with any sequences split into separate statements
+fn make_block(mut expr: Box<Expr>) -> Box<Stmt> {
+    // `(a = 1, b = 2)`
+    while let Expr::Paren(ParenExpr { expr: inner, .. }) = *expr {
+        expr = inner;
+    }
+    let span = expr.span();
+    let mut stmts = vec![Stmt::Expr(ExprStmt { span, expr })];
+    stmts.visit_mut_with(&mut SeqExpandVisitor::default());
+
+    Box::new(Stmt::Block(BlockStmt { span, stmts }))
+}
+
+/// Convert the condition in `expr;` into an `if` statement
+fn expand_cond(expr: Expr) -> Result<IfStmt, Expr> {
+    match expr {
+        Expr::Bin(bin) if bin.op == BinaryOp::LogicalAnd => Ok(IfStmt {
+            span: bin.span,
+            test: bin.left,
+            cons: make_block(bin.right),
+            alt: None,
+        }),
+        Expr::Bin(bin) if bin.op == BinaryOp::LogicalOr => Ok(IfStmt {
+            span: bin.span,
+            test: negate(*bin.left),
+            cons: make_block(bin.right),
+            alt: None,
+        }),
+        Expr::Cond(CondExpr {
+            span,
+            test,
+            cons,
+            alt,
+        }) => {
+            if is_noop(&alt) {
+                Ok(IfStmt {
+                    span,
+                    test,
+                    cons: make_block(cons),
+                    alt: None,
+                })
+            } else if is_noop(&cons) {
+                Ok(IfStmt {
+                    span,
+                    test: negate(*test),
+                    cons: make_block(alt),
+                    alt: None,
+                })
+            } else {
+                Ok(IfStmt {
+                    span,
+                    test,
+                    cons: make_block(cons),
+                    alt: Some(make_block(alt)),
+                })
+            }
+        }
+        // `(a && b);`
+        Expr::Paren(ParenExpr { expr, .. }) if matches!(*expr, Expr::Bin(_) | Expr::Cond(_)) => {
+            expand_cond(*expr)
+        }
+        expr => Err(expr),
+    }
+}
+
+impl VisitMut for CondExpandVisitor {
+    noop_visit_mut_type!();
+
+    fn visit_mut_stmt(&mut self, stmt: &mut Stmt) {
+        if let Stmt::Expr(ExprStmt { span, expr }) = stmt {
+            let span = *span;
+            let expr = *expr.take();
+            *stmt = match expand_cond(expr) {
+                Ok(if_stmt) => {
+                    self.changed = true;
+                    Stmt::If(IfStmt { span, ..if_stmt })
+                }
+                Err(expr) => Stmt::Expr(ExprStmt {
+                    span,
+                    expr: Box::new(expr),
+                }),
+            };
+        }
+
+        // The new blocks can contain more conditions
+        stmt.visit_mut_children_with(self);
+    }
+}
+
+test!(
+    Default::default(),
+    |_| as_folder(CondExpandVisitor::default()),
+    cond_expand_and,
+    "s && (a = s.type, c = s.handler);" // "if (s) { a = s.type; c = s.handler; }"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(CondExpandVisitor::default()),
+    cond_expand_or_nested,
+    "this.a || (this.a = !0, this.b && (this.b.fire(void 0), this.dispose()));" // "if (!this.a) { this.a = !0; if (this.b) { this.b.fire(void 0); this.dispose(); } }"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(CondExpandVisitor::default()),
+    cond_expand_ternary,
+    r#"
+    a ? b() : c();
+    a ? b() : void 0;
+    a ? void 0 : c();
+    a ? (b(), c()) : d ? e() : f();
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(CondExpandVisitor::default()),
+    cond_expand_negate,
+    r#"
+    !a || b();
+    a === b || c();
+    a = b || c();
+    function f() { x && y && z(); }
+    "#
+);
@@ -0,0 +1,811 @@
+//! Restore the default and rest parameters that Babel and Typescript compile away.
+//! Example:
+//! ```js
+//! function f(a, b) {
+//!     if (b === undefined) {
+//!         b = 5;
+//!     }
+//!     var c = arguments.length > 2 && arguments[2] !== undefined ? arguments[2] : {};
+//!     var rest = [];
+//!     for (var _i = 3; _i < arguments.length; _i++) {
+//!         rest[_i - 3] = arguments[_i];
+//!     }
+//! }
+//! ```
+//! becomes
+//! ```js
+//! function f(a, b = 5, c = {}, ...rest) {}
+//! ```
+//! This expects `void 0` to have been turned into `undefined` and `b === undefined && (b = 5)`
+//! into an `if`, though it also accepts them as they are.
+
+use std::collections::HashSet;
+
+use swc_common::{util::take::Take, Span, Spanned};
+use swc_ecma_ast::{
+    ArrowExpr, AssignOp, AssignPat, BinaryOp, BindingIdent, BlockStmtOrExpr, Expr, ExprStmt,
+    ForStmt, Function, Id, Ident, Lit, MemberProp, Param, Pat, PatOrExpr, RestPat, Stmt, UpdateOp,
+    VarDeclKind, VarDeclOrExpr, VarDeclarator,
+};
+use swc_ecma_transforms_testing::test;
+use swc_ecma_utils::{collect_decls, find_pat_ids, IdentUsageFinder};
+#[cfg(test)]
+use swc_ecma_visit::as_folder;
+use swc_ecma_visit::{
+    noop_visit_mut_type, noop_visit_type, Visit, VisitMut, VisitMutWith, VisitWith,
+};
+
+use crate::{
+    eval::EvalGuard,
+    passes::{Pass, PassId},
+    safety::Annotator,
+    util::{extract_ident_assign, is_undefined},
+    FromMagiConfig, MagiConfig,
+};
+
+/// Converts `if (b === undefined) { b = 5; }` at the start of a function into `b = 5` in its
+/// parameters, along with Babel's `arguments` versions and rest parameter loops
+#[derive(Default)]
+pub struct DefaultParamsVisitor {
+    /// An `eval` can read `arguments`, which stops following the parameters once they aren't
+    /// simple
+    eval: EvalGuard,
+    annotator: Annotator,
+    changed: bool,
+}
+impl FromMagiConfig for DefaultParamsVisitor {
+    fn from_config(conf: &MagiConfig) -> Self {
+        Self {
+            eval: EvalGuard::new(conf.eval_safety),
+            annotator: Annotator::new(conf, PassId::DefaultParams),
+            changed: false,
+        }
+    }
+}
+impl Pass for DefaultParamsVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// A statement at the start of a function body that sets up a parameter
+enum Prologue {
+    /// `if (b === undefined) { b = 5; }` for the existing parameter `b`
+    Default { param: Id, value: Box<Expr> },
+    /// `var c = arguments.length > 2 && arguments[2] !== undefined ? arguments[2] : {}`, or
+    /// `var c = arguments.length > 2 ? arguments[2] : undefined` without a default
+    Param {
+        index: usize,
+        ident: BindingIdent,
+        value: Option<Box<Expr>>,
+    },
+    /// `var rest = Array.prototype.slice.call(arguments, 3)`, or a loop that copies `arguments`
+    Rest {
+        index: usize,
+        ident: BindingIdent,
+        /// The loop variables, which must not be used once the loop is gone
+        temps: Vec<Id>,
+    },
+}
+
+/// A recognized prologue item along with the statements it replaces
+struct Item {
+    prologue: Prologue,
+    span: Span,
+    len: usize,
+}
+
+/// `a === undefined` or `undefined === a` with `op`, returning `a`
+fn compared_to_undefined(expr: &Expr, op: BinaryOp) -> Option<&Expr> {
+    let bin = expr.as_bin().filter(|bin| bin.op == op)?;
+    if is_undefined(&bin.right) {
+        Some(&bin.left)
+    } else if is_undefined(&bin.left) {
+        Some(&bin.right)
+    } else {
+        None
+    }
+}
+
+fn is_arguments(expr: &Expr) -> bool {
+    matches!(expr, Expr::Ident(ident) if &*ident.sym == "arguments")
+}
+
+/// `arguments.length`
+fn is_arguments_length(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Member(member) if is_arguments(&member.obj)
+            && matches!(&member.prop, MemberProp::Ident(prop) if &*prop.sym == "length")
+    )
+}
+
+fn as_index(expr: &Expr) -> Option<usize> {
+    let Expr::Lit(Lit::Num(num)) = expr else {
+        return None;
+    };
+    (num.value.fract() == 0.0 && (0.0..1e9).contains(&num.value)).then_some(num.value as usize)
+}
+
+/// `arguments[1]`, returning `1`
+fn arguments_index(expr: &Expr) -> Option<usize> {
+    let member = expr
+        .as_member()
+        .filter(|member| is_arguments(&member.obj))?;
+    as_index(&member.prop.as_computed()?.expr)
+}
+
+/// `arguments.length > 1`, returning `1`
+fn arguments_length_above(expr: &Expr) -> Option<usize> {
+    let bin = expr.as_bin().filter(|bin| bin.op == BinaryOp::Gt)?;
+    is_arguments_length(&bin.left)
+        .then(|| as_index(&bin.right))
+        .flatten()
+}
+
+/// The only statement in `stmt`, unwrapping a block
+fn single_stmt(stmt: &Stmt) -> Option<&Stmt> {
+    match stmt {
+        Stmt::Block(block) if block.stmts.len() == 1 => Some(&block.stmts[0]),
+        Stmt::Block(_) => None,
+        stmt => Some(stmt),
+    }
+}
+
+/// `b = 5`, returning `b` and `5`
+fn as_assign(expr: &Expr) -> Option<(Id, &Expr)> {
+    extract_ident_assign(expr).map(|(ident, value)| (ident.to_id(), value))
+}
+
+/// `if (b === undefined) { b = 5; }` or `b === undefined && (b = 5);`
+fn as_default(stmt: &Stmt) -> Option<Prologue> {
+    let (test, assign) = match stmt {
+        Stmt::If(if_stmt) if if_stmt.alt.is_none() => (
+            &*if_stmt.test,
+            &*single_stmt(&if_stmt.cons)?.as_expr()?.expr,
+        ),
+        Stmt::Expr(ExprStmt { expr, .. }) => {
+            let bin = expr.as_bin().filter(|bin| bin.op == BinaryOp::LogicalAnd)?;
+            (&*bin.left, &*bin.right)
+        }
+        _ => return None,
+    };
+    let tested = compared_to_undefined(test, BinaryOp::EqEqEq)?.as_ident()?;
+    let (param, value) = as_assign(assign)?;
+    (tested.to_id() == param).then(|| Prologue::Default {
+        param,
+        value: Box::new(value.clone()),
+    })
+}
+
+/// The only declarator of a `var` or `let`
+fn single_decl(stmt: &Stmt) -> Option<(&BindingIdent, Option<&Expr>)> {
+    let Stmt::Decl(decl) = stmt else {
+        return None;
+    };
+    let var = decl.as_var()?;
+    if var.kind == VarDeclKind::Const || var.decls.len() != 1 {
+        return None;
+    }
+    let ident = var.decls[0].name.as_ident()?;
+    Some((ident, var.decls[0].init.as_deref()))
+}
+
+/// `var c = arguments.length > 2 && arguments[2] !== undefined ? arguments[2] : {};`
+fn as_arguments_param(stmt: &Stmt) -> Option<Prologue> {
+    let (ident, init) = single_decl(stmt)?;
+    let cond = init?.as_cond()?;
+    let index = arguments_index(&cond.cons)?;
+
+    let value = match cond.test.as_bin() {
+        Some(bin) if bin.op == BinaryOp::LogicalAnd => {
+            let checked = compared_to_undefined(&bin.right, BinaryOp::NotEqEq)?;
+            if arguments_length_above(&bin.left)? != index || arguments_index(checked)? != index {
+                return None;
+            }
+            Some(cond.alt.clone())
+        }
+        // `var c = arguments.length > 2 ? arguments[2] : undefined;` for a parameter after one
+        // with a default
+        _ if arguments_length_above(&cond.test)? == index && is_undefined(&cond.alt) => None,
+        _ => return None,
+    };
+    Some(Prologue::Param {
+        index,
+        ident: ident.clone(),
+        value,
+    })
+}
+
+/// `Array.prototype.slice.call(arguments, 1)` or `[].slice.call(arguments, 1)`, returning `1`
+fn as_slice_call(expr: &Expr) -> Option<usize> {
+    let call = expr.as_call()?;
+    let call_member = call.callee.as_expr()?.as_member()?;
+    if !matches!(&call_member.prop, MemberProp::Ident(prop) if &*prop.sym == "call") {
+        return None;
+    }
+    let slice = call_member.obj.as_member()?;
+    if !matches!(&slice.prop, MemberProp::Ident(prop) if &*prop.sym == "slice") {
+        return None;
+    }
+    let is_array_proto = match &*slice.obj {
+        Expr::Array(array) => array.elems.is_empty(),
+        Expr::Member(member) => {
+            matches!(&*member.obj, Expr::Ident(ident) if &*ident.sym == "Array")
+                && matches!(&member.prop, MemberProp::Ident(prop) if &*prop.sym == "prototype")
+        }
+        _ => false,
+    };
+    if !is_array_proto || call.args.iter().any(|arg| arg.spread.is_some()) {
+        return None;
+    }
+
+    match call.args.as_slice() {
+        [args] if is_arguments(&args.expr) => Some(0),
+        [args, start] if is_arguments(&args.expr) => as_index(&start.expr),
+        _ => None,
+    }
+}
+
+/// `i++`, `++i` or `i += 1`
+fn is_increment(expr: &Expr, index: &Id) -> bool {
+    match expr {
+        Expr::Update(update) => {
+            update.op == UpdateOp::PlusPlus
+                && matches!(&*update.arg, Expr::Ident(ident) if ident.to_id() == *index)
+        }
+        Expr::Assign(assign) => {
+            assign.op == AssignOp::AddAssign
+                && matches!(as_index(&assign.right), Some(1))
+                && matches!(&assign.left, PatOrExpr::Pat(pat)
+                    if matches!(&**pat, Pat::Ident(ident) if ident.to_id() == *index))
+        }
+        _ => false,
+    }
+}
+
+/// `rest[i - 1] = arguments[i]`, or `rest[i] = arguments[i]` when starting from 0
+fn is_copy(stmt: &Stmt, rest: &Id, index: &Id, start: usize) -> bool {
+    let Some(expr) = single_stmt(stmt).and_then(Stmt::as_expr) else {
+        return false;
+    };
+    let Some(assign) = expr.expr.as_assign().filter(|a| a.op == AssignOp::Assign) else {
+        return false;
+    };
+    let is_index = |expr: &Expr| matches!(expr, Expr::Ident(ident) if ident.to_id() == *index);
+
+    let target = match &assign.left {
+        PatOrExpr::Expr(expr) => expr.as_member(),
+        PatOrExpr::Pat(pat) => pat.as_expr().and_then(|expr| expr.as_member()),
+    };
+    let Some(target) = target else {
+        return false;
+    };
+    let Some(key) = target.prop.as_computed() else {
+        return false;
+    };
+    let key_matches = match &*key.expr {
+        Expr::Bin(bin) => {
+            bin.op == BinaryOp::Sub && is_index(&bin.left) && as_index(&bin.right) == Some(start)
+        }
+        expr => start == 0 && is_index(expr),
+    };
+    let source_matches = assign
+        .right
+        .as_member()
+        .filter(|source| is_arguments(&source.obj))
+        .and_then(|source| source.prop.as_computed())
+        .is_some_and(|source| is_index(&source.expr));
+
+    matches!(&*target.obj, Expr::Ident(ident) if ident.to_id() == *rest)
+        && key_matches
+        && source_matches
+}
+
+/// Typescript's `for (var _i = 1; _i < arguments.length; _i++) { rest[_i - 1] = arguments[_i]; }`
+/// or Babel's `for (var _len = arguments.length, rest = new Array(...), _key = 1; _key < _len;
+/// _key++) { rest[_key - 1] = arguments[_key]; }`.
+/// Returns the start index, the variable for the rest, if it is declared by the loop, and the
+/// other loop variables.
+fn as_copy_loop(
+    stmt: &Stmt,
+    rest: Option<&BindingIdent>,
+) -> Option<(usize, BindingIdent, Vec<Id>)> {
+    let Stmt::For(ForStmt {
+        init: Some(VarDeclOrExpr::VarDecl(init)),
+        test: Some(test),
+        update: Some(update),
+        body,
+        ..
+    }) = stmt
+    else {
+        return None;
+    };
+    if init.kind == VarDeclKind::Const {
+        return None;
+    }
+
+    let mut rest = rest.cloned();
+    let mut len = None;
+    let mut index = None;
+    for VarDeclarator { name, init, .. } in &init.decls {
+        let name = name.as_ident()?;
+        match init.as_deref()? {
+            expr if is_arguments_length(expr) && len.is_none() => len = Some(name.to_id()),
+            Expr::New(new) if rest.is_none() => {
+                if !matches!(&*new.callee, Expr::Ident(ident) if &*ident.sym == "Array") {
+                    return None;
+                }
+                rest = Some(name.clone());
+            }
+            expr if index.is_none() => index = Some((name.to_id(), as_index(expr)?)),
+            _ => return None,
+        }
+    }
+    let rest = rest?;
+    let (index, start) = index?;
+
+    // `_i < arguments.length` or `_key < _len`
+    let test = test.as_bin().filter(|bin| bin.op == BinaryOp::Lt)?;
+    let limit_matches = match &len {
+        Some(len) => matches!(&*test.right, Expr::Ident(ident) if ident.to_id() == *len),
+        None => is_arguments_length(&test.right),
+    };
+    if !limit_matches
+        || !matches!(&*test.left, Expr::Ident(ident) if ident.to_id() == index)
+        || !is_increment(update, &index)
+        || !is_copy(body, &rest.to_id(), &index, start)
+    {
+        return None;
+    }
+
+    Some((start, rest, len.into_iter().chain([index]).collect()))
+}
+
+/// A rest parameter at the start of `stmts`, along with how many statements it takes up
+fn as_rest(stmts: &[Stmt]) -> Option<(Prologue, usize)> {
+    let first = stmts.first()?;
+    if let Some((ident, Some(init))) = single_decl(first) {
+        if let Some(index) = as_slice_call(init) {
+            let ident = ident.clone();
+            let temps = vec![];
+            return Some((
+                Prologue::Rest {
+                    index,
+                    ident,
+                    temps,
+                },
+                1,
+            ));
+        }
+        // `var rest = [];` followed by the loop
+        if matches!(init, Expr::Array(array) if array.elems.is_empty()) {
+            let (index, ident, temps) = as_copy_loop(stmts.get(1)?, Some(ident))?;
+            return Some((
+                Prologue::Rest {
+                    index,
+                    ident,
+                    temps,
+                },
+                2,
+            ));
+        }
+    }
+
+    let (index, ident, temps) = as_copy_loop(first, None)?;
+    Some((
+        Prologue::Rest {
+            index,
+            ident,
+            temps,
+        },
+        1,
+    ))
+}
+
+/// The prologue items at the start of `stmts`, without checking that they can be moved
+fn find_prologue(stmts: &[Stmt], use_arguments: bool) -> Vec<Item> {
+    let mut items = Vec::new();
+    let mut pos = 0;
+    while let Some(stmt) = stmts.get(pos) {
+        let found = as_default(stmt)
+            .map(|prologue| (prologue, 1))
+            .or_else(|| {
+                use_arguments
+                    .then(|| as_arguments_param(stmt))
+                    .flatten()
+                    .map(|p| (p, 1))
+            })
+            .or_else(|| use_arguments.then(|| as_rest(&stmts[pos..])).flatten());
+        let Some((prologue, len)) = found else {
+            break;
+        };
+        items.push(Item {
+            prologue,
+            span: stmt.span(),
+            len,
+        });
+        pos += len;
+    }
+    items
+}
+
+fn uses_any<'a, N>(ids: impl IntoIterator<Item = &'a Id>, node: &N) -> bool
+where
+    N: VisitWith<IdentUsageFinder<'a>>,
+{
+    ids.into_iter().any(|id| IdentUsageFinder::find(id, node))
+}
+
+/// Finds uses of `arguments`, including the ones in nested functions
+#[derive(Default)]
+struct ArgumentsFinder {
+    found: bool,
+}
+impl Visit for ArgumentsFinder {
+    noop_visit_type!();
+
+    fn visit_ident(&mut self, ident: &Ident) {
+        self.found |= &*ident.sym == "arguments";
+    }
+
+    fn visit_member_prop(&mut self, prop: &MemberProp) {
+        if let MemberProp::Computed(computed) = prop {
+            computed.visit_with(self);
+        }
+    }
+}
+
+fn uses_arguments<N: VisitWith<ArgumentsFinder>>(node: &N) -> bool {
+    let mut finder = ArgumentsFinder::default();
+    node.visit_with(&mut finder);
+    finder.found
+}
+
+/// Whether the statements start with a `"use strict"` directive, which isn't allowed in a
+/// function with default or rest parameters
+fn has_use_strict(stmts: &[Stmt]) -> bool {
+    stmts
+        .iter()
+        .map_while(|stmt| match stmt.as_expr()?.expr.as_lit()? {
+            Lit::Str(str) => Some(str),
+            _ => None,
+        })
+        .any(|str| &*str.value == "use strict")
+}
+
+/// The parameters with the items applied, or `None` if any of them can't be moved into the
+/// parameters while `body` is left in the function
+fn apply(params: &[Pat], items: &[Item], body: &[Stmt]) -> Option<Vec<Pat>> {
+    let mut params = params.to_vec();
+    let body_decls: HashSet<Id> = body.iter().flat_map(collect_decls::<Id, _>).collect();
+
+    for item in items {
+        // Everything from this parameter on can't be used by the default, since it is in its
+        // temporal dead zone, and the parameters after it must be simple so that the defaults
+        // are evaluated in the same order. Literals can be evaluated in any order, so
+        // `if (b === undefined) b = 1; if (a === undefined) a = 2;` is still fine
+        let (index, value) = match &item.prologue {
+            Prologue::Default { param, value } => {
+                let index = params
+                    .iter()
+                    .position(|pat| matches!(pat, Pat::Ident(ident) if ident.to_id() == *param))?;
+                if params[index + 1..].iter().any(|pat| match pat {
+                    Pat::Ident(_) | Pat::Rest(_) => false,
+                    Pat::Assign(assign) => !value.is_lit() && !assign.right.is_lit(),
+                    _ => true,
+                }) {
+                    return None;
+                }
+                (index, Some(value))
+            }
+            Prologue::Param { index, value, .. } => {
+                // A parameter without a default after one with a default doesn't change the
+                // `length` of the function
+                if value.is_none() && !params.iter().any(Pat::is_assign) {
+                    return None;
+                }
+                (*index, value.as_ref())
+            }
+            Prologue::Rest { index, temps, .. } => {
+                if body.iter().any(|stmt| uses_any(temps, stmt)) {
+                    return None;
+                }
+                (*index, None)
+            }
+        };
+        let new_ident = match &item.prologue {
+            Prologue::Param { ident, .. } | Prologue::Rest { ident, .. } => {
+                let declared: Vec<Id> = find_pat_ids(&params);
+                if index != params.len()
+                    || params.last().is_some_and(Pat::is_rest)
+                    || declared.contains(&ident.to_id())
+                    || body_decls.contains(&ident.to_id())
+                {
+                    return None;
+                }
+                Some(ident)
+            }
+            Prologue::Default { .. } => None,
+        };
+        if let Some(value) = value {
+            let later: Vec<Id> = params[index..].iter().flat_map(find_pat_ids).collect();
+            let own = new_ident.map(|ident| ident.to_id());
+            if uses_any(later.iter().chain(&own), &**value)
+                || uses_any(&body_decls, &**value)
+                || uses_arguments(&**value)
+            {
+                return None;
+            }
+        }
+
+        match &item.prologue {
+            Prologue::Default { value, .. } => {
+                let left = params[index].take();
+                params[index] = Pat::Assign(AssignPat {
+                    span: item.span,
+                    left: Box::new(left),
+                    right: value.clone(),
+                });
+            }
+            Prologue::Param { ident, value, .. } => {
+                let left = Pat::Ident(ident.clone());
+                params.push(match value {
+                    Some(value) => Pat::Assign(AssignPat {
+                        span: item.span,
+                        left: Box::new(left),
+                        right: value.clone(),
+                    }),
+                    None => left,
+                });
+            }
+            Prologue::Rest { ident, .. } => params.push(Pat::Rest(RestPat {
+                span: item.span,
+                dot3_token: item.span,
+                arg: Box::new(Pat::Ident(ident.clone())),
+                type_ann: None,
+            })),
+        }
+    }
+    Some(params)
+}
+
+/// Describes the change for eval warnings
+const RESTORE: &str = "restoring default parameters";
+
+impl DefaultParamsVisitor {
+    /// Move the prologue at the start of `stmts` into `params`
+    fn restore(
+        &mut self,
+        span: Span,
+        params: &mut Vec<Pat>,
+        stmts: &mut Vec<Stmt>,
+        use_arguments: bool,
+    ) {
+        if has_use_strict(stmts) {
+            return;
+        }
+        // Duplicate names aren't allowed alongside default parameters
+        let ids: Vec<Id> = find_pat_ids(&*params);
+        if ids.iter().collect::<HashSet<_>>().len() != ids.len() {
+            return;
+        }
+
+        let mut items = find_prologue(stmts, use_arguments);
+        // Take off items from the end until the rest can be applied. Each one that is left in
+        // the body could be what stops an earlier one from being moved
+        while !items.is_empty() {
+            let len: usize = items.iter().map(|item| item.len).sum();
+            let body = &stmts[len..];
+            // `arguments` isn't linked to the parameters once they aren't simple
+            if !body.iter().any(uses_arguments) {
+                if let Some(new_params) = apply(params, &items, body) {
+                    if !self.eval.allows(span, RESTORE) {
+                        return;
+                    }
+                    for item in &items {
+                        if !matches!(item.prologue, Prologue::Rest { .. }) {
+                            self.annotator.annotate(item.span);
+                        }
+                    }
+                    *params = new_params;
+                    stmts.drain(..len);
+                    self.changed = true;
+                    return;
+                }
+            }
+            items.pop();
+        }
+    }
+
+    /// Move the prologue of a function into its parameters, which are `Param`s rather than
+    /// `Pat`s
+    fn restore_function(&mut self, function: &mut Function) {
+        // A generator evaluates its parameters when it is called, but its body on the first
+        // `next()`
+        if function.is_generator {
+            return;
+        }
+        let Some(body) = &mut function.body else {
+            return;
+        };
+        let mut params = function
+            .params
+            .iter_mut()
+            .map(|param| param.pat.take())
+            .collect::<Vec<_>>();
+        self.restore(function.span, &mut params, &mut body.stmts, true);
+
+        let mut params = params.into_iter();
+        for (param, pat) in function.params.iter_mut().zip(&mut params) {
+            param.pat = pat;
+        }
+        function.params.extend(params.map(|pat| Param {
+            span: pat.span(),
+            decorators: vec![],
+            pat,
+        }));
+    }
+}
+
+impl VisitMut for DefaultParamsVisitor {
+    noop_visit_mut_type!();
+
+    fn visit_mut_function(&mut self, function: &mut Function) {
+        self.eval.enter(function);
+        function.visit_mut_children_with(self);
+        self.restore_function(function);
+        self.eval.exit();
+    }
+
+    fn visit_mut_arrow_expr(&mut self, arrow: &mut ArrowExpr) {
+        self.eval.enter(arrow);
+        arrow.visit_mut_children_with(self);
+
+        // `arguments` belongs to the function around the arrow
+        if let BlockStmtOrExpr::BlockStmt(body) = &mut *arrow.body {
+            self.restore(arrow.span, &mut arrow.params, &mut body.stmts, false);
+        }
+        self.eval.exit();
+    }
+}
+
+test!(
+    Default::default(),
+    |_| as_folder(DefaultParamsVisitor::default()),
+    default_params_if,
+    r#"
+    function f(a, b) {
+        if (b === undefined) {
+            b = 5;
+        }
+        if (void 0 === a) a = b;
+        return a + b;
+    }
+    function g(a, b) {
+        if (a === void 0) { a = 1; }
+        if (b === void 0) { b = a; }
+    }
+    var h = (a) => { void 0 === a && (a = {}); return a; };
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(DefaultParamsVisitor::default()),
+    default_params_several,
+    r#"
+    function f(a, b, c) {
+        if (a === void 0) { a = 1; }
+        if (b === void 0) { b = {}; }
+        if (c === void 0) { c = []; }
+        return a + b + c;
+    }
+    function g(a, b) {
+        if (b === void 0) { b = 1; }
+        if (a === void 0) { a = 2; }
+        return a + b;
+    }
+    function h(a, b) {
+        if (b === void 0) { b = foo(); }
+        if (a === void 0) { a = bar(); }
+    }
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(DefaultParamsVisitor::default()),
+    default_params_eval,
+    // The eval could read `arguments[0]` after `b` is set
+    r#"
+    function f(a) {
+        if (a === void 0) { a = 5; }
+        return a;
+    }
+    function g(b) {
+        if (b === void 0) { b = 5; }
+        return eval(s);
+    }
+    var h = (c) => { if (c === void 0) { c = 5; } return () => eval(s); };
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(DefaultParamsVisitor::default()),
+    default_params_arguments,
+    r#"
+    function f(a) {
+        var b = arguments.length > 1 && arguments[1] !== undefined ? arguments[1] : 5;
+        var c = arguments.length > 2 ? arguments[2] : undefined;
+        return a + b + c;
+    }
+    function g() {
+        var a = arguments.length > 0 ? arguments[0] : undefined;
+        return a;
+    }
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(DefaultParamsVisitor::default()),
+    default_params_rest,
+    r#"
+    function ts(a) {
+        var rest = [];
+        for (var _i = 1; _i < arguments.length; _i++) {
+            rest[_i - 1] = arguments[_i];
+        }
+        return rest;
+    }
+    function babel() {
+        for (var _len = arguments.length, args = new Array(_len), _key = 0; _key < _len; _key++) {
+            args[_key] = arguments[_key];
+        }
+        return args;
+    }
+    function slice(a, b) {
+        var c = Array.prototype.slice.call(arguments, 2);
+        return c;
+    }
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(DefaultParamsVisitor::default()),
+    default_params_unsafe,
+    r#"
+    function usesBody(a) {
+        if (a === undefined) { a = g(); }
+        function g() {}
+    }
+    function usesArguments(a) {
+        if (a === undefined) { a = 1; }
+        return arguments[0];
+    }
+    function strict(a) {
+        "use strict";
+        if (a === undefined) { a = 1; }
+    }
+    function* gen(a) {
+        if (a === undefined) { a = 1; }
+    }
+    function later(a, b) {
+        if (a === undefined) { a = b; }
+    }
+    function notFirst(a) {
+        f();
+        if (a === undefined) { a = 1; }
+    }
+    function usesIndex(a) {
+        var rest = [];
+        for (var _i = 1; _i < arguments.length; _i++) {
+            rest[_i - 1] = arguments[_i];
+        }
+        return _i;
+    }
+    "#
+);
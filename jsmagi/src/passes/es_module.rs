@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use swc_atoms::{js_word, JsWord};
+use swc_common::Spanned;
 
 use swc_ecma_ast::{Decl, Expr, ModuleItem, Pat, Prop, PropOrSpread};
 use swc_ecma_transforms_base::rename::rename;
@@ -10,7 +11,12 @@ use swc_ecma_utils::ident::IdentLike;
 use swc_ecma_visit::as_folder;
 use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
 
-use crate::{FromMagiConfig, MagiConfig};
+use crate::{
+    eval::EvalGuard,
+    passes::{Pass, PassId},
+    safety::Annotator,
+    FromMagiConfig, MagiConfig,
+};
 
 // TODO: analyze what the module sets on `exports.*` and collect those into a typescript interface
 // and maybe a comment
@@ -20,16 +26,33 @@ use crate::{FromMagiConfig, MagiConfig};
 /// and renames them to `module, exports, require` if they are found.
 pub struct EsModuleRenameVisitor {
     typescript: bool,
+    eval: EvalGuard,
+    annotator: Annotator,
+    changed: bool,
 }
 impl FromMagiConfig for EsModuleRenameVisitor {
     fn from_config(conf: &MagiConfig) -> Self {
         Self {
             typescript: conf.typescript,
+            eval: EvalGuard::new(conf.eval_safety),
+            annotator: Annotator::new(conf, PassId::EsModuleRename),
+            changed: false,
         }
     }
 }
+impl Pass for EsModuleRenameVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
 
-fn visit_mut_module_items(_typescript: bool, n: &mut Vec<ModuleItem>) -> Option<()> {
+/// Returns `Some(true)` if any of the module functions were renamed
+fn visit_mut_module_items(
+    _typescript: bool,
+    eval: &mut EvalGuard,
+    annotator: &mut Annotator,
+    n: &mut [ModuleItem],
+) -> Option<bool> {
     // TODO: this might benefit from being more general?
 
     // If there is an iife at the root, go into it
@@ -63,6 +86,8 @@ fn visit_mut_module_items(_typescript: bool, n: &mut Vec<ModuleItem>) -> Option<
     // Get the value it is being initialized to
     let init = decl.init.as_deref_mut()?.as_mut_object()?;
 
+    let mut changed = false;
+
     for prop in &mut init.props {
         // TODO: is it actually okay to skip over these?
         let PropOrSpread::Prop(prop) = prop else {
@@ -100,23 +125,37 @@ fn visit_mut_module_items(_typescript: bool, n: &mut Vec<ModuleItem>) -> Option<
             continue;
         }
 
+        // The parameters belong to the module function, so that is the scope that matters
+        eval.enter(key_value.value.as_ref());
+        let allowed = eval.allows(key_value.value.span(), "renaming module parameters");
+        eval.exit();
+        if !allowed {
+            continue;
+        }
+
         let mut renames = HashMap::default();
         renames.insert(p1.id.to_id(), js_word!("module"));
         renames.insert(p2.id.to_id(), JsWord::from("exports"));
         renames.insert(p3.id.to_id(), js_word!("require"));
 
+        annotator.annotate(key_value.value.span());
         let mut renamer = rename(&renames);
         prop.visit_mut_children_with(&mut renamer);
+        changed = true;
     }
 
-    Some(())
+    Some(changed)
 }
 
 impl VisitMut for EsModuleRenameVisitor {
     noop_visit_mut_type!();
 
     fn visit_mut_module_items(&mut self, n: &mut Vec<ModuleItem>) {
-        visit_mut_module_items(self.typescript, n);
+        if visit_mut_module_items(self.typescript, &mut self.eval, &mut self.annotator, n)
+            == Some(true)
+        {
+            self.changed = true;
+        }
 
         n.visit_mut_children_with(self);
     }
@@ -124,14 +163,17 @@ impl VisitMut for EsModuleRenameVisitor {
 
 /// Check if the given idents match `e`, `t`, `n`
 fn idents_match_req<T: IdentLike>(first: &T, second: &T, third: &T) -> bool {
-    first.to_id().0 == JsWord::from("e")
-        && second.to_id().0 == JsWord::from("t")
-        && third.to_id().0 == JsWord::from("n")
+    first.to_id().0 == *"e" && second.to_id().0 == *"t" && third.to_id().0 == *"n"
 }
 
 test!(
     Default::default(),
-    |_| as_folder(EsModuleRenameVisitor { typescript: false }),
+    |_| as_folder(EsModuleRenameVisitor {
+        typescript: false,
+        eval: EvalGuard::default(),
+        annotator: Annotator::default(),
+        changed: false
+    }),
     rename1,
     "(() => { var e1 = { 428: (e, t, n) => { t.thing = 5; let j = n(524); } }; })();" // "(() => { var e1 = { 428: (module, exports, require) => { exports.thing = 5; let j = require(524); } }; })();"
 );
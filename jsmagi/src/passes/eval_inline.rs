@@ -0,0 +1,698 @@
+//! Inline `eval` and `new Function` calls whose code is a constant string.
+//! Example:
+//! ```js
+//! function f(a) {
+//!     eval("let b = a + 1; " + "console.log(b)");
+//!     return new Function("x", "y", "return x + y");
+//! }
+//! ```
+//! becomes
+//! ```js
+//! function f(a) {
+//!     {
+//!         let b = a + 1;
+//!         console.log(b);
+//!     }
+//!     return function(x, y) {
+//!         return x + y;
+//!     };
+//! }
+//! ```
+//! The parsed code is run through the rest of the pipeline before it is inlined.
+//! When the code can't be inlined without changing what it does, such as when the `eval`
+//! declares a `var` in the surrounding function, it is left as-is with a comment containing the
+//! decoded code.
+
+use std::collections::{HashMap, HashSet};
+
+use swc_atoms::{js_word, JsWord};
+use swc_common::{
+    comments::{Comment, CommentKind, Comments},
+    sync::Lrc,
+    BytePos, FileName, Mark, SourceMap, Span, SyntaxContext, DUMMY_SP,
+};
+use swc_ecma_ast::{
+    ArrowExpr, BinaryOp, BindingIdent, BlockStmt, CallExpr, Callee, ClassDecl, Expr, FnDecl,
+    Function, Id, Ident, ImportDefaultSpecifier, ImportNamedSpecifier, ImportStarAsSpecifier, Lit,
+    MemberProp, Module, ModuleItem, NewExpr, ParenExpr, Script, Stmt, VarDecl, VarDeclKind,
+};
+use swc_ecma_parser::parse_file_as_script;
+use swc_ecma_transforms_base::resolver;
+use swc_ecma_transforms_testing::test;
+#[cfg(test)]
+use swc_ecma_visit::as_folder;
+use swc_ecma_visit::{
+    noop_visit_mut_type, noop_visit_type, Visit, VisitMut, VisitMutWith, VisitWith,
+};
+
+use crate::{
+    eval::contains_eval,
+    passes::{Pass, PassId},
+    safety::Annotator,
+    syntax, FromMagiConfig, MagiConfig, TARGET,
+};
+
+pub struct EvalInline {
+    /// Used to run the pipeline over the code being inlined
+    conf: MagiConfig,
+    /// The variables declared in each of the scopes being visited, innermost last
+    scopes: Vec<Vec<Id>>,
+    /// The calls that have already been given a comment, since the pass is run repeatedly
+    commented: HashSet<BytePos>,
+    /// Set when the statement that was just visited became the block of an inlined `eval`
+    inlined_block: bool,
+    annotator: Annotator,
+    changed: bool,
+}
+impl FromMagiConfig for EvalInline {
+    fn from_config(conf: &MagiConfig) -> Self {
+        Self {
+            conf: conf.clone(),
+            scopes: Vec::new(),
+            commented: HashSet::new(),
+            inlined_block: false,
+            annotator: Annotator::new(conf, PassId::EvalInline),
+            changed: false,
+        }
+    }
+}
+impl Pass for EvalInline {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+impl EvalInline {
+    fn enter<N>(&mut self, node: &N)
+    where
+        N: VisitWith<ScopeBindings>,
+    {
+        let mut bindings = ScopeBindings::default();
+        node.visit_children_with(&mut bindings);
+        self.scopes.push(bindings.ids);
+    }
+
+    /// Whether there is a variable with this name in any of the scopes being visited
+    fn is_declared(&self, sym: &JsWord) -> bool {
+        self.scopes.iter().flatten().any(|(other, _)| other == sym)
+    }
+
+    /// Parse the code and run the pipeline over it, so the inlined code is as readable as the
+    /// code around it.
+    /// Returns the code and the syntax context of the variables that it doesn't declare.
+    fn parse(&self, code: &str) -> Option<(Script, SyntaxContext)> {
+        let cm: Lrc<SourceMap> = Default::default();
+        let fm = cm.new_source_file(FileName::Anon, code.to_owned());
+        let mut errors = Vec::new();
+        let mut script = parse_file_as_script(&fm, syntax(), TARGET, None, &mut errors).ok()?;
+        if !errors.is_empty() {
+            return None;
+        }
+
+        let unresolved_mark = Mark::new();
+        script.visit_mut_with(&mut resolver(unresolved_mark, Mark::new(), false));
+
+        // Any comments from the nested passes would refer to positions in `code`
+        let conf = MagiConfig {
+            comments: Default::default(),
+            ..self.conf.clone()
+        };
+        script.visit_mut_with(&mut conf.get_passes());
+
+        // The positions refer to `code` rather than the file being transformed
+        script.visit_mut_with(&mut ClearSpans);
+
+        Some((script, SyntaxContext::empty().apply_mark(unresolved_mark)))
+    }
+
+    /// Make the variables of `code` that it doesn't declare refer to the variables around the
+    /// `eval`, like they do when the `eval` runs.
+    /// Returns `None` if it isn't clear which variable one of them refers to.
+    fn resolve_free_idents<N>(&self, code: &mut N, unresolved: SyntaxContext) -> Option<()>
+    where
+        N: VisitWith<CollectFreeIdents> + VisitMutWith<FreeIdents>,
+    {
+        let mut collect = CollectFreeIdents {
+            unresolved,
+            syms: HashSet::new(),
+        };
+        code.visit_with(&mut collect);
+
+        let mut free = FreeIdents {
+            unresolved,
+            ctxts: HashMap::new(),
+        };
+        for sym in collect.syms {
+            for scope in self.scopes.iter().rev() {
+                let mut matching = scope.iter().filter(|(other, _)| *other == sym);
+                let Some((_, ctxt)) = matching.next() else {
+                    continue;
+                };
+                // The scopes are whole functions, so a name can be declared in several blocks
+                if matching.any(|(_, other)| other != ctxt) {
+                    return None;
+                }
+
+                free.ctxts.insert(sym.clone(), *ctxt);
+                break;
+            }
+        }
+
+        code.visit_mut_with(&mut free);
+        Some(())
+    }
+
+    /// Inline `eval("...")` in statement position as a block, returning whether it was inlined
+    fn inline_eval_stmt(&mut self, stmt: &mut Stmt) -> Option<()> {
+        let expr_stmt = stmt.as_expr()?;
+        let call = expr_stmt.expr.as_call()?;
+        let code = self.direct_eval_code(call)?;
+        let span = call.span;
+
+        let inlined = self.parse(&code).and_then(|(mut script, unresolved)| {
+            // The `var`s and functions of the `eval` are declared in the surrounding function,
+            // while a block would only hide its `let`s and `const`s.
+            if declares_hoisted(&script) {
+                return None;
+            }
+            self.resolve_free_idents(&mut script, unresolved)?;
+            Some(script)
+        });
+
+        match inlined {
+            Some(script) => {
+                let span = expr_stmt.span;
+                *stmt = Stmt::Block(BlockStmt {
+                    span,
+                    stmts: script.body,
+                });
+                self.annotator.annotate(span);
+                self.changed = true;
+                Some(())
+            }
+            None => {
+                self.comment(span, &code);
+                None
+            }
+        }
+    }
+
+    /// The statements that replace `stmt` in a list of statements. The block of an inlined
+    /// `eval` is spliced into the list, unless it needs to hide its `let`s and `const`s.
+    fn splice_inlined(&mut self, stmt: Stmt) -> Vec<Stmt> {
+        match stmt {
+            Stmt::Block(block)
+                if std::mem::take(&mut self.inlined_block) && !declares_lexical(&block.stmts) =>
+            {
+                let mut stmts = block.stmts;
+                // The annotation and the source map position belong to the first statement now
+                if let Some(first) = stmts.first_mut() {
+                    first.visit_mut_with(&mut FirstSpan(Some(block.span)));
+                }
+                stmts
+            }
+            stmt => vec![stmt],
+        }
+    }
+
+    /// Inline `eval("...")` in expression position, if the code is a single expression
+    fn inline_eval_expr(&mut self, expr: &mut Expr) -> Option<()> {
+        let call = expr.as_call()?;
+        let code = self.direct_eval_code(call)?;
+        let span = call.span;
+
+        let inlined = self.parse(&code).and_then(|(mut script, unresolved)| {
+            if script.body.len() != 1 {
+                return None;
+            }
+            let mut value = script.body.pop()?.expr()?.expr;
+            self.resolve_free_idents(&mut value, unresolved)?;
+            Some(value)
+        });
+
+        match inlined {
+            Some(value) => {
+                *expr = Expr::Paren(ParenExpr { span, expr: value });
+                self.annotator.annotate(span);
+                self.changed = true;
+            }
+            None => self.comment(span, &code),
+        }
+
+        Some(())
+    }
+
+    /// Inline `new Function("a", "b", "return a + b")` as a function expression
+    fn inline_function(&mut self, expr: &mut Expr) -> Option<()> {
+        let (span, callee, args) = match &*expr {
+            Expr::New(NewExpr {
+                span, callee, args, ..
+            }) => (*span, &**callee, args.as_deref().unwrap_or_default()),
+            Expr::Call(CallExpr {
+                span,
+                callee: Callee::Expr(callee),
+                args,
+                ..
+            }) => (*span, &**callee, args.as_slice()),
+            _ => return None,
+        };
+
+        let callee = callee.as_ident()?;
+        if callee.sym != *"Function" || self.is_declared(&callee.sym) {
+            return None;
+        }
+
+        let mut strings = args
+            .iter()
+            .map(|arg| arg.spread.is_none().then(|| fold_string(&arg.expr))?)
+            .collect::<Option<Vec<String>>>()?;
+        let body = strings.pop().unwrap_or_default();
+        let code = format!("(function({}\n) {{\n{}\n}});", strings.join(","), body);
+
+        // The code is run in the global scope, so its variables are left unresolved, and the
+        // hygiene pass renames any local variables that would hide them.
+        let inlined = self.parse(&code).and_then(|(mut script, _)| {
+            if script.body.len() != 1 {
+                return None;
+            }
+            let value = script.body.pop()?.expr()?.expr;
+            match *value {
+                Expr::Paren(ParenExpr { expr, .. }) => Some(expr),
+                value => Some(Box::new(value)),
+            }
+        });
+
+        match inlined {
+            Some(mut value) => {
+                // Keep the position of the call, so the function can be annotated
+                if let Expr::Fn(func) = &mut *value {
+                    func.function.span = span;
+                }
+                *expr = *value;
+                self.annotator.annotate(span);
+                self.changed = true;
+            }
+            None => self.comment(span, &code),
+        }
+
+        Some(())
+    }
+
+    /// The code of a direct `eval` call, if it is a constant
+    fn direct_eval_code(&self, call: &CallExpr) -> Option<String> {
+        let callee = call.callee.as_expr()?.as_ident()?;
+        if callee.sym != js_word!("eval") || self.is_declared(&callee.sym) {
+            return None;
+        }
+
+        // Only the first argument is evaluated, but the rest could have side-effects
+        let [arg] = call.args.as_slice() else {
+            return None;
+        };
+        if arg.spread.is_some() {
+            return None;
+        }
+
+        fold_string(&arg.expr)
+    }
+
+    /// Leave a comment with the decoded code before the call
+    fn comment(&mut self, span: Span, code: &str) {
+        if span.is_dummy() || !self.commented.insert(span.lo) {
+            return;
+        }
+
+        let code = code.trim().replace("*/", "*\\/");
+        let separator = if code.contains('\n') { "\n" } else { " " };
+
+        self.conf.comments.add_leading(
+            span.lo,
+            Comment {
+                kind: CommentKind::Block,
+                span: DUMMY_SP,
+                text: format!(" decoded:{}{}{}", separator, code, separator).into(),
+            },
+        );
+    }
+}
+
+impl VisitMut for EvalInline {
+    noop_visit_mut_type!();
+
+    fn visit_mut_module(&mut self, m: &mut Module) {
+        // `new Function` can't be found with `EvalFinder`, so it isn't worth skipping the search
+        self.enter(m);
+        m.visit_mut_children_with(self);
+        self.scopes.pop();
+    }
+
+    fn visit_mut_script(&mut self, s: &mut Script) {
+        self.enter(s);
+        s.visit_mut_children_with(self);
+        self.scopes.pop();
+    }
+
+    fn visit_mut_function(&mut self, f: &mut Function) {
+        self.enter(f);
+        f.visit_mut_children_with(self);
+        self.scopes.pop();
+    }
+
+    fn visit_mut_arrow_expr(&mut self, a: &mut ArrowExpr) {
+        self.enter(a);
+        a.visit_mut_children_with(self);
+        self.scopes.pop();
+    }
+
+    fn visit_mut_stmt(&mut self, stmt: &mut Stmt) {
+        let inlined = contains_eval(stmt, false) && self.inline_eval_stmt(stmt).is_some();
+
+        stmt.visit_mut_children_with(self);
+        // Set after the children, which may have inlined statements of their own
+        self.inlined_block = inlined;
+    }
+
+    fn visit_mut_stmts(&mut self, stmts: &mut Vec<Stmt>) {
+        let mut new_stmts = Vec::with_capacity(stmts.len());
+        for mut stmt in stmts.drain(..) {
+            self.inlined_block = false;
+            stmt.visit_mut_with(self);
+            new_stmts.extend(self.splice_inlined(stmt));
+        }
+        *stmts = new_stmts;
+    }
+
+    fn visit_mut_module_items(&mut self, items: &mut Vec<ModuleItem>) {
+        let mut new_items = Vec::with_capacity(items.len());
+        for mut item in items.drain(..) {
+            self.inlined_block = false;
+            item.visit_mut_with(self);
+            match item {
+                ModuleItem::Stmt(stmt) => {
+                    new_items.extend(self.splice_inlined(stmt).into_iter().map(ModuleItem::Stmt))
+                }
+                item => new_items.push(item),
+            }
+        }
+        *items = new_items;
+    }
+
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        if contains_eval(expr, false) {
+            self.inline_eval_expr(expr);
+        }
+        self.inline_function(expr);
+
+        expr.visit_mut_children_with(self);
+    }
+}
+
+/// Fold an expression that builds a constant string, such as `"ev" + "al"`
+fn fold_string(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(Lit::Str(s)) => Some(s.value.to_string()),
+        Expr::Tpl(tpl) if tpl.exprs.is_empty() => {
+            Some(tpl.quasis.first()?.cooked.as_ref()?.to_string())
+        }
+        Expr::Paren(paren) => fold_string(&paren.expr),
+        Expr::Bin(bin) if bin.op == BinaryOp::Add => {
+            let left = fold_string(&bin.left);
+            let right = fold_string(&bin.right);
+            // At least one side has to be a string for `+` to concatenate
+            match (left, right) {
+                (Some(left), Some(right)) => Some(left + right.as_str()),
+                (Some(left), None) => Some(left + fold_number(&bin.right)?.as_str()),
+                (None, Some(right)) => Some(fold_number(&bin.left)? + right.as_str()),
+                (None, None) => None,
+            }
+        }
+        Expr::Call(call) => {
+            let callee = call.callee.as_expr()?.as_member()?;
+            let MemberProp::Ident(prop) = &callee.prop else {
+                return None;
+            };
+            if call.args.iter().any(|arg| arg.spread.is_some()) {
+                return None;
+            }
+
+            match &*callee.obj {
+                // `String.fromCharCode(104, 105)`
+                Expr::Ident(obj) if obj.sym == *"String" && prop.sym == *"fromCharCode" => {
+                    let units = call
+                        .args
+                        .iter()
+                        .map(|arg| match &*arg.expr {
+                            Expr::Lit(Lit::Num(num)) => u16::try_from(num.value as i64).ok(),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<u16>>>()?;
+                    String::from_utf16(&units).ok()
+                }
+                // `["ev", "al"].join("")`
+                Expr::Array(array) if prop.sym == *"join" => {
+                    let separator = match call.args.as_slice() {
+                        [] => ",".to_owned(),
+                        [separator] => fold_string(&separator.expr)?,
+                        _ => return None,
+                    };
+                    let parts = array
+                        .elems
+                        .iter()
+                        .map(|elem| {
+                            let elem = elem.as_ref()?;
+                            elem.spread.is_none().then(|| fold_string(&elem.expr))?
+                        })
+                        .collect::<Option<Vec<String>>>()?;
+                    Some(parts.join(&separator))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Integers are the only numbers whose string form is simple to get right
+fn fold_number(expr: &Expr) -> Option<String> {
+    let Expr::Lit(Lit::Num(num)) = expr else {
+        return None;
+    };
+    (num.value.fract() == 0.0 && num.value.abs() < 1e21).then(|| (num.value as i64).to_string())
+}
+
+/// Whether the statements declare a `let`, `const` or class, which a block keeps from leaking.
+/// The `var`s and functions were already ruled out by [`declares_hoisted`].
+fn declares_lexical(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(Stmt::is_decl)
+}
+
+/// Replaces the first span it visits, which is the span of the node itself
+struct FirstSpan(Option<Span>);
+impl VisitMut for FirstSpan {
+    noop_visit_mut_type!();
+
+    fn visit_mut_span(&mut self, span: &mut Span) {
+        if let Some(new_span) = self.0.take() {
+            *span = new_span.with_ctxt(span.ctxt);
+        }
+    }
+}
+
+/// Whether the code declares a `var` or function that would belong to the function around the
+/// `eval`.
+fn declares_hoisted(script: &Script) -> bool {
+    #[derive(Default)]
+    struct Finder {
+        found: bool,
+    }
+    impl Visit for Finder {
+        noop_visit_type!();
+
+        fn visit_var_decl(&mut self, v: &VarDecl) {
+            if v.kind == VarDeclKind::Var {
+                self.found = true;
+            }
+            v.visit_children_with(self);
+        }
+
+        fn visit_fn_decl(&mut self, _: &FnDecl) {
+            self.found = true;
+        }
+
+        fn visit_function(&mut self, _: &Function) {}
+
+        fn visit_arrow_expr(&mut self, _: &ArrowExpr) {}
+    }
+
+    let mut finder = Finder::default();
+    script.visit_with(&mut finder);
+    finder.found
+}
+
+/// Collects the variables that are declared directly in a scope, rather than in a function
+/// inside of it.
+#[derive(Default)]
+struct ScopeBindings {
+    ids: Vec<Id>,
+}
+impl Visit for ScopeBindings {
+    noop_visit_type!();
+
+    fn visit_binding_ident(&mut self, i: &BindingIdent) {
+        self.ids.push(i.id.to_id());
+    }
+
+    fn visit_fn_decl(&mut self, f: &FnDecl) {
+        self.ids.push(f.ident.to_id());
+    }
+
+    fn visit_class_decl(&mut self, c: &ClassDecl) {
+        self.ids.push(c.ident.to_id());
+        c.class.visit_with(self);
+    }
+
+    fn visit_import_default_specifier(&mut self, s: &ImportDefaultSpecifier) {
+        self.ids.push(s.local.to_id());
+    }
+
+    fn visit_import_named_specifier(&mut self, s: &ImportNamedSpecifier) {
+        self.ids.push(s.local.to_id());
+    }
+
+    fn visit_import_star_as_specifier(&mut self, s: &ImportStarAsSpecifier) {
+        self.ids.push(s.local.to_id());
+    }
+
+    fn visit_function(&mut self, _: &Function) {}
+
+    fn visit_arrow_expr(&mut self, _: &ArrowExpr) {}
+}
+
+/// Collects the names of the variables that the inlined code doesn't declare
+struct CollectFreeIdents {
+    unresolved: SyntaxContext,
+    syms: HashSet<JsWord>,
+}
+impl Visit for CollectFreeIdents {
+    noop_visit_type!();
+
+    fn visit_ident(&mut self, i: &Ident) {
+        if i.span.ctxt == self.unresolved {
+            self.syms.insert(i.sym.clone());
+        }
+    }
+}
+
+/// Gives the variables that the inlined code doesn't declare the context of the variable they
+/// refer to.
+struct FreeIdents {
+    unresolved: SyntaxContext,
+    ctxts: HashMap<JsWord, SyntaxContext>,
+}
+impl VisitMut for FreeIdents {
+    noop_visit_mut_type!();
+
+    fn visit_mut_ident(&mut self, i: &mut Ident) {
+        if i.span.ctxt != self.unresolved {
+            return;
+        }
+        if let Some(ctxt) = self.ctxts.get(&i.sym) {
+            i.span.ctxt = *ctxt;
+        }
+    }
+}
+
+/// Removes the positions from the spans, while keeping their syntax context
+struct ClearSpans;
+impl VisitMut for ClearSpans {
+    noop_visit_mut_type!();
+
+    fn visit_mut_span(&mut self, span: &mut Span) {
+        *span = DUMMY_SP.with_ctxt(span.ctxt);
+    }
+}
+
+#[cfg(test)]
+fn tr(t: &swc_ecma_transforms_testing::Tester<'_>) -> impl swc_ecma_visit::Fold {
+    use swc_common::chain;
+    use swc_ecma_transforms_base::{fixer::fixer, hygiene::hygiene};
+
+    let conf = MagiConfig {
+        comments: (*t.comments).clone(),
+        ..Default::default()
+    };
+
+    chain!(
+        resolver(Mark::new(), Mark::new(), false),
+        as_folder(EvalInline::from_config(&conf)),
+        hygiene(),
+        fixer(None),
+    )
+}
+
+test!(
+    Default::default(),
+    |t| tr(t),
+    eval_inline_stmt,
+    "function f(a) { eval(\"let b = a + 1; \" + \"console.log(b)\"); }"
+);
+
+test!(
+    Default::default(),
+    |t| tr(t),
+    eval_inline_expr,
+    "function f(a) { return eval(`a * 2`) + 1; }"
+);
+
+test!(
+    Default::default(),
+    |t| tr(t),
+    eval_inline_pipeline,
+    // The inlined code goes through the other passes, including this one
+    "eval(\"a(), b(), eval('c(void 0)')\");"
+);
+
+test!(
+    Default::default(),
+    |t| tr(t),
+    eval_inline_fold,
+    "eval(String.fromCharCode(102, 40, 41)); eval(['g', '()'].join(''));"
+);
+
+test!(
+    Default::default(),
+    |t| tr(t),
+    eval_inline_splice,
+    // Only a `let` needs the block, and an `if` needs a single statement
+    "function f(x) { eval('a(); b()'); eval('let c = 1; d(c)'); if (x) eval('e()'); g(); }"
+);
+
+test!(
+    Default::default(),
+    |t| tr(t),
+    eval_inline_new_function,
+    "function f(x) { return new Function('x', 'y', 'return x + y'); }"
+);
+
+test!(
+    Default::default(),
+    |t| tr(t),
+    eval_inline_function_global_scope,
+    // The `z` inside of the function is the global one, not the parameter
+    "function f(z) { return Function('return z'); }"
+);
+
+test!(
+    Default::default(),
+    |t| tr(t),
+    eval_inline_hoisted_var,
+    // The `var` belongs to `f`, so the `eval` can't become a block
+    "function f() { eval('var a = 1'); return a; }"
+);
+
+test!(
+    Default::default(),
+    |t| tr(t),
+    eval_inline_not_constant,
+    "function f(code) { eval(code); let eval2 = eval; eval2('a'); }"
+);
@@ -0,0 +1,431 @@
+//! Restructure minified control flow into `if` statements that are easier to follow.
+//! Example:
+//! ```js
+//! function f(a) {
+//!     if (a) if (a.b) g();
+//!     return a ? 1 : b ? 2 : 3;
+//! }
+//! ```
+//! becomes
+//! ```js
+//! function f(a) {
+//!     if (a && a.b) {
+//!         g();
+//!     }
+//!     if (a) {
+//!         return 1;
+//!     }
+//!     if (b) {
+//!         return 2;
+//!     }
+//!     return 3;
+//! }
+//! ```
+//! One level of ternaries is usually fine, so only nested ones are expanded. Besides `return`s,
+//! they are expanded when assigned to a variable or used to initialize a declaration, as an
+//! `if`-`else if` chain which assigns each value.
+//! Every `if` and loop body is also put in braces.
+
+use swc_common::{util::take::Take, Mark, Span, Spanned, SyntaxContext};
+use swc_ecma_ast::{
+    AssignExpr, AssignOp, BinExpr, BinaryOp, BindingIdent, BlockStmt, CondExpr, Decl, DoWhileStmt,
+    Expr, ExprStmt, ForInStmt, ForOfStmt, ForStmt, Ident, IfStmt, ModuleItem, ParenExpr, Pat,
+    PatOrExpr, ReturnStmt, Stmt, VarDecl, VarDeclKind, VarDeclarator, WhileStmt,
+};
+use swc_ecma_transforms_testing::test;
+use swc_ecma_utils::IdentUsageFinder;
+#[cfg(test)]
+use swc_ecma_visit::as_folder;
+use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
+
+use crate::{passes::Pass, util::replace_entries, FromMagiConfig, MagiConfig};
+
+#[derive(Default)]
+pub struct IfChainVisitor {
+    changed: bool,
+}
+impl FromMagiConfig for IfChainVisitor {
+    fn from_config(_conf: &MagiConfig) -> Self {
+        Self::default()
+    }
+}
+impl Pass for IfChainVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// The ternary in `expr`, if it is one
+fn as_cond(expr: &Expr) -> Option<&CondExpr> {
+    expr.unwrap_parens().as_cond()
+}
+
+/// Whether `expr` is a ternary with another ternary as one of its values
+fn is_nested_cond(expr: &Expr) -> bool {
+    as_cond(expr).is_some_and(|cond| as_cond(&cond.cons).is_some() || as_cond(&cond.alt).is_some())
+}
+
+fn block(span: Span, stmts: Vec<Stmt>) -> Box<Stmt> {
+    Box::new(Stmt::Block(BlockStmt { span, stmts }))
+}
+
+/// `if (test) { make(cons) } else if ... else { make(alt) }`, expanding each nested ternary
+fn assign_chain(cond: &CondExpr, make: &impl Fn(Box<Expr>) -> Stmt) -> Stmt {
+    let branch = |value: &Expr| match as_cond(value) {
+        Some(cond) => block(cond.span, vec![assign_chain(cond, make)]),
+        None => block(value.span(), vec![make(Box::new(value.clone()))]),
+    };
+
+    let alt = match as_cond(&cond.alt) {
+        // `else if`
+        Some(alt) => Box::new(assign_chain(alt, make)),
+        None => branch(&cond.alt),
+    };
+
+    Stmt::If(IfStmt {
+        span: cond.span,
+        test: cond.test.clone(),
+        cons: branch(&cond.cons),
+        alt: Some(alt),
+    })
+}
+
+/// `if (test) { return cons; } return alt;`, expanding each nested ternary
+fn return_chain(cond: &CondExpr, span: Span, out: &mut Vec<Stmt>) {
+    let branch = |value: &Expr| {
+        let mut stmts = Vec::new();
+        match as_cond(value) {
+            Some(cond) => return_chain(cond, span, &mut stmts),
+            None => stmts.push(make_return(span, value)),
+        }
+        block(value.span(), stmts)
+    };
+
+    out.push(Stmt::If(IfStmt {
+        span: cond.span,
+        test: cond.test.clone(),
+        cons: branch(&cond.cons),
+        alt: None,
+    }));
+    match as_cond(&cond.alt) {
+        Some(alt) => return_chain(alt, span, out),
+        None => out.push(make_return(span, &cond.alt)),
+    }
+}
+
+fn make_return(span: Span, value: &Expr) -> Stmt {
+    Stmt::Return(ReturnStmt {
+        span,
+        arg: Some(Box::new(value.clone())),
+    })
+}
+
+/// `ident = value;`
+fn make_assign(span: Span, ident: &Ident, value: Box<Expr>) -> Stmt {
+    Stmt::Expr(ExprStmt {
+        span,
+        expr: Box::new(Expr::Assign(AssignExpr {
+            span,
+            op: AssignOp::Assign,
+            left: PatOrExpr::Pat(Box::new(Pat::Ident(BindingIdent::from(ident.clone())))),
+            right: value,
+        })),
+    })
+}
+
+fn make_decl(span: Span, kind: VarDeclKind, name: Pat, init: Option<Box<Expr>>) -> Stmt {
+    Stmt::Decl(Decl::Var(Box::new(VarDecl {
+        span,
+        kind,
+        declare: false,
+        decls: vec![VarDeclarator {
+            span,
+            name,
+            init,
+            definite: false,
+        }],
+    })))
+}
+
+/// Expand a nested ternary that is returned, assigned or declared into `if` statements
+fn expand_nested_cond(stmt: &Stmt) -> Option<Vec<Stmt>> {
+    match stmt {
+        // `return a ? b : c ? d : e;`
+        Stmt::Return(ReturnStmt {
+            span,
+            arg: Some(arg),
+        }) if is_nested_cond(arg) => {
+            let mut out = Vec::new();
+            return_chain(as_cond(arg)?, *span, &mut out);
+            Some(out)
+        }
+        // `x = a ? b : c ? d : e;`
+        Stmt::Expr(ExprStmt { span, expr }) => {
+            let assign = expr.as_assign()?;
+            if assign.op != AssignOp::Assign || !is_nested_cond(&assign.right) {
+                return None;
+            }
+            // Assigning to a property would evaluate the object after the condition
+            let ident = match &assign.left {
+                PatOrExpr::Pat(pat) => match &**pat {
+                    Pat::Ident(ident) => &ident.id,
+                    Pat::Expr(expr) => expr.as_ident()?,
+                    _ => return None,
+                },
+                PatOrExpr::Expr(expr) => expr.as_ident()?,
+            };
+
+            let make = |value| make_assign(*span, ident, value);
+            Some(vec![assign_chain(as_cond(&assign.right)?, &make)])
+        }
+        // `var x = a ? b : c ? d : e;`
+        Stmt::Decl(Decl::Var(var)) if !var.declare => {
+            let [decl] = var.decls.as_slice() else {
+                return None;
+            };
+            let init = decl.init.as_deref().filter(|init| is_nested_cond(init))?;
+            let cond = as_cond(init)?;
+
+            // A `let` can't be used before it is initialized, and a `const` has to be
+            // initialized, so those go through a temporary variable unless that can't matter
+            let direct = decl.name.as_ident().filter(|ident| match var.kind {
+                VarDeclKind::Var => true,
+                VarDeclKind::Let => !IdentUsageFinder::find(&ident.to_id(), init),
+                VarDeclKind::Const => false,
+            });
+
+            let mut out = Vec::new();
+            if let Some(ident) = direct {
+                out.push(make_decl(var.span, var.kind, decl.name.clone(), None));
+                let make = |value| make_assign(var.span, &ident.id, value);
+                out.push(assign_chain(cond, &make));
+            } else {
+                let new_ctxt = SyntaxContext::empty().apply_mark(Mark::fresh(Mark::root()));
+                let tmp = Ident::new("tmp".into(), var.span.with_ctxt(new_ctxt));
+                out.push(make_decl(
+                    var.span,
+                    VarDeclKind::Let,
+                    Pat::Ident(tmp.clone().into()),
+                    None,
+                ));
+                let make = |value| make_assign(var.span, &tmp, value);
+                out.push(assign_chain(cond, &make));
+                out.push(make_decl(
+                    var.span,
+                    var.kind,
+                    decl.name.clone(),
+                    Some(Box::new(Expr::Ident(tmp))),
+                ));
+            }
+            Some(out)
+        }
+        _ => None,
+    }
+}
+
+/// Put the statement in braces, if it isn't already.
+/// Returns whether it was changed.
+fn add_braces(body: &mut Box<Stmt>) -> bool {
+    if body.is_block() {
+        return false;
+    }
+
+    let span = body.span();
+    let stmt = body.take();
+    **body = Stmt::Block(BlockStmt {
+        span,
+        stmts: vec![*stmt],
+    });
+    true
+}
+
+/// `a` as the left side of `a && b`
+fn and_operand(expr: Box<Expr>) -> Box<Expr> {
+    match &*expr {
+        Expr::Bin(BinExpr {
+            op: BinaryOp::LogicalOr | BinaryOp::NullishCoalescing,
+            ..
+        })
+        | Expr::Cond(_)
+        | Expr::Assign(_)
+        | Expr::Seq(_)
+        | Expr::Arrow(_)
+        | Expr::Yield(_) => Box::new(Expr::Paren(ParenExpr {
+            span: expr.span(),
+            expr,
+        })),
+        _ => expr,
+    }
+}
+
+/// `if (a) { if (b) { x } }` into `if (a && b) { x }`, when neither has an `else`.
+/// Returns whether it was changed.
+fn merge_nested_if(if_stmt: &mut IfStmt) -> bool {
+    if if_stmt.alt.is_some() {
+        return false;
+    }
+    let inner = match &mut *if_stmt.cons {
+        Stmt::Block(BlockStmt { stmts, .. }) if stmts.len() == 1 => &mut stmts[0],
+        stmt => stmt,
+    };
+    let Stmt::If(IfStmt {
+        test, cons, alt, ..
+    }) = inner
+    else {
+        return false;
+    };
+    if alt.is_some() {
+        return false;
+    }
+
+    let test = test.take();
+    let cons = cons.take();
+    let outer = if_stmt.test.take();
+    *if_stmt.test = Expr::Bin(BinExpr {
+        span: if_stmt.span,
+        op: BinaryOp::LogicalAnd,
+        left: and_operand(outer),
+        right: and_operand(test),
+    });
+    if_stmt.cons = cons;
+    true
+}
+
+impl VisitMut for IfChainVisitor {
+    noop_visit_mut_type!();
+
+    fn visit_mut_stmts(&mut self, stmts: &mut Vec<Stmt>) {
+        stmts.visit_mut_children_with(self);
+
+        if replace_entries(stmts, expand_nested_cond) {
+            self.changed = true;
+            // The new `if`s need to be merged and given braces too
+            stmts.visit_mut_children_with(self);
+        }
+    }
+
+    fn visit_mut_module_items(&mut self, items: &mut Vec<ModuleItem>) {
+        items.visit_mut_children_with(self);
+
+        let replaced = replace_entries(items, |item| {
+            let ModuleItem::Stmt(stmt) = item else {
+                return None;
+            };
+            expand_nested_cond(stmt)
+        });
+        if replaced {
+            self.changed = true;
+            items.visit_mut_children_with(self);
+        }
+    }
+
+    // The braces are added first, so that the bodies are visited as lists of statements
+    fn visit_mut_if_stmt(&mut self, if_stmt: &mut IfStmt) {
+        self.changed |= add_braces(&mut if_stmt.cons);
+        if let Some(alt) = &mut if_stmt.alt {
+            // `else if` is clearer without the braces
+            if !alt.is_if_stmt() {
+                self.changed |= add_braces(alt);
+            }
+        }
+
+        if_stmt.visit_mut_children_with(self);
+
+        // `else { if (b) {} }` into `else if (b) {}`
+        if let Some(alt) = &mut if_stmt.alt {
+            if let Stmt::Block(BlockStmt { stmts, .. }) = &mut **alt {
+                if let [Stmt::If(_)] = stmts.as_slice() {
+                    let inner = stmts.pop().unwrap();
+                    **alt = inner;
+                    self.changed = true;
+                }
+            }
+        }
+
+        while merge_nested_if(if_stmt) {
+            self.changed = true;
+        }
+    }
+
+    fn visit_mut_for_stmt(&mut self, stmt: &mut ForStmt) {
+        self.changed |= add_braces(&mut stmt.body);
+        stmt.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_for_in_stmt(&mut self, stmt: &mut ForInStmt) {
+        self.changed |= add_braces(&mut stmt.body);
+        stmt.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_for_of_stmt(&mut self, stmt: &mut ForOfStmt) {
+        self.changed |= add_braces(&mut stmt.body);
+        stmt.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_while_stmt(&mut self, stmt: &mut WhileStmt) {
+        self.changed |= add_braces(&mut stmt.body);
+        stmt.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_do_while_stmt(&mut self, stmt: &mut DoWhileStmt) {
+        self.changed |= add_braces(&mut stmt.body);
+        stmt.visit_mut_children_with(self);
+    }
+}
+
+test!(
+    Default::default(),
+    |_| as_folder(IfChainVisitor::default()),
+    if_chain_readme,
+    "function f(a) { if (a) if (a.b) g(); return a ? 1 : b ? 2 : 3; }"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(IfChainVisitor::default()),
+    if_chain_merge,
+    r#"
+    if (c) if (undefined === e1.params) { f(); }
+    if (a || b) { if (c ?? d) f(); }
+    if (a) if (b) f(); else g();
+    if (a) { if (b) f(); } else g();
+    if (a) { if (b) f(); g(); }
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(IfChainVisitor::default()),
+    if_chain_braces,
+    r#"
+    if (a) f(); else if (b) g(); else h();
+    if (a) { f(); } else { if (b) g(); else { if (c) h(); } }
+    for (;;) f();
+    for (x in y) f();
+    for (x of y) f();
+    while (a) f();
+    do f(); while (a);
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(IfChainVisitor::default()),
+    if_chain_assign,
+    r#"
+    x = a ? 1 : b ? 2 : 3;
+    o.x = a ? 1 : b ? 2 : 3;
+    y = a ? 1 : 2;
+    var v = a ? b ? 1 : 2 : 3;
+    let l = a ? 1 : b ? 2 : 3;
+    let s = a ? s : b ? 2 : 3;
+    const c = a ? 1 : b ? 2 : 3;
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(IfChainVisitor::default()),
+    if_chain_return,
+    "function f() { if (x) return a ? b ? 1 : 2 : c ? 3 : 4; return a ? 1 : 2; }"
+);
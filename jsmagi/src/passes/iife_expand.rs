@@ -1,7 +1,12 @@
 use std::collections::HashMap;
 
 use crate::{
+    access::{Access, AccessAnalysis},
+    eval::{track_eval_scopes, EvalGuard},
+    passes::{Pass, PassId},
     rename::RenameIdentPass,
+    safety::Annotator,
+    trust::Trust,
     util::{
         extract_or_initializer_with_assign, get_assign_eq_expr, make_empty_object, make_undefined,
         NiceAccess, Remapper,
@@ -12,24 +17,49 @@ use crate::{
 use swc_common::chain;
 use swc_common::{Mark, SyntaxContext};
 use swc_ecma_ast::{
-    op, AssignExpr, BinExpr, BindingIdent, CallExpr, Decl, Expr, ExprOrSpread, ExprStmt, Id, Ident,
-    ModuleItem, Pat, PatOrExpr, Stmt, VarDecl, VarDeclKind, VarDeclarator,
+    op, ArrowExpr, AssignExpr, BinExpr, BindingIdent, CallExpr, Decl, Expr, ExprOrSpread, ExprStmt,
+    Function, Id, Ident, ModuleItem, Pat, PatOrExpr, Stmt, VarDecl, VarDeclKind, VarDeclarator,
 };
 #[cfg(test)]
 use swc_ecma_transforms_base::{hygiene::hygiene, resolver};
 use swc_ecma_transforms_testing::test;
-use swc_ecma_utils::find_pat_ids;
+use swc_ecma_utils::{find_pat_ids, IdentUsageFinder};
 #[cfg(test)]
 use swc_ecma_visit::{as_folder, Fold};
-use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
-pub struct IifeExpandVisitor;
-
+use swc_ecma_visit::{
+    noop_visit_mut_type, noop_visit_type, Visit, VisitMut, VisitMutWith, VisitWith,
+};
+#[derive(Default)]
+pub struct IifeExpandVisitor {
+    eval: EvalGuard,
+    access: AccessAnalysis,
+    trust: Trust,
+    /// Keep reading and writing the argument exactly as often as before
+    strict: bool,
+    annotator: Annotator,
+    changed: bool,
+}
 impl FromMagiConfig for IifeExpandVisitor {
-    fn from_config(_conf: &crate::MagiConfig) -> Self {
-        Self
+    fn from_config(conf: &crate::MagiConfig) -> Self {
+        Self {
+            eval: EvalGuard::new(conf.eval_safety),
+            access: AccessAnalysis::default(),
+            trust: conf.trust.clone(),
+            strict: conf.strict_semantics,
+            annotator: Annotator::new(conf, PassId::IifeExpand),
+            changed: false,
+        }
+    }
+}
+impl Pass for IifeExpandVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
     }
 }
 
+/// Describes the change for eval warnings
+const EXPAND: &str = "expanding an IIFE";
+
 // TODO: We should probably be checking for any recursiveness? I think you can still manage that even without a named function..
 
 enum IifeExpansion {
@@ -40,17 +70,17 @@ enum IifeExpansion {
 }
 
 /// Attempt to evaluate a simple IIFE into an expression.
-fn eval_iife(expr: &Expr) -> Option<IifeExpansion> {
+fn eval_iife(expr: &Expr, access: &AccessAnalysis, strict: bool) -> Option<IifeExpansion> {
     let call = expr.as_call()?;
     let callee = call.callee.as_expr()?.unwrap_parens();
 
     // TODO: check type parameters, for typescript code
     if call.args.is_empty() {
-        return eval_no_args_iife(call, callee);
+        eval_no_args_iife(call, callee)
     } else if call.args.len() == 1 {
-        return eval_initializer_iife(call, callee);
+        eval_initializer_iife(call, callee, access, strict)
     } else {
-        return None;
+        None
     }
 }
 
@@ -94,7 +124,12 @@ fn eval_no_args_iife(call: &CallExpr, callee: &Expr) -> Option<IifeExpansion> {
     }
 }
 
-fn eval_initializer_iife(call: &CallExpr, callee: &Expr) -> Option<IifeExpansion> {
+fn eval_initializer_iife(
+    call: &CallExpr,
+    callee: &Expr,
+    access: &AccessAnalysis,
+    strict: bool,
+) -> Option<IifeExpansion> {
     let fn_expr = callee.as_fn_expr()?;
 
     if fn_expr.ident.is_some() {
@@ -124,7 +159,7 @@ fn eval_initializer_iife(call: &CallExpr, callee: &Expr) -> Option<IifeExpansion
 
     // Extract initializers of the form `a = x || (x = {})` or `x || (x = {})`
     // where `assign_ident` is `a` and `init_ident` is `x`
-    let (assign_ident, init_access) = extract_or_initializer_with_assign(&*init_expr)?;
+    let (assign_ident, init_access) = extract_or_initializer_with_assign(init_expr, access)?;
     let init_access_pat_or_expr: PatOrExpr = init_access.clone().try_into().ok()?;
     let init_access_expr: Expr = init_access.clone().try_into().ok()?;
 
@@ -134,27 +169,49 @@ fn eval_initializer_iife(call: &CallExpr, callee: &Expr) -> Option<IifeExpansion
 
     let body = func.body.as_ref()?;
 
+    // A plain member expression can be used directly instead of through a temporary variable.
+    // Functions in the body still need the temporary, since they could run after it has been
+    // reassigned.
+    // `x.y = x.y || {}` reads `x.y` again and always writes to it, which a getter or setter could
+    // notice, so in strict mode the argument is kept as it was.
+    let keep_init = strict && matches!(init_access, NiceAccess::Member(_));
+    let plain_access = assign_ident.is_none()
+        && !keep_init
+        && matches!(init_access, NiceAccess::Member(_))
+        && access.get(&init_access_expr) == Access::Safe
+        && !used_in_closures(&param.to_id(), &body.stmts);
+
     let mut res = Vec::new();
-    // We need to add the init initializer to the beginning of the statements
-    // `x = x || {}`, a simplification of what was `x || (x = {})`
-    res.push(Stmt::Expr(ExprStmt {
-        span: call.span,
-        expr: Box::new(Expr::Assign(AssignExpr {
+    if !keep_init {
+        // We need to add the init initializer to the beginning of the statements
+        // `x = x || {}`, a simplification of what was `x || (x = {})`
+        res.push(Stmt::Expr(ExprStmt {
             span: call.span,
-            left: init_access_pat_or_expr,
-            op: op!("="),
-            // a || {}
-            right: Box::new(Expr::Bin(BinExpr {
+            expr: Box::new(Expr::Assign(AssignExpr {
                 span: call.span,
-                op: op!("||"),
-                left: Box::new(init_access_expr.clone()),
-                right: Box::new(make_empty_object(call.span)),
+                left: init_access_pat_or_expr,
+                op: op!("="),
+                // a || {}
+                right: Box::new(Expr::Bin(BinExpr {
+                    span: call.span,
+                    op: op!("||"),
+                    left: Box::new(init_access_expr.clone()),
+                    right: Box::new(make_empty_object(call.span)),
+                })),
             })),
-        })),
-    }));
+        }));
+    }
 
     // Get the identifier that we want to use when swapping the parameter with the argument
-    let use_ident = if let Some(assign_ident) = assign_ident {
+    let use_ident = if let (Some(assign_ident), true) = (&assign_ident, keep_init) {
+        // `a = x.y || (x.y = {})`
+        res.push(Stmt::Expr(ExprStmt {
+            span: call.span,
+            expr: init_expr.clone(),
+        }));
+
+        assign_ident.clone()
+    } else if let Some(assign_ident) = assign_ident {
         // `a = x`
         res.push(Stmt::Expr(ExprStmt {
             span: call.span,
@@ -181,6 +238,7 @@ fn eval_initializer_iife(call: &CallExpr, callee: &Expr) -> Option<IifeExpansion
         match init_access {
             // If it is an ident then we don't even bother using the above created identifier
             NiceAccess::Ident(_) => {}
+            NiceAccess::Member(_) if plain_access => {}
             NiceAccess::Member(_) => {
                 // `let {use_ident} = x`
                 // We introduce the temporary variable because we can't always directly replace
@@ -196,7 +254,12 @@ fn eval_initializer_iife(call: &CallExpr, callee: &Expr) -> Option<IifeExpansion
                             id: use_ident.clone(),
                             type_ann: None,
                         }),
-                        init: Some(Box::new(init_access_expr)),
+                        // `x.y || (x.y = {})` in strict mode
+                        init: Some(if keep_init {
+                            init_expr.clone()
+                        } else {
+                            Box::new(init_access_expr.clone())
+                        }),
                         definite: false,
                     }],
                     declare: false,
@@ -214,21 +277,18 @@ fn eval_initializer_iife(call: &CallExpr, callee: &Expr) -> Option<IifeExpansion
     remap.insert(param.to_id(), new_ctxt);
 
     for stmt in &body.stmts {
-        match stmt {
-            Stmt::Decl(Decl::Var(var)) => {
-                for decl in &var.decls {
-                    let ids: Vec<Id> = find_pat_ids(&decl.name);
-                    let ids = ids.into_iter().map(|id| {
-                        (
-                            id,
-                            SyntaxContext::empty().apply_mark(Mark::fresh(Mark::root())),
-                        )
-                    });
-
-                    remap.extend(ids);
-                }
+        if let Stmt::Decl(Decl::Var(var)) = stmt {
+            for decl in &var.decls {
+                let ids: Vec<Id> = find_pat_ids(&decl.name);
+                let ids = ids.into_iter().map(|id| {
+                    (
+                        id,
+                        SyntaxContext::empty().apply_mark(Mark::fresh(Mark::root())),
+                    )
+                });
+
+                remap.extend(ids);
             }
-            _ => {}
         }
     }
 
@@ -257,18 +317,24 @@ fn eval_initializer_iife(call: &CallExpr, callee: &Expr) -> Option<IifeExpansion
                 let _prop = left.prop.as_ident()?;
 
                 let mut rename_map = HashMap::default();
+                let mut expr = expr.clone();
 
                 match &init_access {
                     NiceAccess::Ident(x) => {
                         rename_map.insert(new_ident.to_id(), x.clone());
                     }
+                    NiceAccess::Member(_) if plain_access => {
+                        expr.visit_mut_with(&mut ReplaceIdent {
+                            id: new_ident.to_id(),
+                            with: init_access_expr.clone(),
+                        });
+                    }
                     NiceAccess::Member(_) => {
                         rename_map.insert(new_ident.to_id(), use_ident.clone());
                     }
                 }
 
                 let mut ren = RenameIdentPass { names: rename_map };
-                let mut expr = expr.clone();
                 expr.visit_mut_with(&mut ren);
                 res.push(Stmt::Expr(ExprStmt { expr, span: *span }));
             }
@@ -279,15 +345,62 @@ fn eval_initializer_iife(call: &CallExpr, callee: &Expr) -> Option<IifeExpansion
     Some(IifeExpansion::Stmts(res))
 }
 
+/// Replaces uses of a variable with an expression
+struct ReplaceIdent {
+    id: Id,
+    with: Expr,
+}
+impl VisitMut for ReplaceIdent {
+    noop_visit_mut_type!();
+
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Ident(ident) if ident.to_id() == self.id => *expr = self.with.clone(),
+            _ => expr.visit_mut_children_with(self),
+        }
+    }
+}
+
+/// Whether the variable is used inside of a function in the statements
+fn used_in_closures(id: &Id, stmts: &[Stmt]) -> bool {
+    struct ClosureUsage<'a> {
+        id: &'a Id,
+        found: bool,
+    }
+    impl Visit for ClosureUsage<'_> {
+        noop_visit_type!();
+
+        fn visit_function(&mut self, function: &Function) {
+            self.found |= IdentUsageFinder::find(self.id, function);
+        }
+
+        fn visit_arrow_expr(&mut self, arrow: &ArrowExpr) {
+            self.found |= IdentUsageFinder::find(self.id, arrow);
+        }
+    }
+
+    let mut usage = ClosureUsage { id, found: false };
+    stmts.visit_with(&mut usage);
+    usage.found
+}
+
 impl VisitMut for IifeExpandVisitor {
     noop_visit_mut_type!();
 
+    track_eval_scopes!(eval, |this, program| {
+        this.access = AccessAnalysis::analyze(program, &this.trust);
+    });
+
     fn visit_mut_stmts(&mut self, stmts: &mut Vec<Stmt>) {
         let mut new_stmts = Vec::new();
         for stmt in stmts.drain(..) {
             match stmt {
                 Stmt::Expr(ExprStmt { expr, span }) => {
-                    if let Some(val) = eval_iife(&expr) {
+                    if let Some(val) = eval_iife(&expr, &self.access, self.strict)
+                        .filter(|_| self.eval.allows(span, EXPAND))
+                    {
+                        self.annotator.annotate(span);
+                        self.changed = true;
                         match val {
                             IifeExpansion::Expr(val) => {
                                 new_stmts.push(Stmt::Expr(ExprStmt {
@@ -309,7 +422,12 @@ impl VisitMut for IifeExpandVisitor {
                     Decl::Var(mut var) => {
                         let mut decls = Vec::new();
                         for decl in var.decls.drain(..) {
-                            if let Some(val) = decl.init.as_ref().and_then(|x| eval_iife(&*x)) {
+                            if let Some(val) = decl
+                                .init
+                                .as_ref()
+                                .and_then(|x| eval_iife(x, &self.access, self.strict))
+                                .filter(|_| self.eval.allows(decl.span, EXPAND))
+                            {
                                 let val = match val {
                                     IifeExpansion::Expr(val) => val,
                                     IifeExpansion::Stmts(_stmts) => {
@@ -319,6 +437,8 @@ impl VisitMut for IifeExpandVisitor {
                                     }
                                     IifeExpansion::Nothing => make_undefined(decl.span),
                                 };
+                                self.annotator.annotate(decl.span);
+                                self.changed = true;
                                 decls.push(VarDeclarator {
                                     span: decl.span,
                                     name: decl.name,
@@ -355,7 +475,11 @@ impl VisitMut for IifeExpandVisitor {
             match item {
                 ModuleItem::Stmt(stmt) => match stmt {
                     Stmt::Expr(ExprStmt { expr, span }) => {
-                        if let Some(val) = eval_iife(&expr) {
+                        if let Some(val) = eval_iife(&expr, &self.access, self.strict)
+                            .filter(|_| self.eval.allows(span, EXPAND))
+                        {
+                            self.annotator.annotate(span);
+                            self.changed = true;
                             match val {
                                 IifeExpansion::Expr(val) => {
                                     new_items.push(ModuleItem::Stmt(Stmt::Expr(ExprStmt {
@@ -377,7 +501,12 @@ impl VisitMut for IifeExpandVisitor {
                         Decl::Var(mut var) => {
                             let mut decls = Vec::new();
                             for decl in var.decls.drain(..) {
-                                if let Some(val) = decl.init.as_ref().and_then(|x| eval_iife(&*x)) {
+                                if let Some(val) = decl
+                                    .init
+                                    .as_ref()
+                                    .and_then(|x| eval_iife(x, &self.access, self.strict))
+                                    .filter(|_| self.eval.allows(decl.span, EXPAND))
+                                {
                                     let val = match val {
                                         IifeExpansion::Expr(val) => val,
                                         IifeExpansion::Stmts(_stmts) => {
@@ -387,6 +516,8 @@ impl VisitMut for IifeExpandVisitor {
                                         }
                                         IifeExpansion::Nothing => make_undefined(decl.span),
                                     };
+                                    self.annotator.annotate(decl.span);
+                                    self.changed = true;
                                     decls.push(VarDeclarator {
                                         span: decl.span,
                                         name: decl.name,
@@ -431,7 +562,7 @@ fn tr() -> impl Fold {
     chain!(
         // We have to run the resolver if we want to have the correct scope information for renaming properly
         resolver(unresolved_mark, top_level_mark, false),
-        as_folder(IifeExpandVisitor),
+        as_folder(IifeExpandVisitor::default()),
         hygiene(),
         fixer(None),
     )
@@ -439,42 +570,42 @@ fn tr() -> impl Fold {
 
 test!(
     Default::default(),
-    |_| as_folder(IifeExpandVisitor),
+    |_| as_folder(IifeExpandVisitor::default()),
     iife_expand1_sanity1,
     // TODO: We can actually do better than this, since this is side-effect free and returns nothing
     "var a = 1; (function() { var b = 2; })();" // "var a = 1; (function() { var b = 2; })();"
 );
 test!(
     Default::default(),
-    |_| as_folder(IifeExpandVisitor),
+    |_| as_folder(IifeExpandVisitor::default()),
     iife_expand1_sanity3,
     // TODO: We can do better than this. Especially since SWC keeps the variables with separate identifiers for their scopes, so I think it can just automatically deduplicate the names?
     "var a = 1, b = 3; (function() { var b = 2; })();" // "var a = 1, b = 3; (function() { var b = 2; })();"
 );
 test!(
     Default::default(),
-    |_| as_folder(IifeExpandVisitor),
+    |_| as_folder(IifeExpandVisitor::default()),
     iife_expand1_sanity2,
     // TODO: We can actually just expand this out
     "var a = 1; (function() { console.log('blah') })();" // "var a = 1; (function() { console.log('blah') })();"
 );
 test!(
     Default::default(),
-    |_| as_folder(IifeExpandVisitor),
+    |_| as_folder(IifeExpandVisitor::default()),
     iife_expand2,
     "var a = 1; (function() { })();" // "var a = 1;"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(IifeExpandVisitor),
+    |_| as_folder(IifeExpandVisitor::default()),
     iife_expand3,
     "var a = (function() { return 2; })();" // "var a = 2;"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(IifeExpandVisitor),
+    |_| as_folder(IifeExpandVisitor::default()),
     iife_expand5,
     "var a = (function() { })();" // "var a = undefined;"
 );
@@ -532,8 +663,66 @@ test!(
     Default::default(),
     |_| tr(),
     iife_expand14,
-    "(function (e1) { e1.a = 'a'; })(exports.Thing || (exports.Thing = {}));" // TODO: We could remove the `tmp` here
-                                                                              // "exports.Thing = exports.Thing || {}; let tmp = exports.Thing; tmp.a = 'a'"
+    // `exports.Thing` is only ever initialized, so it doesn't need a `tmp`
+    "(function (e1) { e1.a = 'a'; })(exports.Thing || (exports.Thing = {}));" // "exports.Thing = exports.Thing || {}; exports.Thing.a = 'a'"
+);
+test!(
+    Default::default(),
+    |_| tr(),
+    iife_expand_computed_member,
+    "(function (e1) { e1.a = 'a'; })(exports[\"Thing\"] || (exports.Thing = {}));" // "exports[\"Thing\"] = exports[\"Thing\"] || {}; exports[\"Thing\"].a = 'a'"
+);
+test!(
+    Default::default(),
+    |_| tr(),
+    iife_expand_reassigned_member,
+    // `reset` could be called while the body runs
+    "(function (e1) { e1.a = f(); e1.b = 'b'; })(exports.Thing || (exports.Thing = {})); function reset() { exports.Thing = {}; }"
+);
+test!(
+    Default::default(),
+    |_| tr(),
+    iife_expand_escaped_member,
+    // `register` could do anything to `exports`
+    "register(exports); (function (e1) { e1.a = 'a'; })(exports.Thing || (exports.Thing = {}));"
+);
+test!(
+    Default::default(),
+    |_| tr(),
+    iife_expand_getter_member,
+    "Object.defineProperty(exports, 'Thing', { get: function () { return thing; } }); (function (e1) { e1.a = 'a'; })(exports.Thing || (exports.Thing = {}));"
+);
+test!(
+    Default::default(),
+    |_| as_folder(IifeExpandVisitor {
+        trust: Trust {
+            plain: vec!["exports".into()],
+            proxies: vec!["store".into()],
+            ..Default::default()
+        },
+        ..Default::default()
+    }),
+    iife_expand_trusted_member,
+    // The user knows that `exports` has no getters, even though it looks like it does, and that
+    // `store` is a proxy
+    r#"
+    Object.defineProperty(exports, 'Thing', { get: function () { return thing; } });
+    (function (e1) { e1.a = 'a'; })(exports.Thing || (exports.Thing = {}));
+    (function (e1) { e1.a = 'a'; })(store.Thing || (store.Thing = {}));
+    "#
+);
+test!(
+    Default::default(),
+    |_| as_folder(IifeExpandVisitor {
+        strict: true,
+        ..Default::default()
+    }),
+    iife_expand_strict_member,
+    r#"
+    (function (e1) { e1.a = 'a'; })(exports.Thing || (exports.Thing = {}));
+    (function (e1) { e1.a = 'a'; })(p = exports.Other || (exports.Other = {}));
+    (function (e1) { e1.a = 'a'; })(w || (w = {}));
+    "#
 );
 test!(
     Default::default(),
@@ -567,7 +756,7 @@ test!(
 
 // test!(
 //     Default::default(),
-//     |_| as_folder(IifeExpandVisitor),
+//     |_| as_folder(IifeExpandVisitor::default()),
 //     iife_expand9,
 //     "var d; (function(e1) { e1.is = function(e1) { return o.func(e1); }; })(d || (d = {}));",
 //     // We expand this as `x.j` because we can then easily apply a variable removal pass
@@ -577,10 +766,28 @@ test!(
 // TODO: It would be nice to delete this, but it has the issue that it is not *necessarily* side-effect free due to getters. Also, it'd be better to do some constant propagation pass rather than specially handling this case, because it isn't common.
 // test!(
 //     Default::default(),
-//     |_| as_folder(IifeExpandVisitor),
+//     |_| as_folder(IifeExpandVisitor::default()),
 //     iife_expand7,
 //     "var a; (function(e) { e.j })(a = x || (x = {}));",
 //     "var a; a = x || (x = {});"
 // );
 
 // TODO: I feel like there's probably edge cases where this doesn't work right!
+
+test!(
+    Default::default(),
+    |_| tr(),
+    iife_expand_eval_scope,
+    // The `eval` can see the top level, but not the inside of `f`
+    "var a = (function() { return 5; })(); function f() { var b = (function() { return 6; })(); } function g() { eval('a'); }"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(IifeExpandVisitor {
+        eval: EvalGuard::new(crate::eval::EvalSafety::Ignore),
+        ..Default::default()
+    }),
+    iife_expand_eval_ignore,
+    "var a = (function() { return 5; })(); eval('a');"
+);
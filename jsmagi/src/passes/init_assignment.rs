@@ -1,4 +1,5 @@
 use smallvec::{smallvec, SmallVec};
+use swc_common::Spanned;
 use swc_ecma_ast::{
     AssignExpr, AssignOp, BinExpr, BinaryOp, Expr, ExprStmt, MemberExpr, ModuleItem, Pat,
     PatOrExpr, Stmt,
@@ -8,11 +9,18 @@ use swc_ecma_transforms_testing::test;
 use swc_ecma_visit::as_folder;
 use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
 
-use crate::{util::replace_entries, FromMagiConfig};
+use crate::{
+    passes::{Pass, PassId},
+    safety::Annotator,
+    util::replace_entries,
+    FromMagiConfig,
+};
 
 /// `(c = n || (n = {})).thing = 'hi'` into
 /// `n = n || {}; c = n; c.thing = 'hi'`
-pub fn replace_init_assignment(root_stmt: &Stmt) -> Option<SmallVec<[Stmt; 3]>> {
+/// With `strict` it becomes `c = n || (n = {}); c.thing = 'hi'` instead, so that `n` is read and
+/// written exactly as often as before.
+pub fn replace_init_assignment(root_stmt: &Stmt, strict: bool) -> Option<SmallVec<[Stmt; 3]>> {
     // (c = n || (n = {})).thing = 'hi'
     let root_expr = root_stmt.as_expr()?;
     let root_assign = root_expr.expr.as_assign()?;
@@ -72,6 +80,32 @@ pub fn replace_init_assignment(root_stmt: &Stmt) -> Option<SmallVec<[Stmt; 3]>>
 
     // TODO: any more verifications we need to make?
 
+    // `c.thing = 'hi'`
+    let c_field_init = Box::new(Expr::Assign(AssignExpr {
+        span: root_assign.span,
+        op: AssignOp::Assign,
+        left: PatOrExpr::Expr(Box::new(Expr::Member(MemberExpr {
+            span: obj_left.span,
+            prop: obj_left.prop.clone(),
+            obj: Box::new(Expr::Ident(c_obj.id.clone())),
+        }))),
+        right: root_assign.right.clone(),
+    }));
+
+    if strict {
+        // `c = n || (n = {})`
+        return Some(smallvec![
+            Stmt::Expr(ExprStmt {
+                span: root_expr.span,
+                expr: Box::new(Expr::Assign(inner_obj.clone())),
+            }),
+            Stmt::Expr(ExprStmt {
+                span: root_expr.span,
+                expr: c_field_init,
+            }),
+        ]);
+    }
+
     // `n = n || {};`
     let n_init = Box::new(Expr::Assign(AssignExpr {
         span: root_assign.span,
@@ -93,18 +127,6 @@ pub fn replace_init_assignment(root_stmt: &Stmt) -> Option<SmallVec<[Stmt; 3]>>
         right: Box::new(Expr::Ident(n_obj.clone())),
     }));
 
-    // `c.thing = 'hi'`
-    let c_field_init = Box::new(Expr::Assign(AssignExpr {
-        span: root_assign.span,
-        op: AssignOp::Assign,
-        left: PatOrExpr::Expr(Box::new(Expr::Member(MemberExpr {
-            span: obj_left.span,
-            prop: obj_left.prop.clone(),
-            obj: Box::new(Expr::Ident(c_obj.id.clone())),
-        }))),
-        right: root_assign.right.clone(),
-    }));
-
     Some(smallvec![
         Stmt::Expr(ExprStmt {
             span: root_expr.span,
@@ -123,10 +145,24 @@ pub fn replace_init_assignment(root_stmt: &Stmt) -> Option<SmallVec<[Stmt; 3]>>
 
 /// `(c = n || (n = {})).thing = 'hi'` into
 /// `n = n || {}; c = n; c.thing = 'hi'`
-pub struct InitAssignmentVisitor;
+#[derive(Default)]
+pub struct InitAssignmentVisitor {
+    strict: bool,
+    annotator: Annotator,
+    changed: bool,
+}
 impl FromMagiConfig for InitAssignmentVisitor {
-    fn from_config(_conf: &crate::MagiConfig) -> Self {
-        Self
+    fn from_config(conf: &crate::MagiConfig) -> Self {
+        Self {
+            strict: conf.strict_semantics,
+            annotator: Annotator::new(conf, PassId::InitAssignment),
+            changed: false,
+        }
+    }
+}
+impl Pass for InitAssignmentVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
     }
 }
 
@@ -134,19 +170,38 @@ impl VisitMut for InitAssignmentVisitor {
     noop_visit_mut_type!();
 
     fn visit_mut_stmts(&mut self, stmts: &mut Vec<Stmt>) {
-        replace_entries(stmts, replace_init_assignment);
+        let strict = self.strict;
+        let annotator = &mut self.annotator;
+        let replaced = replace_entries(stmts, |stmt| {
+            let res = replace_init_assignment(stmt, strict);
+            if res.is_some() {
+                annotator.annotate(stmt.span());
+            }
+            res
+        });
+        if replaced {
+            self.changed = true;
+        }
 
         stmts.visit_mut_children_with(self);
     }
 
     fn visit_mut_module_items(&mut self, n: &mut Vec<ModuleItem>) {
-        replace_entries(n, |x| {
-            if let ModuleItem::Stmt(x) = x {
-                replace_init_assignment(x)
-            } else {
-                None
+        let strict = self.strict;
+        let annotator = &mut self.annotator;
+        let replaced = replace_entries(n, |x| {
+            let ModuleItem::Stmt(x) = x else {
+                return None;
+            };
+            let res = replace_init_assignment(x, strict);
+            if res.is_some() {
+                annotator.annotate(x.span());
             }
+            res
         });
+        if replaced {
+            self.changed = true;
+        }
 
         n.visit_mut_children_with(self);
     }
@@ -154,17 +209,27 @@ impl VisitMut for InitAssignmentVisitor {
 
 test!(
     Default::default(),
-    |_| as_folder(InitAssignmentVisitor),
+    |_| as_folder(InitAssignmentVisitor::default()),
     single_variable,
     r#"let n;"# // "let n;"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(InitAssignmentVisitor),
+    |_| as_folder(InitAssignmentVisitor::default()),
     weird_assign,
     // TODO: We can do better than this in some cases.
     // It is common for it to assign no value to `c`, and sometimes for `n` to be unused after initialization
     // but we'll need more complicated detection
     "(c = n || (n = {})).thing = 'hi'" // "n = n || {};\nc = n;\nc.thing = 'hi'"
 );
+
+test!(
+    Default::default(),
+    |_| as_folder(InitAssignmentVisitor {
+        strict: true,
+        ..Default::default()
+    }),
+    weird_assign_strict,
+    "(c = n || (n = {})).thing = 'hi'" // "c = n || (n = {});\nc.thing = 'hi'"
+);
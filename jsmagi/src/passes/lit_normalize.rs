@@ -0,0 +1,379 @@
+//! Undo the tricks that minifiers use to write literals and conversions in fewer characters.
+//! Example:
+//! ```js
+//! !"";
+//! !![];
+//! 1 / 0;
+//! "" + a;
+//! +b;
+//! 864e5;
+//! "\x41B";
+//! ```
+//! becomes
+//! ```js
+//! true;
+//! true;
+//! Infinity;
+//! String(a);
+//! Number(b);
+//! /* 864e5 */ 86400000;
+//! "AB";
+//! ```
+
+use std::collections::HashSet;
+
+use swc_atoms::JsWord;
+use swc_common::{
+    comments::{Comment, CommentKind, Comments, SingleThreadedComments},
+    util::take::Take,
+    Span, DUMMY_SP,
+};
+use swc_ecma_ast::{
+    BinExpr, BinaryOp, Bool, CallExpr, Callee, Expr, ExprOrSpread, Id, Ident, Lit, Module, Number,
+    Script, Str, UnaryExpr, UnaryOp,
+};
+use swc_ecma_transforms_testing::test;
+use swc_ecma_utils::collect_decls;
+#[cfg(test)]
+use swc_ecma_visit::as_folder;
+use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
+
+use crate::{
+    passes::{Pass, PassId},
+    safety::Annotator,
+    FromMagiConfig, MagiConfig,
+};
+
+/// Converts `!""`, `1 / 0`, `"" + a`, `+a`, `864e5`, `0xff` and `"\x41"` into their readable forms
+#[derive(Default)]
+pub struct LitNormalizeVisitor {
+    /// Where to keep the original form of a number that is hard to read without it
+    comments: SingleThreadedComments,
+    /// The names declared anywhere in the file, which could hide a global like `String`
+    declared: HashSet<JsWord>,
+    annotator: Annotator,
+    changed: bool,
+}
+impl FromMagiConfig for LitNormalizeVisitor {
+    fn from_config(conf: &MagiConfig) -> Self {
+        Self {
+            comments: conf.comments.clone(),
+            declared: HashSet::new(),
+            annotator: Annotator::new(conf, PassId::LitNormalize),
+            changed: false,
+        }
+    }
+}
+impl Pass for LitNormalizeVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// Numbers with more digits than this keep their original form in a comment, since counting the
+/// zeros in `86400000` is harder than reading `864e5`
+const MAX_PLAIN_DIGITS: usize = 6;
+/// Numbers that would be longer than this stay as they are, like `1e100`
+const MAX_NUMBER_LEN: usize = 21;
+
+/// Whether `!lit` is always `true` or `false`, for the literals that `NotLit` doesn't handle
+fn truthiness(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Lit(Lit::Str(str)) => Some(!str.value.is_empty()),
+        Expr::Lit(Lit::Bool(bool)) => Some(bool.value),
+        Expr::Lit(Lit::Null(_)) => Some(false),
+        Expr::Array(array) if array.elems.is_empty() => Some(true),
+        Expr::Object(object) if object.props.is_empty() => Some(true),
+        Expr::Paren(paren) => truthiness(&paren.expr),
+        _ => None,
+    }
+}
+
+/// The value of `1` or `-1`
+fn num_value(expr: &Expr) -> Option<f64> {
+    match expr {
+        Expr::Lit(Lit::Num(num)) => Some(num.value),
+        Expr::Unary(UnaryExpr {
+            op: UnaryOp::Minus,
+            arg,
+            ..
+        }) => num_value(arg).map(|value| -value),
+        Expr::Paren(paren) => num_value(&paren.expr),
+        _ => None,
+    }
+}
+
+fn is_empty_str(expr: &Expr) -> bool {
+    matches!(expr, Expr::Lit(Lit::Str(str)) if str.value.is_empty())
+}
+
+/// `name(arg)`
+fn make_call(span: Span, name: &str, arg: Box<Expr>) -> Expr {
+    Expr::Call(CallExpr {
+        span,
+        callee: Callee::Expr(Box::new(Expr::Ident(Ident::new(name.into(), span)))),
+        args: vec![ExprOrSpread {
+            spread: None,
+            expr: arg,
+        }],
+        type_args: None,
+    })
+}
+
+/// The decimal form of a number written like `864e5`, `0xff` or `0o17`, if it has one
+fn plain_number(num: &Number) -> Option<String> {
+    let raw = num.raw.as_deref()?;
+    let lower = raw.to_ascii_lowercase();
+    let radix = lower.starts_with("0x") || lower.starts_with("0o") || lower.starts_with("0b");
+    let exponent = !radix && lower.contains('e');
+    // `1_000` is already readable
+    if !(radix || exponent) || raw.contains('_') || !num.value.is_finite() {
+        return None;
+    }
+
+    let plain = num.value.to_string();
+    (plain.len() <= MAX_NUMBER_LEN).then_some(plain)
+}
+
+/// Whether the string can be written without the `\x41` and `\u0041` escapes, so that every
+/// character it contains is readable when it isn't escaped
+fn has_readable_escapes(str: &Str) -> bool {
+    let Some(raw) = &str.raw else {
+        return false;
+    };
+    if !raw.contains("\\x") && !raw.contains("\\u") {
+        return false;
+    }
+
+    // A tab or a zero-width space is better off escaped
+    str.value.chars().all(|c| {
+        if c.is_ascii() {
+            c != '\t'
+        } else {
+            c.is_alphanumeric()
+        }
+    })
+}
+
+impl LitNormalizeVisitor {
+    /// Whether a global like `String` could be hidden by a variable in the file
+    fn is_shadowed(&self, name: &str) -> bool {
+        self.declared.contains(&JsWord::from(name))
+    }
+
+    fn normalize(&mut self, expr: &mut Expr) -> bool {
+        match expr {
+            // `!""`, `![]`
+            Expr::Unary(UnaryExpr {
+                op: UnaryOp::Bang,
+                arg,
+                span,
+            }) => {
+                let Some(truthy) = truthiness(arg) else {
+                    return false;
+                };
+                *expr = Expr::Lit(Lit::Bool(Bool {
+                    span: *span,
+                    value: !truthy,
+                }));
+                true
+            }
+            // `+1` or `+a`
+            Expr::Unary(UnaryExpr {
+                op: UnaryOp::Plus,
+                arg,
+                span,
+            }) => {
+                let span = *span;
+                if matches!(**arg, Expr::Lit(Lit::Num(_))) {
+                    *expr = *arg.take();
+                } else if !self.is_shadowed("Number") {
+                    self.annotator.annotate(span);
+                    *expr = make_call(span, "Number", arg.take());
+                } else {
+                    return false;
+                }
+                true
+            }
+            // `1 / 0`, `-1 / 0` and `0 / 0`
+            Expr::Bin(BinExpr {
+                op: BinaryOp::Div,
+                left,
+                right,
+                span,
+            }) => {
+                let (Some(left), Some(right)) = (num_value(left), num_value(right)) else {
+                    return false;
+                };
+                if right != 0.0 {
+                    return false;
+                }
+                // `1 / -0` is `-Infinity`
+                let value = left / right;
+                let name = if value.is_nan() { "NaN" } else { "Infinity" };
+                if self.is_shadowed(name) {
+                    return false;
+                }
+
+                let span = *span;
+                self.annotator.annotate(span);
+                let ident = Expr::Ident(Ident::new(name.into(), span));
+                *expr = if value.is_sign_negative() && !value.is_nan() {
+                    Expr::Unary(UnaryExpr {
+                        span,
+                        op: UnaryOp::Minus,
+                        arg: Box::new(ident),
+                    })
+                } else {
+                    ident
+                };
+                true
+            }
+            // `"" + a` or `a + ""`
+            Expr::Bin(BinExpr {
+                op: BinaryOp::Add,
+                left,
+                right,
+                span,
+            }) => {
+                let value = if is_empty_str(left) {
+                    right
+                } else if is_empty_str(right) {
+                    left
+                } else {
+                    return false;
+                };
+                if matches!(**value, Expr::Lit(Lit::Str(_))) || self.is_shadowed("String") {
+                    return false;
+                }
+
+                let span = *span;
+                self.annotator.annotate(span);
+                *expr = make_call(span, "String", value.take());
+                true
+            }
+            Expr::Lit(Lit::Num(num)) => {
+                let Some(plain) = plain_number(num) else {
+                    return false;
+                };
+                if plain.len() > MAX_PLAIN_DIGITS && !num.span.is_dummy() {
+                    let raw = num.raw.as_deref().unwrap_or_default();
+                    self.comments.add_leading(
+                        num.span.lo,
+                        Comment {
+                            kind: CommentKind::Block,
+                            span: DUMMY_SP,
+                            text: format!(" {} ", raw).into(),
+                        },
+                    );
+                }
+                num.raw = None;
+                true
+            }
+            Expr::Lit(Lit::Str(str)) if has_readable_escapes(str) => {
+                str.raw = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl VisitMut for LitNormalizeVisitor {
+    noop_visit_mut_type!();
+
+    fn visit_mut_module(&mut self, m: &mut Module) {
+        self.declared = collect_decls::<Id, _>(&*m)
+            .into_iter()
+            .map(|id| id.0)
+            .collect();
+        m.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_script(&mut self, s: &mut Script) {
+        self.declared = collect_decls::<Id, _>(&*s)
+            .into_iter()
+            .map(|id| id.0)
+            .collect();
+        s.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        // `!![]` needs `![]` to be converted first
+        expr.visit_mut_children_with(self);
+
+        if self.normalize(expr) {
+            self.changed = true;
+        }
+    }
+}
+
+test!(
+    Default::default(),
+    |_| as_folder(LitNormalizeVisitor::default()),
+    lit_normalize_not,
+    r#"
+    !"";
+    !"a";
+    !![];
+    !{};
+    !null;
+    !!x;
+    ![a];
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(LitNormalizeVisitor::default()),
+    lit_normalize_division,
+    r#"
+    a = 1 / 0;
+    b = -1 / 0;
+    c = 0 / 0;
+    d = 1 / 2;
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(LitNormalizeVisitor::default()),
+    lit_normalize_coercion,
+    r#"
+    a = "" + b;
+    c = d.e + "";
+    f = "" + g + "h";
+    i = "" + "j";
+    k = +l;
+    m = +1;
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(LitNormalizeVisitor::default()),
+    lit_normalize_shadowed,
+    r#"
+    function f(String, NaN) {
+        return ["" + a, 0 / 0, +b];
+    }
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(LitNormalizeVisitor::default()),
+    lit_normalize_numbers,
+    r#"
+    a = [864e5, 1e3, 2.5e-3, 0xff, 0o17, 0b101, 1e100, 1_000, 1.5, 0xffffffff];
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(LitNormalizeVisitor::default()),
+    lit_normalize_strings,
+    r#"
+    a = ["\x41B", "caf\u00e9", "\u200b", "\x41\t", "\n"];
+    "#
+);
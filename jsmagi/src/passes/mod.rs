@@ -1,11 +1,673 @@
+use std::{cell::Cell, fmt, rc::Rc, str::FromStr};
+
+use swc_ecma_ast::{Module, Script};
+use swc_ecma_transforms_testing::test;
+#[cfg(test)]
+use swc_ecma_visit::as_folder;
+use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
+
+use crate::{safety::Safety, FromMagiConfig, MagiConfig};
+
+pub mod cond_expand;
+pub mod default_params;
 pub mod es_module;
+pub mod eval_inline;
+pub mod if_chain;
 pub mod iife_expand;
 pub mod init_assignment;
+pub mod lit_normalize;
 pub mod nested_assignment;
 pub mod not_iife;
 pub mod not_lit;
+pub mod object_init;
+pub mod optional_chain;
+pub mod prop_normalize;
+pub mod pure_annotate;
 pub mod seq_expand;
 pub mod ts;
 pub mod var_decl_expand;
 pub mod var_decl_simp;
 pub mod void_to_undefined;
+pub mod yoda_cond;
+
+/// A transformation that is run as part of the pipeline.
+pub trait Pass: VisitMut {
+    /// Whether the pass changed the tree since the last time this was called.
+    /// This resets the flag, so that the pass can be run again.
+    fn take_changed(&mut self) -> bool;
+}
+
+/// Identifies a pass that can be run as part of the pipeline.
+/// The name of each pass is stable, so that it can be referred to from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PassId {
+    EvalInline,
+    SeqExpand,
+    OptionalChain,
+    CondExpand,
+    IfChain,
+    VoidToUndefined,
+    NotLit,
+    LitNormalize,
+    YodaCond,
+    PropNormalize,
+    DefaultParams,
+    NotIife,
+    InitAssignment,
+    NestedAssignment,
+    VarDeclExpand,
+    IifeExpand,
+    EsModuleRename,
+    EnumConvert,
+    VarDeclSimp,
+    ObjectInit,
+    PureAnnotate,
+}
+impl PassId {
+    /// Every pass, in the order that they are run by default.
+    pub const ALL: &'static [PassId] = &[
+        PassId::EvalInline,
+        PassId::SeqExpand,
+        PassId::OptionalChain,
+        PassId::CondExpand,
+        PassId::IfChain,
+        PassId::VoidToUndefined,
+        PassId::NotLit,
+        PassId::LitNormalize,
+        PassId::YodaCond,
+        PassId::PropNormalize,
+        PassId::DefaultParams,
+        PassId::NotIife,
+        PassId::InitAssignment,
+        PassId::NestedAssignment,
+        PassId::VarDeclExpand,
+        PassId::IifeExpand,
+        PassId::EsModuleRename,
+        PassId::EnumConvert,
+        PassId::VarDeclSimp,
+        PassId::ObjectInit,
+        PassId::PureAnnotate,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PassId::EvalInline => "eval-inline",
+            PassId::SeqExpand => "seq-expand",
+            PassId::CondExpand => "cond-expand",
+            PassId::IfChain => "if-chain",
+            PassId::VoidToUndefined => "void-to-undefined",
+            PassId::NotLit => "not-lit",
+            PassId::LitNormalize => "lit-normalize",
+            PassId::YodaCond => "yoda-cond",
+            PassId::PropNormalize => "prop-normalize",
+            PassId::DefaultParams => "default-params",
+            PassId::OptionalChain => "optional-chain",
+            PassId::NotIife => "not-iife",
+            PassId::InitAssignment => "init-assignment",
+            PassId::NestedAssignment => "nested-assignment",
+            PassId::VarDeclExpand => "var-decl-expand",
+            PassId::IifeExpand => "iife-expand",
+            PassId::EsModuleRename => "es-module-rename",
+            PassId::EnumConvert => "enum-convert",
+            PassId::VarDeclSimp => "var-decl-simp",
+            PassId::ObjectInit => "object-init",
+            PassId::PureAnnotate => "pure-annotate",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            PassId::EvalInline => "Inlines `eval` and `new Function` calls on constant strings",
+            PassId::SeqExpand => {
+                "Converts `a, b, c` statements into `a; b; c;`, including in `return`s and tests"
+            }
+            PassId::CondExpand => {
+                "Converts `a && b()`, `a || b()` and `a ? b() : c()` statements into `if`s"
+            }
+            PassId::IfChain => {
+                "Expands nested ternaries into `if`s, merges `if (a) if (b)` and adds braces"
+            }
+            PassId::VoidToUndefined => "Converts `void 0` into `undefined`",
+            PassId::NotLit => "Converts `!0` into `true` and `!1` into `false`",
+            PassId::LitNormalize => {
+                "Converts `!\"\"`, `1 / 0`, `\"\" + a`, `+a`, `864e5`, `0xff` and `\"\\x41\"` into readable forms"
+            }
+            PassId::YodaCond => {
+                "Swaps `0 === a` into `a === 0` and `typeof a > \"u\"` into a comparison with `\"undefined\"`"
+            }
+            PassId::PropNormalize => {
+                "Converts `a[\"b\"]` into `a.b`, `{b: b}` into `{b}` and `{f: function(){}}` into `{f(){}}`"
+            }
+            PassId::DefaultParams => {
+                "Converts `if (b === undefined) { b = 5; }` and `arguments` copies into default and rest parameters"
+            }
+            PassId::OptionalChain => {
+                "Converts `null == (t = a.b) ? void 0 : t.c` and `null != (t = a.b) ? t : d` into `a.b?.c` and `a.b ?? d`"
+            }
+            PassId::NotIife => "Converts `!function(){}()` statements into `(function(){})()`",
+            PassId::InitAssignment => {
+                "Splits `(c = n || (n = {})).thing = 'hi'` into separate statements"
+            }
+            PassId::NestedAssignment => "Converts `a = b = 0` into `a = 0; b = 0;`",
+            PassId::VarDeclExpand => "Converts `var a, b;` into `var a; var b;`",
+            PassId::IifeExpand => "Expands simple IIFEs into their body",
+            PassId::EsModuleRename => {
+                "Renames `(e, t, n)` module functions to `(module, exports, require)`"
+            }
+            PassId::EnumConvert => "Converts IIFE constructed enums into Typescript enums",
+            PassId::VarDeclSimp => "Merges the first assignment to a variable into its declaration",
+            PassId::ObjectInit => "Moves `a.b = c` after `var a = {}` into the object literal",
+            PassId::PureAnnotate => {
+                "Marks calls to functions without side effects with `/* pure */`"
+            }
+        }
+    }
+
+    /// The code that identifies the pass in `--annotate` comments and `jsmagi explain`.
+    /// The letter is the level of the pass without `--strict-semantics`: `S` for preserving,
+    /// `P` for probably-preserving and `D` for readability-only.
+    pub fn code(self) -> &'static str {
+        match self {
+            PassId::EvalInline => "P001",
+            PassId::SeqExpand => "S001",
+            PassId::CondExpand => "S007",
+            PassId::IfChain => "S008",
+            PassId::VoidToUndefined => "P002",
+            PassId::NotLit => "S002",
+            PassId::LitNormalize => "P008",
+            PassId::YodaCond => "S009",
+            PassId::PropNormalize => "P009",
+            PassId::DefaultParams => "P010",
+            PassId::OptionalChain => "P011",
+            PassId::NotIife => "S003",
+            PassId::InitAssignment => "P003",
+            PassId::NestedAssignment => "P004",
+            PassId::VarDeclExpand => "S004",
+            PassId::IifeExpand => "P005",
+            PassId::EsModuleRename => "P006",
+            PassId::EnumConvert => "D001",
+            PassId::VarDeclSimp => "S005",
+            PassId::ObjectInit => "P007",
+            PassId::PureAnnotate => "S006",
+        }
+    }
+
+    /// The pass with the given code, ignoring case
+    pub fn from_code(code: &str) -> Option<PassId> {
+        PassId::ALL
+            .iter()
+            .copied()
+            .find(|pass| pass.code().eq_ignore_ascii_case(code))
+    }
+
+    /// How much the pass might change what the code does.
+    /// Some passes only change it through getters, setters or proxies, which
+    /// `strict_semantics` guards against.
+    pub fn safety(self, strict_semantics: bool) -> Safety {
+        match self {
+            PassId::SeqExpand
+            | PassId::CondExpand
+            | PassId::IfChain
+            | PassId::NotLit
+            | PassId::YodaCond
+            | PassId::NotIife
+            | PassId::VarDeclExpand
+            | PassId::VarDeclSimp
+            | PassId::PureAnnotate => Safety::Preserving,
+            PassId::InitAssignment | PassId::NestedAssignment | PassId::IifeExpand
+                if strict_semantics =>
+            {
+                Safety::Preserving
+            }
+            PassId::EvalInline
+            | PassId::VoidToUndefined
+            | PassId::LitNormalize
+            | PassId::PropNormalize
+            | PassId::DefaultParams
+            | PassId::OptionalChain
+            | PassId::InitAssignment
+            | PassId::NestedAssignment
+            | PassId::IifeExpand
+            | PassId::EsModuleRename
+            | PassId::ObjectInit => Safety::Probable,
+            PassId::EnumConvert => Safety::Readable,
+        }
+    }
+
+    /// What the pass does to the code and what it could break, for `jsmagi explain`
+    pub fn explanation(self) -> &'static str {
+        match self {
+            PassId::EvalInline => {
+                "Replaced an `eval` or `new Function` call on a constant string with the code \
+                 in the string.\n\
+                 The inlined code runs in the surrounding scope. An indirect call through an \
+                 `eval` variable that isn't the global one, code that relies on `eval` running \
+                 in sloppy mode, or a `Function` that has been replaced would behave differently."
+            }
+            PassId::SeqExpand => {
+                "Split a sequence `a, b, c` into separate statements, including the start of a \
+                 sequence in a `return`, `throw`, `if` or `switch` test, `for` initializer or \
+                 arrow function. `return void f()` became `f(); return;`.\n\
+                 The expressions run in the same order, so nothing changes."
+            }
+            PassId::VoidToUndefined => {
+                "Replaced `void 0` with `undefined`.\n\
+                 If a variable named `undefined` is in scope, or the global `undefined` has been \
+                 replaced in an old engine, then the value is no longer `undefined`."
+            }
+            PassId::CondExpand => {
+                "Replaced a condition used as a statement, like `a && b()`, `a || b()` or \
+                 `a ? b() : c()`, with an `if` statement.\n\
+                 The condition and the branch run in the same order, so nothing changes."
+            }
+            PassId::IfChain => {
+                "Replaced a nested ternary that is returned, assigned to a variable or used to \
+                 initialize a declaration with `if` statements, merged `if (a) if (b)` into \
+                 `if (a && b)` or put the body of an `if` or loop in braces.\n\
+                 Each condition and value is evaluated in the same order, and a `let` or \
+                 `const` goes through a temporary variable when it couldn't be used before, \
+                 so nothing changes."
+            }
+            PassId::NotLit => {
+                "Replaced `!0` with `true` and `!1` with `false`.\n\
+                 These always have the same value, so nothing changes."
+            }
+            PassId::LitNormalize => {
+                "Replaced a literal written the way a minifier writes it with a readable one: \
+                 `!\"\"` with `true`, `1 / 0` with `Infinity`, `0 / 0` with `NaN`, \
+                 `\"\" + a` with `String(a)`, `+a` with `Number(a)`, `864e5` or `0xff` with a \
+                 plain number and `\"\\x41\"` with `\"A\"`.\n\
+                 `String(a)` converts an object with its `toString` rather than its `valueOf`, \
+                 and unlike `\"\" + a` it works on a symbol. `Number(a)` works on a `BigInt`, \
+                 where `+a` throws. `Infinity`, `NaN`, `String` and `Number` are assumed to be \
+                 the globals unless the file declares them. The literals have the same value."
+            }
+            PassId::YodaCond => {
+                "Swapped a comparison with a constant on the left, like `0 === a` or \
+                 `0 < a.length`, into `a === 0` or `a.length > 0`, and replaced \
+                 `typeof a > \"u\"` with `typeof a === \"undefined\"`.\n\
+                 Only comparisons where neither side can run code, like a getter, are swapped, \
+                 and `\"undefined\"` is the only result of `typeof` after `\"u\"`, so nothing \
+                 changes."
+            }
+            PassId::PropNormalize => {
+                "Replaced a constant computed property like `a[\"b\"]` or `{[\"b\"]: 1}` with \
+                 `a.b` or `{b: 1}`, `{b: b}` with `{b}`, or `{f: function () {}}` with \
+                 `{f() {}}`.\n\
+                 The properties are the same, but a method can't be called with `new` and has no \
+                 `prototype`, so code that constructs it would throw. Functions that use \
+                 `new.target`, set properties on `this` or use a `prototype` are left alone."
+            }
+            PassId::DefaultParams => {
+                "Replaced `if (b === undefined) { b = 5; }` at the start of a function, or \
+                 Babel's `var b = arguments.length > 1 && arguments[1] !== undefined ? \
+                 arguments[1] : 5`, with the default parameter `b = 5`, and a copy of \
+                 `arguments` into an array with a rest parameter.\n\
+                 The `length` of the function no longer counts a parameter that was given a \
+                 default, and `undefined` is assumed to be the global. The default is only moved \
+                 if it doesn't use anything declared in the body, and the body doesn't use \
+                 `arguments`, since it stops following the parameters."
+            }
+            PassId::OptionalChain => {
+                "Replaced a conditional that tests for `null` and `undefined`, like \
+                 `null == (t = a.b) ? void 0 : t.c` or `null != (t = a.b) ? t : d`, with the \
+                 optional chain `a.b?.c` or `a.b ?? d`, and removed the temporary variable it \
+                 went through once nothing else used it.\n\
+                 `document.all` is the one object that `== null` considers nullish, so a chain \
+                 through it now continues instead of stopping. `undefined` is assumed to be the \
+                 global."
+            }
+            PassId::NotIife => {
+                "Replaced `!function(){}()` with `(function(){})()` as a statement.\n\
+                 The result of the statement was unused, so nothing changes."
+            }
+            PassId::InitAssignment => {
+                "Split `(c = n || (n = {})).thing = 'hi'` into `n = n || {}; c = n; \
+                 c.thing = 'hi';`.\n\
+                 `n` is read one more time and is always assigned to. If `n` is a property with a \
+                 getter or setter, or belongs to a proxy, it will run more often. \
+                 `--strict-semantics` keeps the accesses the same."
+            }
+            PassId::NestedAssignment => {
+                "Split `a = b = 0` into `a = 0; b = 0;`.\n\
+                 The targets are assigned in a different order, and a property target may be \
+                 read back instead of reusing the value. A setter or proxy could notice this. \
+                 `--strict-semantics` only splits assignments of a literal to variables."
+            }
+            PassId::VarDeclExpand => {
+                "Split `var a, b;` into `var a; var b;`.\n\
+                 The variables are declared in the same order, so nothing changes."
+            }
+            PassId::IifeExpand => {
+                "Replaced an immediately invoked function with its body, such as \
+                 `(function (e) { e.a = 1; })(x.y || (x.y = {}))` with \
+                 `x.y = x.y || {}; x.y.a = 1;`.\n\
+                 `x.y` may be read again instead of through a temporary variable and is always \
+                 assigned to, which a getter, setter or proxy could notice. \
+                 `--strict-semantics` keeps the accesses the same."
+            }
+            PassId::EsModuleRename => {
+                "Renamed the `(e, t, n)` parameters of a bundled module function to \
+                 `(module, exports, require)`.\n\
+                 This assumes that the function is a module because of its shape. The names \
+                 could hide a global `module`, `exports` or `require` that the function uses."
+            }
+            PassId::EnumConvert => {
+                "Replaced an IIFE that fills in an object with a Typescript `enum`, followed by \
+                 `Object.assign` onto the original object.\n\
+                 The output is only valid Typescript. The enum is named after the export, which \
+                 could clash with another variable, and values which aren't numbers or were \
+                 changed elsewhere are assumed to be a plain enum."
+            }
+            PassId::VarDeclSimp => {
+                "Merged the first assignment to a variable into its declaration.\n\
+                 Only assignments which run before any use of the variable are merged, so \
+                 nothing changes."
+            }
+            PassId::ObjectInit => {
+                "Moved `a.b = c` statements after `var a = {}`, or after `a = {}` for a declared \
+                 `a`, into the object literal.\n\
+                 Assigning a property runs setters on `Object.prototype` and a `__proto__` \
+                 property sets the prototype, while an object literal defines the property \
+                 directly. The values are also evaluated before `a` is declared, which only \
+                 matters if functions which aren't known to be pure are called."
+            }
+            PassId::PureAnnotate => {
+                "Added a `/* pure */` comment to a call of a function without side effects.\n\
+                 This is only a comment, so nothing changes."
+            }
+        }
+    }
+
+    /// Whether the pass is part of the default pipeline.
+    pub fn enabled_by_default(self) -> bool {
+        // It only adds comments, which not everyone wants
+        !matches!(self, PassId::PureAnnotate)
+    }
+
+    /// The passes that are run when the user does not specify otherwise, in order.
+    pub fn default_pipeline() -> Vec<PassId> {
+        PassId::ALL
+            .iter()
+            .copied()
+            .filter(|pass| pass.enabled_by_default())
+            .collect()
+    }
+
+    /// The position of the pass in [`PassId::ALL`]
+    fn order(self) -> usize {
+        PassId::ALL
+            .iter()
+            .position(|pass| *pass == self)
+            .expect("every pass should be in `PassId::ALL`")
+    }
+
+    pub(crate) fn make(self, conf: &MagiConfig) -> Box<dyn Pass> {
+        match self {
+            PassId::EvalInline => Box::new(eval_inline::EvalInline::from_config(conf)),
+            PassId::SeqExpand => Box::new(seq_expand::SeqExpandVisitor::from_config(conf)),
+            PassId::CondExpand => Box::new(cond_expand::CondExpandVisitor::from_config(conf)),
+            PassId::IfChain => Box::new(if_chain::IfChainVisitor::from_config(conf)),
+            PassId::VoidToUndefined => {
+                Box::new(void_to_undefined::VoidToUndefinedVisitor::from_config(conf))
+            }
+            PassId::NotLit => Box::new(not_lit::NotLitVisitor::from_config(conf)),
+            PassId::LitNormalize => Box::new(lit_normalize::LitNormalizeVisitor::from_config(conf)),
+            PassId::YodaCond => Box::new(yoda_cond::YodaCondVisitor::from_config(conf)),
+            PassId::PropNormalize => {
+                Box::new(prop_normalize::PropNormalizeVisitor::from_config(conf))
+            }
+            PassId::DefaultParams => {
+                Box::new(default_params::DefaultParamsVisitor::from_config(conf))
+            }
+            PassId::OptionalChain => {
+                Box::new(optional_chain::OptionalChainVisitor::from_config(conf))
+            }
+            PassId::NotIife => Box::new(not_iife::NotIifeVisitor::from_config(conf)),
+            PassId::InitAssignment => {
+                Box::new(init_assignment::InitAssignmentVisitor::from_config(conf))
+            }
+            PassId::NestedAssignment => Box::new(
+                nested_assignment::NestedAssignmentVisitor::from_config(conf),
+            ),
+            PassId::VarDeclExpand => Box::new(var_decl_expand::VarDeclExpand::from_config(conf)),
+            PassId::IifeExpand => Box::new(iife_expand::IifeExpandVisitor::from_config(conf)),
+            PassId::EsModuleRename => Box::new(es_module::EsModuleRenameVisitor::from_config(conf)),
+            PassId::EnumConvert => Box::new(ts::enum_convert::EnumConvert::from_config(conf)),
+            PassId::VarDeclSimp => Box::new(var_decl_simp::VarDeclSimp::from_config(conf)),
+            PassId::ObjectInit => Box::new(object_init::ObjectInit::from_config(conf)),
+            PassId::PureAnnotate => Box::new(pure_annotate::PureAnnotate::from_config(conf)),
+        }
+    }
+}
+impl fmt::Display for PassId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+impl FromStr for PassId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PassId::ALL
+            .iter()
+            .copied()
+            .find(|pass| pass.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = PassId::ALL.iter().map(|pass| pass.name()).collect();
+                format!(
+                    "unknown pass `{}`, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// How a run of the [`Pipeline`] went.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineReport {
+    /// How many times the passes were run over the tree.
+    pub rounds: usize,
+    /// Whether the last round made no changes. If this is false then the pipeline stopped
+    /// because it hit the iteration cap.
+    pub converged: bool,
+}
+
+/// Runs each pass over the entire tree, one after another, and then repeats that until no pass
+/// changes anything or the iteration cap is reached.
+pub struct Pipeline {
+    passes: Vec<Box<dyn Pass>>,
+    max_iterations: usize,
+    report: Rc<Cell<PipelineReport>>,
+}
+impl Pipeline {
+    pub(crate) fn new(passes: Vec<Box<dyn Pass>>, max_iterations: usize) -> Self {
+        Self {
+            passes,
+            max_iterations,
+            report: Default::default(),
+        }
+    }
+
+    /// A handle to the report, which is filled in once the pipeline has been run.
+    pub fn report(&self) -> Rc<Cell<PipelineReport>> {
+        self.report.clone()
+    }
+
+    fn run<N>(&mut self, node: &mut N)
+    where
+        N: VisitMutWith<dyn Pass>,
+    {
+        let mut report = PipelineReport::default();
+        while report.rounds < self.max_iterations {
+            report.rounds += 1;
+
+            let mut changed = false;
+            for pass in &mut self.passes {
+                node.visit_mut_with(pass.as_mut());
+                // Always take the flag, so that it is reset for the next round
+                changed |= pass.take_changed();
+            }
+
+            if !changed {
+                report.converged = true;
+                break;
+            }
+        }
+
+        self.report.set(report);
+    }
+}
+impl VisitMut for Pipeline {
+    noop_visit_mut_type!();
+
+    fn visit_mut_module(&mut self, m: &mut Module) {
+        self.run(m);
+    }
+
+    fn visit_mut_script(&mut self, s: &mut Script) {
+        self.run(s);
+    }
+}
+
+/// Enable `pass` in the ordered list of passes, if it is not already there.
+/// It is placed before the first pass which comes after it in the default ordering, so that
+/// enabling a pass on top of the default pipeline puts it where it would normally run.
+pub fn enable_pass(passes: &mut Vec<PassId>, pass: PassId) {
+    if passes.contains(&pass) {
+        return;
+    }
+
+    let idx = passes
+        .iter()
+        .position(|other| other.order() > pass.order())
+        .unwrap_or(passes.len());
+    passes.insert(idx, pass);
+}
+
+/// Remove `pass` from the ordered list of passes.
+pub fn disable_pass(passes: &mut Vec<PassId>, pass: PassId) {
+    passes.retain(|other| *other != pass);
+}
+
+#[cfg(test)]
+fn pipeline(passes: &[PassId], max_iterations: usize) -> Pipeline {
+    let conf = MagiConfig {
+        passes: passes.to_vec(),
+        max_iterations,
+        ..Default::default()
+    };
+    conf.get_passes()
+}
+
+// The IIFE can only be expanded once the sequence it is in has been split up, which happens after
+test!(
+    Default::default(),
+    |_| as_folder(pipeline(&[PassId::IifeExpand, PassId::SeqExpand], 10)),
+    pipeline_fixed_point,
+    "a, (function (e) { e.j = 5; })(x || (x = {}));" // "a; x = x || {}; x.j = 5;"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(pipeline(&[PassId::IifeExpand, PassId::SeqExpand], 1)),
+    pipeline_single_round,
+    "a, (function (e) { e.j = 5; })(x || (x = {}));" // "a; (function (e) { e.j = 5; })(x || (x = {}));"
+);
+
+#[cfg(test)]
+mod tests {
+    use swc_ecma_transforms_testing::Tester;
+    use swc_ecma_visit::as_folder;
+
+    use super::{disable_pass, enable_pass, pipeline, PassId, PipelineReport};
+    use crate::safety::Safety;
+
+    fn run_report(passes: &[PassId], max_iterations: usize, src: &str) -> PipelineReport {
+        Tester::run(|tester| {
+            let pipeline = pipeline(passes, max_iterations);
+            let report = pipeline.report();
+            tester.apply_transform(as_folder(pipeline), "input.js", Default::default(), src)?;
+            Ok(report.get())
+        })
+    }
+
+    #[test]
+    fn test_pipeline_report() {
+        let src = "a, (function (e) { e.j = 5; })(x || (x = {}));";
+        let passes = [PassId::IifeExpand, PassId::SeqExpand];
+
+        // Round 1 splits the sequence, round 2 expands the IIFE, round 3 changes nothing
+        assert_eq!(
+            run_report(&passes, 10, src),
+            PipelineReport {
+                rounds: 3,
+                converged: true
+            }
+        );
+        assert_eq!(
+            run_report(&passes, 2, src),
+            PipelineReport {
+                rounds: 2,
+                converged: false
+            }
+        );
+        assert_eq!(
+            run_report(&passes, 10, "let a = 1;"),
+            PipelineReport {
+                rounds: 1,
+                converged: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_pass_names_roundtrip() {
+        for pass in PassId::ALL {
+            assert_eq!(pass.name().parse::<PassId>(), Ok(*pass));
+        }
+        assert!("not-a-pass".parse::<PassId>().is_err());
+    }
+
+    #[test]
+    fn test_pass_codes() {
+        for pass in PassId::ALL {
+            assert_eq!(PassId::from_code(pass.code()), Some(*pass));
+            // The letter of the code is the level of the pass
+            let letter = match pass.safety(false) {
+                Safety::Preserving => 'S',
+                Safety::Probable => 'P',
+                Safety::Readable => 'D',
+            };
+            assert!(pass.code().starts_with(letter), "{}", pass);
+            assert!(pass.safety(true) <= pass.safety(false));
+        }
+        assert_eq!(PassId::from_code("d001"), Some(PassId::EnumConvert));
+        assert_eq!(PassId::from_code("X001"), None);
+    }
+
+    #[test]
+    fn test_enable_disable_pass() {
+        let mut passes = vec![PassId::SeqExpand, PassId::IifeExpand];
+        enable_pass(&mut passes, PassId::NotLit);
+        assert_eq!(
+            passes,
+            vec![PassId::SeqExpand, PassId::NotLit, PassId::IifeExpand]
+        );
+
+        // Already enabled passes keep their position
+        enable_pass(&mut passes, PassId::SeqExpand);
+        assert_eq!(
+            passes,
+            vec![PassId::SeqExpand, PassId::NotLit, PassId::IifeExpand]
+        );
+
+        enable_pass(&mut passes, PassId::VarDeclSimp);
+        disable_pass(&mut passes, PassId::SeqExpand);
+        assert_eq!(
+            passes,
+            vec![PassId::NotLit, PassId::IifeExpand, PassId::VarDeclSimp]
+        );
+    }
+}
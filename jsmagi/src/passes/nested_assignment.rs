@@ -1,29 +1,57 @@
 use smallvec::SmallVec;
 use swc_common::Span;
-use swc_ecma_ast::{AssignExpr, AssignOp, Expr, ExprStmt, ModuleItem, Stmt};
+use swc_ecma_ast::{AssignExpr, AssignOp, Expr, ExprStmt, ModuleItem, Pat, PatOrExpr, Stmt};
 use swc_ecma_transforms_testing::test;
 #[cfg(test)]
 use swc_ecma_visit::as_folder;
 use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
 
-use crate::{FromMagiConfig, MagiConfig};
+use crate::{
+    passes::{Pass, PassId},
+    safety::Annotator,
+    trust::{expr_path, Trust},
+    FromMagiConfig, MagiConfig,
+};
 
 /// Transform nest assignments like `a = b = c = d = (some literal)` to
 /// `a = (some literal);\nb = (some literal);\nc = (some literal);\nd = (some literal);`
-pub struct NestedAssignmentVisitor;
+#[derive(Default)]
+pub struct NestedAssignmentVisitor {
+    /// Only split assignments to variables, since assigning to a property could run a setter
+    /// which sees the other assignments happen in a different order, or changes the value. The
+    /// value must also be a literal, as reading a variable again could give something else.
+    strict: bool,
+    /// Assignments to proxies are left in order, since their setters can see the order
+    trust: Trust,
+    annotator: Annotator,
+    changed: bool,
+}
 impl FromMagiConfig for NestedAssignmentVisitor {
-    fn from_config(_conf: &MagiConfig) -> Self {
-        Self
+    fn from_config(conf: &MagiConfig) -> Self {
+        Self {
+            strict: conf.strict_semantics,
+            trust: conf.trust.clone(),
+            annotator: Annotator::new(conf, PassId::NestedAssignment),
+            changed: false,
+        }
+    }
+}
+impl Pass for NestedAssignmentVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
     }
 }
 
-fn nested_assignment_converter(expr: Expr, span: Span) -> Vec<Expr> {
+fn nested_assignment_converter(expr: Expr, span: Span, strict: bool, trust: &Trust) -> Vec<Expr> {
     let expr2 = expr.clone();
     let mut cexpr = &expr;
 
     let mut vars = SmallVec::<[_; 4]>::new();
     let mut val = None;
     while let Expr::Assign(expr) = &cexpr {
+        if strict && !is_ident(&expr.left) || is_proxy(&expr.left, trust) {
+            return vec![expr2];
+        }
         vars.push(expr.left.clone());
 
         if expr.op != AssignOp::Assign {
@@ -32,6 +60,11 @@ fn nested_assignment_converter(expr: Expr, span: Span) -> Vec<Expr> {
 
         // TODO: There's probably a bigger class that we could extract here
         if matches!(expr.right.as_ref(), Expr::Lit(_) | Expr::Ident(_)) {
+            if strict && !expr.right.is_lit()
+                || expr_path(&expr.right).is_some_and(|path| trust.is_proxy(&path))
+            {
+                return vec![expr2];
+            }
             val = Some(expr.right.clone());
             break;
         }
@@ -54,6 +87,25 @@ fn nested_assignment_converter(expr: Expr, span: Span) -> Vec<Expr> {
     }
 }
 
+fn is_ident(left: &PatOrExpr) -> bool {
+    match left {
+        PatOrExpr::Expr(expr) => expr.is_ident(),
+        PatOrExpr::Pat(pat) => pat.is_ident() || pat.as_expr().is_some_and(|expr| expr.is_ident()),
+    }
+}
+
+fn is_proxy(left: &PatOrExpr, trust: &Trust) -> bool {
+    let expr = match left {
+        PatOrExpr::Expr(expr) => Some(&**expr),
+        PatOrExpr::Pat(pat) => match &**pat {
+            Pat::Ident(ident) => return trust.is_proxy(&[&*ident.id.sym]),
+            pat => pat.as_expr().map(|expr| &**expr),
+        },
+    };
+    expr.and_then(expr_path)
+        .is_some_and(|path| trust.is_proxy(&path))
+}
+
 impl VisitMut for NestedAssignmentVisitor {
     noop_visit_mut_type!();
 
@@ -62,7 +114,11 @@ impl VisitMut for NestedAssignmentVisitor {
         for stmt in stmts.drain(..) {
             match stmt {
                 Stmt::Expr(ExprStmt { expr, span }) => {
-                    let exprs = nested_assignment_converter(*expr, span);
+                    let exprs = nested_assignment_converter(*expr, span, self.strict, &self.trust);
+                    if exprs.len() > 1 {
+                        self.annotator.annotate(span);
+                        self.changed = true;
+                    }
                     new_stmts.extend(exprs.into_iter().map(|expr| {
                         Stmt::Expr(ExprStmt {
                             expr: Box::new(expr),
@@ -83,7 +139,11 @@ impl VisitMut for NestedAssignmentVisitor {
         for item in items.drain(..) {
             match item {
                 ModuleItem::Stmt(Stmt::Expr(ExprStmt { expr, span })) => {
-                    let exprs = nested_assignment_converter(*expr, span);
+                    let exprs = nested_assignment_converter(*expr, span, self.strict, &self.trust);
+                    if exprs.len() > 1 {
+                        self.annotator.annotate(span);
+                        self.changed = true;
+                    }
                     new_items.extend(exprs.into_iter().map(|expr| {
                         ModuleItem::Stmt(Stmt::Expr(ExprStmt {
                             expr: Box::new(expr),
@@ -102,49 +162,74 @@ impl VisitMut for NestedAssignmentVisitor {
 
 test!(
     Default::default(),
-    |_| as_folder(NestedAssignmentVisitor),
+    |_| as_folder(NestedAssignmentVisitor::default()),
     nested_assignment_sanity,
     "a = 1" // "a = 1;"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(NestedAssignmentVisitor),
+    |_| as_folder(NestedAssignmentVisitor::default()),
     nested_assignment,
     "a = b = c = d = 1" // "a = 1;\nb = 1;\nc = 1;\nd = 1;"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(NestedAssignmentVisitor),
+    |_| as_folder(NestedAssignmentVisitor::default()),
     nested_assignment2,
     "function abc() { a = b = c = d = 1 }" // "function abc () { a = 1;\nb = 1;\nc = 1;\nd = 1; }"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(NestedAssignmentVisitor),
+    |_| as_folder(NestedAssignmentVisitor::default()),
     nested_assignment3,
     "a = b = 1" // "a = 1;\nb = 1;"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(NestedAssignmentVisitor),
+    |_| as_folder(NestedAssignmentVisitor::default()),
     nested_assignment4,
     "t.a = t.b = t.c = t.d = 4" // "t.a = 4;\nt.b = 4;\nt.c = 4;\nt.d = 4;"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(NestedAssignmentVisitor),
+    |_| as_folder(NestedAssignmentVisitor::default()),
     nested_assignment5,
     "function abc() { t.a = t.b = t.c = t.d = 4; }" // "function abc() { t.a = 4;\nt.b = 4;\nt.c = 4;\nt.d = 4; }"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(NestedAssignmentVisitor),
+    |_| as_folder(NestedAssignmentVisitor::default()),
     nested_assignment6,
     "t.a = t.b = t.c = undefined;" // "t.a = undefined;\nt.b = undefined;\nt.c = undefined;"
 );
+
+test!(
+    Default::default(),
+    |_| as_folder(NestedAssignmentVisitor {
+        strict: true,
+        ..Default::default()
+    }),
+    nested_assignment_strict,
+    // A setter on `t.a` could see whether `t.b` was set yet
+    "a = b = c = 1; t.a = t.b = 4; a = t.b = b = 5; a = b = c;" // "a = 1;\nb = 1;\nc = 1;\nt.a = t.b = 4;\na = t.b = b = 5;\na = b = c;"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(NestedAssignmentVisitor::from_config(&MagiConfig {
+        trust: Trust {
+            proxies: vec!["store".into()],
+            ..Default::default()
+        },
+        ..Default::default()
+    })),
+    nested_assignment_proxy,
+    // The setters on `store` could see which assignment happens first
+    "store.a = store.b = 0; a = b = store.c; t.a = t.b = 1;" // "store.a = store.b = 0;\na = b = store.c;\nt.a = 1;\nt.b = 1;"
+);
@@ -4,14 +4,22 @@ use swc_ecma_transforms_testing::test;
 use swc_ecma_visit::as_folder;
 use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
 
-use crate::{FromMagiConfig, MagiConfig};
+use crate::{passes::Pass, FromMagiConfig, MagiConfig};
 
 /// `!function (x) { ... }(x)` expr stmt => `(function () {})()`  
 /// May not be eval-safe
-pub struct NotIifeVisitor;
+#[derive(Default)]
+pub struct NotIifeVisitor {
+    changed: bool,
+}
 impl FromMagiConfig for NotIifeVisitor {
     fn from_config(_conf: &MagiConfig) -> Self {
-        Self
+        Self::default()
+    }
+}
+impl Pass for NotIifeVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
     }
 }
 
@@ -44,7 +52,9 @@ impl VisitMut for NotIifeVisitor {
     noop_visit_mut_type!();
 
     fn visit_mut_stmt(&mut self, stmt: &mut Stmt) {
-        replace_not_iife(stmt);
+        if replace_not_iife(stmt).is_some() {
+            self.changed = true;
+        }
 
         stmt.visit_mut_children_with(self);
     }
@@ -52,7 +62,7 @@ impl VisitMut for NotIifeVisitor {
 
 test!(
     Default::default(),
-    |_| as_folder(NotIifeVisitor),
+    |_| as_folder(NotIifeVisitor::default()),
     neg_iife,
     "!function (x) { alert('hi') }(x)" // "(function (x) { alert('hi') })(x)"
 );
@@ -60,7 +70,7 @@ test!(
 // TODO: We could optimize some basic cases where they don't return a value from a function
 test!(
     Default::default(),
-    |_| as_folder(NotIifeVisitor),
+    |_| as_folder(NotIifeVisitor::default()),
     neg_iife_sanity,
     "let j = !function (x) { return x }(x);" // "let j = !function (x) { return x }(x);"
 );
@@ -4,12 +4,20 @@ use swc_ecma_transforms_testing::test;
 use swc_ecma_visit::as_folder;
 use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
 
-use crate::{FromMagiConfig, MagiConfig};
+use crate::{passes::Pass, FromMagiConfig, MagiConfig};
 
-pub struct NotLitVisitor;
+#[derive(Default)]
+pub struct NotLitVisitor {
+    changed: bool,
+}
 impl FromMagiConfig for NotLitVisitor {
     fn from_config(_conf: &MagiConfig) -> Self {
-        Self
+        Self::default()
+    }
+}
+impl Pass for NotLitVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
     }
 }
 
@@ -24,7 +32,7 @@ fn replace_not_lit(expr: &mut Expr) -> Option<()> {
         return None;
     };
 
-    let value = if v.value == 0.0 { true } else { false };
+    let value = v.value == 0.0;
 
     *expr = Expr::Lit(Lit::Bool(Bool {
         value,
@@ -38,7 +46,9 @@ impl VisitMut for NotLitVisitor {
     noop_visit_mut_type!();
 
     fn visit_mut_expr(&mut self, expr: &mut Expr) {
-        replace_not_lit(expr);
+        if replace_not_lit(expr).is_some() {
+            self.changed = true;
+        }
 
         expr.visit_mut_children_with(self);
     }
@@ -46,28 +56,28 @@ impl VisitMut for NotLitVisitor {
 
 test!(
     Default::default(),
-    |_| as_folder(NotLitVisitor),
+    |_| as_folder(NotLitVisitor::default()),
     not_lit,
     "!0" // "true"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(NotLitVisitor),
+    |_| as_folder(NotLitVisitor::default()),
     not_lit1,
     "!1" // "false"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(NotLitVisitor),
+    |_| as_folder(NotLitVisitor::default()),
     not_lit2,
     "!2" // "false"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(NotLitVisitor),
+    |_| as_folder(NotLitVisitor::default()),
     not_lit3,
     "!'asdf'" // "!'asdf'"
 );
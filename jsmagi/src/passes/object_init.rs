@@ -0,0 +1,562 @@
+//! Collapse the assignments that build up an object into its declaration.
+//! Example:
+//! ```js
+//! var a = {};
+//! a.blah = "hi";
+//! a = a || {};
+//! a.thing = "hi";
+//! ```
+//! becomes
+//! ```js
+//! var a = {
+//!     blah: "hi",
+//!     thing: "hi"
+//! };
+//! ```
+//! The `a = a || {}` does nothing, since `a` is already an object.
+//!
+//! The properties are only moved while nothing in between could see `a` before it is finished.
+//! Statements which can't, because they don't mention `a` or call anything impure, are skipped.
+//! An assignment like `a = {}` starts an object too, as long as `a` is declared in the file, since
+//! an undeclared global could be a getter or setter on the global object.
+
+use swc_common::{collections::AHashSet, Spanned};
+use swc_ecma_ast::{
+    ArrowExpr, BinaryOp, Decl, Expr, Function, GetterProp, Id, KeyValueProp, Lit, MemberProp,
+    MethodProp, Module, ModuleItem, ObjectLit, PatOrExpr, Prop, PropName, PropOrSpread, Script,
+    SetterProp, Stmt,
+};
+use swc_ecma_transforms_testing::test;
+use swc_ecma_utils::{collect_decls, IdentUsageFinder};
+#[cfg(test)]
+use swc_ecma_visit::as_folder;
+use swc_ecma_visit::{noop_visit_type, Visit, VisitMut, VisitMutWith, VisitWith};
+
+use crate::{
+    effect::EffectAnalysis,
+    passes::{Pass, PassId},
+    safety::Annotator,
+    trust::Trust,
+    util::{get_assign_eq_expr, CallFinder, NiceAccess, StmtsMut},
+    FromMagiConfig, MagiConfig,
+};
+
+#[derive(Default)]
+pub struct ObjectInit {
+    effects: EffectAnalysis,
+    /// The variables declared anywhere in the file, which can be assigned an object to start it
+    declared: AHashSet<Id>,
+    trust: Trust,
+    annotator: Annotator,
+    changed: bool,
+}
+impl FromMagiConfig for ObjectInit {
+    fn from_config(conf: &MagiConfig) -> Self {
+        Self {
+            trust: conf.trust.clone(),
+            annotator: Annotator::new(conf, PassId::ObjectInit),
+            ..Self::default()
+        }
+    }
+}
+impl Pass for ObjectInit {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+impl VisitMut for ObjectInit {
+    fn visit_mut_module(&mut self, module: &mut Module) {
+        self.effects = EffectAnalysis::analyze(module, &self.trust);
+        self.declared = collect_decls(&*module);
+        module.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_script(&mut self, script: &mut Script) {
+        self.effects = EffectAnalysis::analyze(script, &self.trust);
+        self.declared = collect_decls(&*script);
+        script.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_stmts(&mut self, stmts: &mut Vec<Stmt>) {
+        if object_init(
+            stmts.into(),
+            &self.effects,
+            &self.declared,
+            &mut self.annotator,
+        ) {
+            self.changed = true;
+        }
+
+        stmts.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_module_items(&mut self, items: &mut Vec<ModuleItem>) {
+        if object_init(
+            items.into(),
+            &self.effects,
+            &self.declared,
+            &mut self.annotator,
+        ) {
+            self.changed = true;
+        }
+
+        items.visit_mut_children_with(self);
+    }
+}
+
+/// Returns `true` if any edits were made
+fn object_init(
+    mut stmts: StmtsMut<'_>,
+    effects: &EffectAnalysis,
+    declared: &AHashSet<Id>,
+    annotator: &mut Annotator,
+) -> bool {
+    let mut changed = false;
+    for idx in 0..stmts.len() {
+        let Some(id) = stmts
+            .get(idx)
+            .and_then(|stmt| started_object(stmt, declared))
+        else {
+            continue;
+        };
+
+        // Absorbed statements are removed, so `next` only moves on past the skipped ones
+        let mut absorbed = false;
+        let mut skipped = Vec::new();
+        let mut next = idx + 1;
+        while next < stmts.len() {
+            match absorb_next(&mut stmts, idx, next, &id, effects, &skipped) {
+                Next::Absorbed => absorbed = true,
+                Next::Skip => {
+                    skipped.push(next);
+                    next += 1;
+                }
+                Next::Stop => break,
+            }
+        }
+        if absorbed {
+            if let Some(stmt) = stmts.get(idx) {
+                annotator.annotate(stmt.span());
+            }
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+enum Next {
+    /// The statement was moved into the object and removed
+    Absorbed,
+    /// The statement can't see `a`, so later ones can be moved past it
+    Skip,
+    Stop,
+}
+
+/// If `stmts[idx]` declares an object that `stmts[next]` initializes or adds a property to,
+/// then move that into the object literal and remove the statement. `skipped` are the
+/// statements in between, which the moved value must not depend on.
+fn absorb_next(
+    stmts: &mut StmtsMut<'_>,
+    idx: usize,
+    next: usize,
+    id: &Id,
+    effects: &EffectAnalysis,
+    skipped: &[usize],
+) -> Next {
+    let Some(stmt) = stmts.get(next) else {
+        return Next::Stop;
+    };
+    let Some(assign) = stmt
+        .as_expr()
+        .and_then(|expr| get_assign_eq_expr(&expr.expr))
+    else {
+        return if is_inert(stmt, id, effects) {
+            Next::Skip
+        } else {
+            Next::Stop
+        };
+    };
+
+    let prop = match NiceAccess::try_from(&assign.left) {
+        // `a = a || {}` doesn't evaluate the right side, since `a` is an object
+        Ok(NiceAccess::Ident(ident)) if ident.to_id() == *id => {
+            if !is_or_default(&assign.right, id) {
+                return Next::Stop;
+            }
+            None
+        }
+        _ => {
+            let target = match &assign.left {
+                PatOrExpr::Expr(expr) => Some(&**expr),
+                PatOrExpr::Pat(pat) => pat.as_expr().map(|expr| &**expr),
+            };
+            let Some(key) = target.and_then(|target| property_key(target, id)) else {
+                return if is_inert(stmt, id, effects) {
+                    Next::Skip
+                } else {
+                    Next::Stop
+                };
+            };
+            // The value used to be evaluated once `a` was set, so it can't be allowed to look at
+            // `a`, or run code which might. Pure functions can't see it. It also used to come
+            // after the skipped statements, so it can't read anything they use.
+            let value = &*assign.right;
+            if CallFinder::find_impure(value, effects)
+                || UsedOutsideClosures::find(id, value)
+                || skipped.iter().filter_map(|&i| stmts.get(i)).any(|skipped| {
+                    let mut used = Idents::default();
+                    skipped.visit_with(&mut used);
+                    used.0
+                        .iter()
+                        .any(|used| UsedOutsideClosures::find(used, value))
+                })
+            {
+                return Next::Stop;
+            }
+            Some((key, assign.right.clone()))
+        }
+    };
+
+    if let Some((key, value)) = prop {
+        let Some(object) = stmts.get_mut(idx).and_then(object_mut) else {
+            unreachable!("the statement was checked to start an object");
+        };
+        if !add_prop(object, key, value, effects) {
+            return Next::Stop;
+        }
+    }
+    stmts.remove(next);
+
+    Next::Absorbed
+}
+
+/// Whether `a` can be moved past the statement, because it can't see it or run code that might
+fn is_inert(stmt: &Stmt, id: &Id, effects: &EffectAnalysis) -> bool {
+    matches!(
+        stmt,
+        Stmt::Expr(_) | Stmt::Decl(Decl::Var(_) | Decl::Fn(_)) | Stmt::Empty(_)
+    ) && !IdentUsageFinder::find(id, stmt)
+        && !CallFinder::find_impure(stmt, effects)
+}
+
+/// Set the property on the object literal. A key that is already there has its value replaced
+/// rather than being written twice, which Typescript doesn't allow, so that is only done when
+/// the old value and the ones after it can't run any code.
+fn add_prop(
+    object: &mut ObjectLit,
+    key: PropName,
+    value: Box<Expr>,
+    effects: &EffectAnalysis,
+) -> bool {
+    let Some(name) = key_name(&key) else {
+        object
+            .props
+            .push(PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                key,
+                value,
+            }))));
+        return true;
+    };
+
+    let existing = object.props.iter().position(|prop| match prop {
+        PropOrSpread::Prop(prop) => match &**prop {
+            Prop::KeyValue(KeyValueProp { key, .. })
+            | Prop::Method(MethodProp { key, .. })
+            | Prop::Getter(GetterProp { key, .. })
+            | Prop::Setter(SetterProp { key, .. }) => key_name(key).as_ref() == Some(&name),
+            Prop::Shorthand(ident) => *ident.sym == *name,
+            Prop::Assign(_) => false,
+        },
+        PropOrSpread::Spread(_) => false,
+    });
+    let Some(existing) = existing else {
+        object
+            .props
+            .push(PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                key,
+                value,
+            }))));
+        return true;
+    };
+
+    // Properties with unknown keys or values that run code could see the order change
+    let rest_is_inert = object.props[existing..].iter().all(|prop| match prop {
+        PropOrSpread::Prop(prop) => match &**prop {
+            Prop::KeyValue(KeyValueProp { key, value }) => {
+                !key.is_computed() && !CallFinder::find_impure(&**value, effects)
+            }
+            Prop::Shorthand(_) => true,
+            _ => false,
+        },
+        PropOrSpread::Spread(_) => false,
+    });
+    let PropOrSpread::Prop(prop) = &mut object.props[existing] else {
+        unreachable!("spreads don't have a key");
+    };
+    match &mut **prop {
+        Prop::KeyValue(prop) if rest_is_inert => {
+            prop.value = value;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The name of a property key, if it is known
+fn key_name(key: &PropName) -> Option<String> {
+    match key {
+        PropName::Ident(ident) => Some(ident.sym.to_string()),
+        PropName::Str(str) => Some(str.value.to_string()),
+        PropName::Num(num) if num.value.fract() == 0.0 && num.value.abs() < 1e21 => {
+            Some((num.value as i64).to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Collects every variable that is used
+#[derive(Default)]
+struct Idents(AHashSet<Id>);
+impl Visit for Idents {
+    noop_visit_type!();
+
+    fn visit_ident(&mut self, ident: &swc_ecma_ast::Ident) {
+        self.0.insert(ident.to_id());
+    }
+
+    fn visit_member_prop(&mut self, prop: &MemberProp) {
+        if let MemberProp::Computed(computed) = prop {
+            computed.visit_with(self);
+        }
+    }
+
+    fn visit_prop_name(&mut self, name: &PropName) {
+        if let PropName::Computed(computed) = name {
+            computed.visit_with(self);
+        }
+    }
+}
+
+/// If the statement ends by declaring a variable to be an object literal, or assigns one to a
+/// declared variable like `a = {}`, get the variable
+fn started_object(stmt: &Stmt, declared: &AHashSet<Id>) -> Option<Id> {
+    let (id, object) = match stmt {
+        Stmt::Decl(Decl::Var(var)) => {
+            let decl = var.decls.last()?;
+            let Expr::Object(object) = decl.init.as_deref()? else {
+                return None;
+            };
+            (decl.name.as_ident()?.to_id(), object)
+        }
+        Stmt::Expr(expr) => {
+            let assign = get_assign_eq_expr(&expr.expr)?;
+            let Ok(NiceAccess::Ident(ident)) = NiceAccess::try_from(&assign.left) else {
+                return None;
+            };
+            let Expr::Object(object) = &*assign.right else {
+                return None;
+            };
+            let id = ident.to_id();
+            (declared.contains(&id).then_some(id)?, object)
+        }
+        _ => return None,
+    };
+
+    // Assigning to a property with a setter would call it, rather than replacing it
+    let plain = object.props.iter().all(|prop| match prop {
+        PropOrSpread::Prop(prop) => !matches!(&**prop, Prop::Getter(_) | Prop::Setter(_)),
+        PropOrSpread::Spread(_) => true,
+    });
+
+    plain.then_some(id)
+}
+
+/// The object literal of a statement that [`started_object`] accepted
+fn object_mut(stmt: &mut Stmt) -> Option<&mut ObjectLit> {
+    let object = match stmt {
+        Stmt::Decl(Decl::Var(var)) => var.decls.last_mut()?.init.as_deref_mut()?,
+        Stmt::Expr(expr) => &mut *expr.expr.as_mut_assign()?.right,
+        _ => return None,
+    };
+    object.as_mut_object()
+}
+
+/// Whether the expression is `id || <anything>`
+fn is_or_default(expr: &Expr, id: &Id) -> bool {
+    expr.as_bin().is_some_and(|bin| {
+        bin.op == BinaryOp::LogicalOr
+            && matches!(&*bin.left, Expr::Ident(left) if left.to_id() == *id)
+    })
+}
+
+/// If the expression is `id.key` or `id["key"]`, get the key for an object literal
+fn property_key(expr: &Expr, id: &Id) -> Option<PropName> {
+    let member = expr.as_member()?;
+    match &*member.obj {
+        Expr::Ident(obj) if obj.to_id() == *id => {}
+        _ => return None,
+    }
+
+    let key = match &member.prop {
+        MemberProp::Ident(ident) => PropName::Ident(ident.clone()),
+        MemberProp::Computed(computed) => match &*computed.expr {
+            Expr::Lit(Lit::Str(str)) => PropName::Str(str.clone()),
+            Expr::Lit(Lit::Num(num)) => PropName::Num(num.clone()),
+            _ => return None,
+        },
+        MemberProp::PrivateName(_) => return None,
+    };
+
+    // `__proto__` in an object literal sets the prototype instead of a property
+    let is_proto = match &key {
+        PropName::Ident(ident) => &*ident.sym == "__proto__",
+        PropName::Str(str) => &*str.value == "__proto__",
+        _ => false,
+    };
+
+    (!is_proto).then_some(key)
+}
+
+/// Finds uses of the variable that happen right away, rather than in a function which might be
+/// called later.
+struct UsedOutsideClosures<'a> {
+    id: &'a Id,
+    found: bool,
+}
+impl<'a> UsedOutsideClosures<'a> {
+    fn find<N: VisitWith<Self>>(id: &'a Id, node: &N) -> bool {
+        let mut finder = Self { id, found: false };
+        node.visit_with(&mut finder);
+        finder.found
+    }
+}
+impl Visit for UsedOutsideClosures<'_> {
+    noop_visit_type!();
+
+    fn visit_ident(&mut self, ident: &swc_ecma_ast::Ident) {
+        if ident.to_id() == *self.id {
+            self.found = true;
+        }
+    }
+
+    fn visit_member_prop(&mut self, prop: &MemberProp) {
+        if let MemberProp::Computed(computed) = prop {
+            computed.visit_with(self);
+        }
+    }
+
+    fn visit_prop_name(&mut self, name: &PropName) {
+        if let PropName::Computed(computed) = name {
+            computed.visit_with(self);
+        }
+    }
+
+    fn visit_function(&mut self, _: &Function) {}
+
+    fn visit_arrow_expr(&mut self, _: &ArrowExpr) {}
+}
+
+test!(
+    Default::default(),
+    |_| as_folder(ObjectInit::default()),
+    object_init_readme,
+    r#"var a = {}; a.blah = "hi"; a = a || {}; a.thing = "hi";"#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(ObjectInit::default()),
+    object_init_keys,
+    r#"var a = { x: 1 }; a["y-z"] = 2; a[3] = 3; a.f = function() { return a.x; }; a.x = 4;"#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(ObjectInit::default()),
+    object_init_duplicate_key,
+    // `f()` has to run before `y` is set, and `x` could be the key in `k`
+    r#"var a = { x: 1, y: f() }; a.x = 2; a.y = 3; var b = { x: 1, [k]: 2 }; b.x = 3;"#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(ObjectInit::default()),
+    object_init_skip_inert,
+    r#"
+    function f() {
+        var a = {};
+        a.blah = "hi";
+        var b = 2;
+        a = a || {};
+        a.thing = "hi";
+        b = 3;
+        a.other = b;
+    }
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(ObjectInit::default()),
+    object_init_stop_at_use,
+    r#"var a = {}; a.x = 1; a.y = a.x + 1; a.z = 2;"#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(ObjectInit::default()),
+    object_init_stop_at_call,
+    r#"var a = {}; a.x = 1; a.y = f(); a.z = 2;"#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(ObjectInit::default()),
+    object_init_pure_call,
+    r#"function add(x, y) { return x + y; } var a = {}; a.x = add(1, 2); a.y = String(a.x); a.z = Math.max(1, add(2, 3)); a.w = 2;"#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(ObjectInit::from_config(&MagiConfig {
+        trust: Trust {
+            pure: vec!["n.d".into()],
+            proxies: vec!["store".into()],
+            ..Default::default()
+        },
+        ..Default::default()
+    })),
+    object_init_trusted,
+    r#"var a = {}; a.x = n.d(1); a.y = store.count; a.z = 2;"#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(ObjectInit::default()),
+    object_init_stop_at_other_statement,
+    r#"var a = {}; console.log(a); a = a || {}; a.x = 1;"#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(ObjectInit::default()),
+    object_init_assignment,
+    // `b` isn't declared, so it could be a setter on the global object
+    r#"var a; a = {}; a.x = 1; a.y = 2; b = {}; b.x = 1;"#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(ObjectInit::default()),
+    object_init_setter,
+    r#"var a = { set x(v) {} }; a.x = 1; var b = {}; b.__proto__ = a; b["__proto__"] = a;"#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(ObjectInit::default()),
+    object_init_other_object,
+    r#"var a = {}; var b = {}; b.x = 1; a.x = 2; c.x = 3;"#
+);
@@ -0,0 +1,473 @@
+//! Recover the optional chaining and nullish coalescing that Babel, Typescript and esbuild compile
+//! into conditionals through a temporary variable.
+//! Example:
+//! ```js
+//! function f(a, e, d) {
+//!     var t, n;
+//!     var b = null == (t = a.b) ? void 0 : t.c();
+//!     var x = null !== (n = e.x) && void 0 !== n ? n : d;
+//!     return null == a ? void 0 : a.y;
+//! }
+//! ```
+//! becomes
+//! ```js
+//! function f(a, e, d) {
+//!     var b = a.b?.c();
+//!     var x = e.x ?? d;
+//!     return a?.y;
+//! }
+//! ```
+
+use std::collections::HashSet;
+
+use swc_common::{Span, Spanned};
+use swc_ecma_ast::{
+    ArrowExpr, BinExpr, BinaryOp, BlockStmtOrExpr, CallExpr, Callee, CondExpr, Decl, Expr,
+    Function, Id, MemberExpr, Module, OptCall, OptChainBase, OptChainExpr, ParenExpr, Pat, Script,
+    Stmt,
+};
+use swc_ecma_transforms_testing::test;
+use swc_ecma_utils::{find_pat_ids, IdentUsageFinder, StmtLike};
+#[cfg(test)]
+use swc_ecma_visit::as_folder;
+use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith, VisitWith};
+
+use crate::{
+    eval::EvalGuard,
+    passes::{Pass, PassId},
+    safety::Annotator,
+    util::{extract_ident_assign, extract_nullish_check, is_undefined},
+    FromMagiConfig, MagiConfig,
+};
+
+/// Converts `null == (t = a.b) ? void 0 : t.c` into `a.b?.c` and
+/// `null != (t = a.b) ? t : d` into `a.b ?? d`, removing the temporaries that are left unused
+#[derive(Default)]
+pub struct OptionalChainVisitor {
+    eval: EvalGuard,
+    assume_es_modules: bool,
+    annotator: Annotator,
+    changed: bool,
+}
+impl FromMagiConfig for OptionalChainVisitor {
+    fn from_config(conf: &MagiConfig) -> Self {
+        Self {
+            eval: EvalGuard::new(conf.eval_safety),
+            assume_es_modules: conf.assume_es_modules,
+            annotator: Annotator::new(conf, PassId::OptionalChain),
+            changed: false,
+        }
+    }
+}
+impl Pass for OptionalChainVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// Describes the change for eval warnings
+const REMOVE_TEMP: &str = "removing a temporary variable";
+
+/// Put parentheses around the object of a chain when `?.` would bind tighter than it
+fn wrap_chain_object(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Ident(_)
+        | Expr::This(_)
+        | Expr::Member(_)
+        | Expr::SuperProp(_)
+        | Expr::Call(_)
+        | Expr::OptChain(_)
+        | Expr::Paren(_)
+        | Expr::Array(_)
+        | Expr::Object(_)
+        | Expr::Lit(_) => expr.clone(),
+        _ => wrap(expr),
+    }
+}
+
+/// Put parentheses around an operand of `??`, which can't be mixed with `||` and `&&`
+fn wrap_nullish_operand(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Bin(BinExpr {
+            op: BinaryOp::LogicalOr | BinaryOp::LogicalAnd,
+            ..
+        })
+        | Expr::Cond(_)
+        | Expr::Assign(_)
+        | Expr::Seq(_)
+        | Expr::Arrow(_)
+        | Expr::Yield(_) => wrap(expr),
+        _ => expr.clone(),
+    }
+}
+
+fn wrap(expr: &Expr) -> Expr {
+    Expr::Paren(ParenExpr {
+        span: expr.span(),
+        expr: Box::new(expr.clone()),
+    })
+}
+
+/// Rebuild the chain `t.b.c()` as `object?.b.c()`, where `t` is the tested variable.
+/// Every link of an optional chain has to be part of it, so that it all short-circuits.
+fn rebuild_chain(expr: &Expr, tested: &Id, object: &Expr) -> Option<Expr> {
+    let (inner, optional, is_call) = match expr {
+        Expr::Member(member) => (&*member.obj, false, false),
+        Expr::Call(CallExpr {
+            callee: Callee::Expr(callee),
+            ..
+        }) => (&**callee, false, true),
+        Expr::OptChain(chain) => match &*chain.base {
+            OptChainBase::Member(member) => (&*member.obj, chain.optional, false),
+            OptChainBase::Call(call) => (&*call.callee, chain.optional, true),
+        },
+        _ => return None,
+    };
+
+    if matches!(inner, Expr::Ident(ident) if ident.to_id() == *tested) {
+        // `t()` calls the function without a `this`, unlike `a.b?.()`
+        if is_call
+            && matches!(
+                object.unwrap_parens(),
+                Expr::Member(_) | Expr::SuperProp(_) | Expr::OptChain(_)
+            )
+        {
+            return None;
+        }
+        return Some(Expr::OptChain(OptChainExpr {
+            span: expr.span(),
+            optional: true,
+            base: Box::new(relink(expr, wrap_chain_object(object))),
+        }));
+    }
+
+    let inner = rebuild_chain(inner, tested, object)?;
+
+    Some(Expr::OptChain(OptChainExpr {
+        span: expr.span(),
+        optional,
+        base: Box::new(relink(expr, inner)),
+    }))
+}
+
+/// The link of the chain in `expr`, on top of a new `inner` object
+fn relink(expr: &Expr, inner: Expr) -> OptChainBase {
+    let inner = Box::new(inner);
+    match expr {
+        Expr::Member(member) => OptChainBase::Member(MemberExpr {
+            span: member.span,
+            obj: inner,
+            prop: member.prop.clone(),
+        }),
+        Expr::Call(call) => OptChainBase::Call(OptCall {
+            span: call.span,
+            callee: inner,
+            args: call.args.clone(),
+            type_args: call.type_args.clone(),
+        }),
+        Expr::OptChain(chain) => match &*chain.base {
+            OptChainBase::Member(member) => OptChainBase::Member(MemberExpr {
+                span: member.span,
+                obj: inner,
+                prop: member.prop.clone(),
+            }),
+            OptChainBase::Call(call) => OptChainBase::Call(OptCall {
+                span: call.span,
+                callee: inner,
+                args: call.args.clone(),
+                type_args: call.type_args.clone(),
+            }),
+        },
+        _ => unreachable!("`rebuild_chain` only relinks members and calls"),
+    }
+}
+
+/// `null == (t = a.b) ? void 0 : t.c` into `a.b?.c`, or `null != (t = a.b) ? t : d` into
+/// `a.b ?? d`, along with the temporary that is no longer needed.
+/// The temporary has to be one of `temps`, but a variable that is tested directly, like in
+/// `null == a ? void 0 : a.c`, is always accepted.
+fn recover(cond: &CondExpr, temps: &HashSet<Id>) -> Option<(Expr, Option<Id>)> {
+    let check = extract_nullish_check(&cond.test)?;
+    let (tested, temp, object) = match extract_ident_assign(check.value) {
+        Some((ident, value)) => {
+            let id = ident.to_id();
+            if !temps.contains(&id) {
+                return None;
+            }
+            (id.clone(), Some(id), value)
+        }
+        None => (check.value.as_ident()?.to_id(), None, check.value),
+    };
+
+    let (when_nullish, otherwise) = if check.when_nullish {
+        (&*cond.cons, &*cond.alt)
+    } else {
+        (&*cond.alt, &*cond.cons)
+    };
+    let otherwise = otherwise.unwrap_parens();
+
+    let expr = if is_undefined(when_nullish) {
+        let mut chain = rebuild_chain(otherwise, &tested, object)?;
+        // The conditional's span is where the annotation for it goes
+        if let Expr::OptChain(chain) = &mut chain {
+            chain.span = cond.span;
+        }
+        chain
+    } else if matches!(otherwise, Expr::Ident(ident) if ident.to_id() == tested) {
+        Expr::Bin(BinExpr {
+            span: cond.span,
+            op: BinaryOp::NullishCoalescing,
+            left: Box::new(wrap_nullish_operand(object)),
+            right: Box::new(wrap_nullish_operand(when_nullish)),
+        })
+    } else {
+        return None;
+    };
+    Some((expr, temp))
+}
+
+/// Recovers the chains that go through one of the temporaries of a scope
+struct TempRewriter<'a> {
+    temps: &'a HashSet<Id>,
+    used: HashSet<Id>,
+    sites: Vec<Span>,
+}
+impl VisitMut for TempRewriter<'_> {
+    noop_visit_mut_type!();
+
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        // `a?.b?.c` is nested the other way around, so the inner chain has to be recovered first
+        expr.visit_mut_children_with(self);
+
+        let Expr::Cond(cond) = expr else {
+            return;
+        };
+        if let Some((new_expr, Some(temp))) = recover(cond, self.temps) {
+            self.sites.push(cond.span);
+            self.used.insert(temp);
+            *expr = new_expr;
+        }
+    }
+}
+
+/// The variables declared without a value directly in `body`, like `var t, n;`
+fn declared_temps<T: StmtLike>(body: &[T]) -> HashSet<Id> {
+    body.iter()
+        .filter_map(|item| match item.as_stmt()? {
+            Stmt::Decl(Decl::Var(var)) => Some(&var.decls),
+            _ => None,
+        })
+        .flatten()
+        .filter(|decl| decl.init.is_none())
+        .filter_map(|decl| Some(decl.name.as_ident()?.to_id()))
+        .collect()
+}
+
+/// Remove the declarations of `temps` directly in `body`
+fn remove_temps<T: StmtLike>(body: &mut Vec<T>, temps: &HashSet<Id>) {
+    body.retain_mut(|item| {
+        let Some(Stmt::Decl(Decl::Var(var))) = item.as_stmt_mut() else {
+            return true;
+        };
+        var.decls.retain(|decl| {
+            !(decl.init.is_none()
+                && matches!(&decl.name, Pat::Ident(ident) if temps.contains(&ident.to_id())))
+        });
+        !var.decls.is_empty()
+    });
+}
+
+impl OptionalChainVisitor {
+    /// Recover the chains in a function or module body that go through the temporaries it
+    /// declares. A temporary that is still used afterwards needed the values that were assigned
+    /// to it, so those chains are left as they are.
+    fn recover_temps<T>(&mut self, span: Span, body: &mut Vec<T>, params: &[Id])
+    where
+        T: StmtLike + Clone,
+        Vec<T>: for<'a> VisitMutWith<TempRewriter<'a>> + for<'a> VisitWith<IdentUsageFinder<'a>>,
+    {
+        let mut temps = declared_temps(body);
+        for param in params {
+            temps.remove(param);
+        }
+
+        while !temps.is_empty() {
+            let mut rewritten = body.clone();
+            let mut rewriter = TempRewriter {
+                temps: &temps,
+                used: HashSet::new(),
+                sites: Vec::new(),
+            };
+            rewritten.visit_mut_with(&mut rewriter);
+            let TempRewriter { used, sites, .. } = rewriter;
+            if used.is_empty() {
+                return;
+            }
+
+            remove_temps(&mut rewritten, &used);
+            let still_used: Vec<Id> = used
+                .into_iter()
+                .filter(|temp| IdentUsageFinder::find(temp, &rewritten))
+                .collect();
+            if still_used.is_empty() {
+                if !self.eval.allows(span, REMOVE_TEMP) {
+                    return;
+                }
+                for site in sites {
+                    self.annotator.annotate(site);
+                }
+                *body = rewritten;
+                self.changed = true;
+                return;
+            }
+            for temp in still_used {
+                temps.remove(&temp);
+            }
+        }
+    }
+}
+
+impl VisitMut for OptionalChainVisitor {
+    noop_visit_mut_type!();
+
+    fn visit_mut_module(&mut self, m: &mut Module) {
+        self.eval.enter(m);
+        m.visit_mut_children_with(self);
+        // Scripts are parsed as modules too, and their top level variables are globals
+        if self.assume_es_modules {
+            self.recover_temps(m.span, &mut m.body, &[]);
+        }
+        self.eval.exit();
+    }
+
+    fn visit_mut_script(&mut self, s: &mut Script) {
+        self.eval.enter(s);
+        s.visit_mut_children_with(self);
+        self.eval.exit();
+    }
+
+    fn visit_mut_function(&mut self, f: &mut Function) {
+        self.eval.enter(f);
+        f.visit_mut_children_with(self);
+        if let Some(body) = &mut f.body {
+            let params: Vec<Id> = find_pat_ids(&f.params);
+            self.recover_temps(f.span, &mut body.stmts, &params);
+        }
+        self.eval.exit();
+    }
+
+    fn visit_mut_arrow_expr(&mut self, a: &mut ArrowExpr) {
+        self.eval.enter(a);
+        a.visit_mut_children_with(self);
+        if let BlockStmtOrExpr::BlockStmt(body) = &mut *a.body {
+            let params: Vec<Id> = find_pat_ids(&a.params);
+            self.recover_temps(a.span, &mut body.stmts, &params);
+        }
+        self.eval.exit();
+    }
+
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        expr.visit_mut_children_with(self);
+
+        let Expr::Cond(cond) = expr else {
+            return;
+        };
+        if let Some((new_expr, _)) = recover(cond, &HashSet::new()) {
+            self.annotator.annotate(cond.span);
+            *expr = new_expr;
+            self.changed = true;
+        }
+    }
+}
+
+test!(
+    Default::default(),
+    |_| as_folder(OptionalChainVisitor::default()),
+    optional_chain_direct,
+    r#"
+    a = null == b ? void 0 : b.c;
+    d = e != null ? e.f.g() : undefined;
+    h = i === null || i === void 0 ? void 0 : i[j];
+    k = null == l ? void 0 : l(m);
+    n = null != o ? o : p;
+    q = r == null ? s || t : r;
+    u = null == v ? void 0 : w.x;
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(OptionalChainVisitor::default()),
+    optional_chain_babel,
+    r#"
+    function f(a) {
+        var _a$b, _a, _a$c;
+        var x = (_a$b = a.b) === null || _a$b === void 0 ? void 0 : _a$b.c;
+        var y = (_a = a) === null || _a === void 0 ? void 0 : (_a$c = _a.c) === null || _a$c === void 0 ? void 0 : _a$c.d();
+        return [x, y];
+    }
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(OptionalChainVisitor::default()),
+    optional_chain_nullish,
+    r#"
+    function f(e, d) {
+        var n, t, r;
+        var a = null !== (n = e.x) && void 0 !== n ? n : d;
+        var b = (t = e.y) != null ? t : d;
+        var c = (r = e.z || e.w) == null ? d : r;
+        return [a, b, c];
+    }
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(OptionalChainVisitor::default()),
+    optional_chain_minified,
+    r#"
+    function f(e) {
+        var t;
+        return null == (t = e.a) ? void 0 : t.b.c(e);
+    }
+    const g = (e) => {
+        let t;
+        return (t = e.a) == null ? void 0 : t.call(e, 1);
+    };
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(OptionalChainVisitor::default()),
+    optional_chain_temp_used,
+    r#"
+    function f(e) {
+        var t, n;
+        var a = null == (t = e.a) ? void 0 : t.b;
+        use(t);
+        var b = null == (n = e.c) ? void 0 : n(n);
+        return [a, b];
+    }
+    function g(t) {
+        return null == (t = t.a) ? void 0 : t.b;
+    }
+    function h(e) {
+        var t;
+        return null == (t = e.a) ? void 0 : t();
+    }
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(OptionalChainVisitor::default()),
+    optional_chain_top_level,
+    r#"
+    var t;
+    a = null == (t = b.c) ? void 0 : t.d;
+    "#
+);
@@ -0,0 +1,279 @@
+//! Undo the ways that obfuscators and minifiers write properties.
+//! Example:
+//! ```js
+//! a["foo"] = a["\x62ar"];
+//! var o = { "b": b, ["c"]: 1, f: function () { return 2; } };
+//! ```
+//! becomes
+//! ```js
+//! a.foo = a.bar;
+//! var o = { b, c: 1, f() { return 2; } };
+//! ```
+
+use swc_common::util::take::Take;
+use swc_ecma_ast::{
+    AssignExpr, Expr, FnExpr, Function, GetterProp, Ident, KeyValueProp, Lit, MemberExpr,
+    MemberProp, MetaPropExpr, MetaPropKind, MethodProp, Pat, PatOrExpr, Prop, PropName, SetterProp,
+};
+use swc_ecma_transforms_testing::test;
+use swc_ecma_utils::{is_valid_prop_ident, IdentUsageFinder};
+#[cfg(test)]
+use swc_ecma_visit::as_folder;
+use swc_ecma_visit::{
+    noop_visit_mut_type, noop_visit_type, Visit, VisitMut, VisitMutWith, VisitWith,
+};
+
+use crate::{
+    passes::{Pass, PassId},
+    safety::Annotator,
+    FromMagiConfig, MagiConfig,
+};
+
+/// Converts `a["b"]` into `a.b`, `{b: b}` into `{b}` and `{f: function () {}}` into `{f() {}}`
+#[derive(Default)]
+pub struct PropNormalizeVisitor {
+    annotator: Annotator,
+    changed: bool,
+}
+impl FromMagiConfig for PropNormalizeVisitor {
+    fn from_config(conf: &MagiConfig) -> Self {
+        Self {
+            annotator: Annotator::new(conf, PassId::PropNormalize),
+            changed: false,
+        }
+    }
+}
+impl Pass for PropNormalizeVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// The name that `["b"]` could be written as with a dot
+fn as_ident(expr: &Expr) -> Option<Ident> {
+    let Expr::Lit(Lit::Str(str)) = expr else {
+        return None;
+    };
+    is_valid_prop_ident(&str.value).then(|| Ident::new(str.value.clone(), str.span))
+}
+
+/// `"b"` or `["b"]` into `b`, and `["a-b"]` into `"a-b"`
+fn normalize_key(key: &mut PropName) -> bool {
+    match key {
+        PropName::Str(str) if is_valid_prop_ident(&str.value) => {
+            *key = PropName::Ident(Ident::new(str.value.clone(), str.span));
+            true
+        }
+        // Unlike `__proto__: a`, `["__proto__"]: a` doesn't set the prototype
+        PropName::Computed(computed) => {
+            let Expr::Lit(Lit::Str(str)) = &mut *computed.expr else {
+                return false;
+            };
+            if &*str.value == "__proto__" {
+                return false;
+            }
+            *key = if is_valid_prop_ident(&str.value) {
+                PropName::Ident(Ident::new(str.value.clone(), str.span))
+            } else {
+                PropName::Str(str.take())
+            };
+            true
+        }
+        _ => false,
+    }
+}
+
+fn is_proto(key: &PropName) -> bool {
+    match key {
+        PropName::Ident(ident) => &*ident.sym == "__proto__",
+        PropName::Str(str) => &*str.value == "__proto__",
+        _ => false,
+    }
+}
+
+/// Finds signs that a function is meant to be called with `new`, like the `{F: function () {
+/// this.x = 1; }}` of a namespace: `new.target`, assigning to a property of `this`, or using a
+/// `prototype`
+#[derive(Default)]
+struct ConstructorFinder {
+    found: bool,
+}
+impl Visit for ConstructorFinder {
+    noop_visit_type!();
+
+    // Nested functions have their own `this` and `new.target`, but arrows share them
+    fn visit_function(&mut self, _: &Function) {}
+
+    fn visit_meta_prop_expr(&mut self, meta: &MetaPropExpr) {
+        self.found |= meta.kind == MetaPropKind::NewTarget;
+    }
+
+    fn visit_assign_expr(&mut self, assign: &AssignExpr) {
+        assign.visit_children_with(self);
+
+        let left = match &assign.left {
+            PatOrExpr::Expr(expr) => Some(&**expr),
+            PatOrExpr::Pat(pat) => match &**pat {
+                Pat::Expr(expr) => Some(&**expr),
+                _ => None,
+            },
+        };
+        if let Some(Expr::Member(member)) = left {
+            self.found |= member.obj.is_this();
+        }
+    }
+
+    fn visit_member_prop(&mut self, prop: &MemberProp) {
+        prop.visit_children_with(self);
+
+        if let MemberProp::Ident(ident) = prop {
+            self.found |= &*ident.sym == "prototype";
+        }
+    }
+}
+
+fn is_constructor(function: &Function) -> bool {
+    let mut finder = ConstructorFinder::default();
+    function.body.visit_with(&mut finder);
+    finder.found
+}
+
+/// Whether `key: function () {}` can be written as `key() {}`.
+/// A method can't be used with `new`, so functions that look like constructors are left alone,
+/// and can't see the name of a named function expression.
+fn is_method(key: &PropName, value: &Expr) -> bool {
+    let Expr::Fn(FnExpr { ident, function }) = value else {
+        return false;
+    };
+    !is_proto(key)
+        && !is_constructor(function)
+        && ident
+            .as_ref()
+            .is_none_or(|ident| !IdentUsageFinder::find(&ident.to_id(), function))
+}
+
+impl VisitMut for PropNormalizeVisitor {
+    noop_visit_mut_type!();
+
+    fn visit_mut_member_expr(&mut self, member: &mut MemberExpr) {
+        member.visit_mut_children_with(self);
+
+        if let MemberProp::Computed(computed) = &member.prop {
+            if let Some(ident) = as_ident(&computed.expr) {
+                member.prop = MemberProp::Ident(ident);
+                self.changed = true;
+            }
+        }
+    }
+
+    fn visit_mut_prop(&mut self, prop: &mut Prop) {
+        prop.visit_mut_children_with(self);
+
+        let key = match prop {
+            Prop::KeyValue(KeyValueProp { key, .. })
+            | Prop::Method(MethodProp { key, .. })
+            | Prop::Getter(GetterProp { key, .. })
+            | Prop::Setter(SetterProp { key, .. }) => key,
+            Prop::Shorthand(_) | Prop::Assign(_) => return,
+        };
+        if normalize_key(key) {
+            self.changed = true;
+        }
+
+        let Prop::KeyValue(KeyValueProp { key, value }) = prop else {
+            return;
+        };
+        // `{b: b}`, which would set the prototype if it was `__proto__`
+        if let (PropName::Ident(key), Expr::Ident(value)) = (&*key, &**value) {
+            if key.sym == value.sym && &*key.sym != "__proto__" {
+                *prop = Prop::Shorthand(value.clone());
+                self.changed = true;
+                return;
+            }
+        }
+
+        if is_method(key, value) {
+            let Expr::Fn(FnExpr { function, .. }) = &mut **value else {
+                unreachable!("`is_method` only accepts functions");
+            };
+            self.annotator.annotate(function.span);
+            *prop = Prop::Method(MethodProp {
+                key: key.clone(),
+                function: function.take(),
+            });
+            self.changed = true;
+        }
+    }
+}
+
+test!(
+    Default::default(),
+    |_| as_folder(PropNormalizeVisitor::default()),
+    prop_normalize_member,
+    r#"
+    a["foo"] = a["\x62ar"];
+    a["b-c"]();
+    a[0];
+    a["class"].d;
+    a?.["e"];
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(PropNormalizeVisitor::default()),
+    prop_normalize_keys,
+    r#"
+    var o = {
+        "a": 1,
+        ["b"]: 2,
+        ["c-d"]: 3,
+        ["__proto__"]: 4,
+        "e-f": 5,
+        get ["g"]() { return 6; },
+    };
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(PropNormalizeVisitor::default()),
+    prop_normalize_shorthand,
+    r#"
+    var o = { a: a, "b": b, c: d, __proto__: __proto__ };
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(PropNormalizeVisitor::default()),
+    prop_normalize_method,
+    r#"
+    var o = {
+        a: function () { return 1; },
+        b: function* () { yield 1; },
+        c: async function () {},
+        d: function d() {},
+        e: function e() { return e; },
+        __proto__: function () {},
+        f: () => 1,
+    };
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(PropNormalizeVisitor::default()),
+    prop_normalize_constructor,
+    r#"
+    var ns = {
+        A: function () { this.x = 1; },
+        B: function () { if (!new.target) throw 1; },
+        C: function () { return () => { this["y"] = 2; }; },
+        D: function () {},
+        E: function () { return this.x; },
+        F: function () { return function () { this.x = 1; }; },
+    };
+    var G = { f: function () { return G.prototype; } };
+    "#
+);
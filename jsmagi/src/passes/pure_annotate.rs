@@ -0,0 +1,150 @@
+//! Mark calls to functions that have no side effects with a `/* pure */` comment.
+//! Example:
+//! ```js
+//! function add(a, b) {
+//!     return a + b;
+//! }
+//! var c = add(1, 2);
+//! ```
+//! becomes
+//! ```js
+//! function add(a, b) {
+//!     return a + b;
+//! }
+//! var c = /* pure */ add(1, 2);
+//! ```
+//! Only calls to functions declared in the file are marked, since it is already clear that
+//! builtins like `Math.max` are pure. See [`crate::effect`] for how the functions are checked.
+
+use std::collections::HashSet;
+
+use swc_common::{
+    comments::{Comment, CommentKind, Comments, SingleThreadedComments},
+    BytePos, DUMMY_SP,
+};
+use swc_ecma_ast::{CallExpr, Module, Script};
+#[cfg(test)]
+use swc_ecma_transforms_base::resolver;
+use swc_ecma_transforms_testing::test;
+use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
+
+use crate::{effect::EffectAnalysis, passes::Pass, trust::Trust, FromMagiConfig, MagiConfig};
+
+pub struct PureAnnotate {
+    comments: SingleThreadedComments,
+    trust: Trust,
+    effects: EffectAnalysis,
+    /// The calls that have already been given a comment, since the pass is run repeatedly
+    commented: HashSet<BytePos>,
+}
+impl FromMagiConfig for PureAnnotate {
+    fn from_config(conf: &MagiConfig) -> Self {
+        Self {
+            comments: conf.comments.clone(),
+            trust: conf.trust.clone(),
+            effects: EffectAnalysis::default(),
+            commented: HashSet::new(),
+        }
+    }
+}
+impl Pass for PureAnnotate {
+    // Comments aren't part of the tree, so they can't give the other passes more to do
+    fn take_changed(&mut self) -> bool {
+        false
+    }
+}
+
+impl PureAnnotate {
+    /// Whether there is already a comment saying that the call is pure
+    fn has_pure_comment(&self, pos: BytePos) -> bool {
+        self.comments.with_leading(pos, |comments| {
+            comments
+                .iter()
+                .any(|comment| matches!(comment.text.trim(), "pure" | "#__PURE__" | "@__PURE__"))
+        })
+    }
+}
+
+impl VisitMut for PureAnnotate {
+    noop_visit_mut_type!();
+
+    fn visit_mut_module(&mut self, module: &mut Module) {
+        self.effects = EffectAnalysis::analyze(module, &self.trust);
+        module.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_script(&mut self, script: &mut Script) {
+        self.effects = EffectAnalysis::analyze(script, &self.trust);
+        script.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_call_expr(&mut self, call: &mut CallExpr) {
+        call.visit_mut_children_with(self);
+
+        let pure = self
+            .effects
+            .declared_callee(call)
+            .is_some_and(|effects| effects.is_pure());
+        let pos = call.span.lo;
+        if !pure || call.span.is_dummy() || self.has_pure_comment(pos) {
+            return;
+        }
+        if !self.commented.insert(pos) {
+            return;
+        }
+
+        self.comments.add_leading(
+            pos,
+            Comment {
+                kind: CommentKind::Block,
+                span: DUMMY_SP,
+                text: " pure ".into(),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+fn tr(t: &swc_ecma_transforms_testing::Tester<'_>) -> impl swc_ecma_visit::Fold {
+    use swc_common::{chain, Mark};
+    use swc_ecma_visit::as_folder;
+
+    let conf = MagiConfig {
+        comments: (*t.comments).clone(),
+        ..Default::default()
+    };
+
+    chain!(
+        resolver(Mark::new(), Mark::new(), false),
+        as_folder(PureAnnotate::from_config(&conf))
+    )
+}
+
+test!(
+    Default::default(),
+    |t| tr(t),
+    pure_annotate_readme,
+    "function add(a, b) { return a + b; } var c = add(1, 2);"
+);
+
+test!(
+    Default::default(),
+    |t| tr(t),
+    pure_annotate_calls,
+    r#"
+    function square(a) { return a * a; }
+    function hyp(a, b) { return Math.sqrt(square(a) + square(b)); }
+    function log(a) { console.log(a); }
+    function set(o) { o.x = 1; }
+    const twice = (x) => String(x) + x;
+    log(hyp(3, twice(4)));
+    set({});
+    "#
+);
+
+test!(
+    Default::default(),
+    |t| tr(t),
+    pure_annotate_existing,
+    "function f() { return 1; } var a = /*#__PURE__*/ f(); var b = f();"
+);
@@ -1,16 +1,129 @@
-use swc_ecma_ast::{Expr, ExprStmt, ModuleItem, Stmt};
+use std::vec::IntoIter;
+
+use swc_common::{util::take::Take, Span, Spanned};
+use swc_ecma_ast::{
+    ArrowExpr, BlockStmt, BlockStmtOrExpr, Expr, ExprStmt, ForStmt, IfStmt, LabeledStmt,
+    ModuleItem, ReturnStmt, Stmt, SwitchStmt, ThrowStmt, UnaryExpr, UnaryOp, VarDeclOrExpr,
+};
 use swc_ecma_transforms_testing::test;
 #[cfg(test)]
 use swc_ecma_visit::as_folder;
 use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
 
-use crate::{FromMagiConfig, MagiConfig};
+use crate::{passes::Pass, FromMagiConfig, MagiConfig};
 
-/// Converts `a, b, c` statements into `a; b; c;`
-pub struct SeqExpandVisitor;
+/// Converts `a, b, c` statements into `a; b; c;`  
+/// The leading expressions of a sequence are also moved out of `return`, `throw`, `if` and
+/// `switch` tests and `for` initializers, so `return a(), b;` becomes `a(); return b;`.
+#[derive(Default)]
+pub struct SeqExpandVisitor {
+    changed: bool,
+}
 impl FromMagiConfig for SeqExpandVisitor {
     fn from_config(_conf: &MagiConfig) -> Self {
-        Self
+        Self::default()
+    }
+}
+impl Pass for SeqExpandVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// If `expr` is a sequence, then replace it with its last expression and return the others
+fn split_seq(expr: &mut Box<Expr>) -> Option<IntoIter<Box<Expr>>> {
+    if !expr.unwrap_parens().is_seq() {
+        return None;
+    }
+
+    let mut inner = expr.take();
+    while let Expr::Paren(paren) = *inner {
+        inner = paren.expr;
+    }
+    let Expr::Seq(mut seq) = *inner else {
+        unreachable!("the sequence was checked for");
+    };
+    let last = seq.exprs.pop()?;
+    *expr = last;
+
+    Some(seq.exprs.into_iter())
+}
+
+fn expr_stmts(
+    span: Span,
+    exprs: impl IntoIterator<Item = Box<Expr>>,
+) -> impl Iterator<Item = Stmt> {
+    exprs
+        .into_iter()
+        .map(move |expr| Stmt::Expr(ExprStmt { span, expr }))
+}
+
+/// Split the statement into several, or give it back if there is nothing to split
+fn expand_stmt(mut stmt: Stmt) -> Result<Vec<Stmt>, Stmt> {
+    let span = stmt.span();
+    let leading = match &mut stmt {
+        Stmt::Expr(ExprStmt { expr, .. }) => match &mut **expr {
+            Expr::Seq(seq) => return Ok(expr_stmts(span, seq.exprs.take()).collect()),
+            _ => None,
+        },
+        Stmt::Return(ReturnStmt {
+            arg: Some(arg),
+            span,
+        }) => {
+            if let Expr::Unary(UnaryExpr {
+                op: UnaryOp::Void,
+                arg: value,
+                ..
+            }) = &mut **arg
+            {
+                // `return void f()` is `f(); return;`, but `return void 0` is left to the other
+                // passes
+                if !value.is_lit() {
+                    return Ok(vec![
+                        Stmt::Expr(ExprStmt {
+                            span: *span,
+                            expr: value.take(),
+                        }),
+                        Stmt::Return(ReturnStmt {
+                            span: *span,
+                            arg: None,
+                        }),
+                    ]);
+                }
+            }
+            split_seq(arg)
+        }
+        Stmt::Throw(ThrowStmt { arg, .. }) => split_seq(arg),
+        Stmt::If(IfStmt { test, .. }) => split_seq(test),
+        Stmt::Switch(SwitchStmt { discriminant, .. }) => split_seq(discriminant),
+        // Only the initializer is run once, so the test and update are left alone
+        Stmt::For(ForStmt {
+            init: Some(VarDeclOrExpr::Expr(init)),
+            ..
+        }) => split_seq(init),
+        _ => None,
+    };
+
+    match leading {
+        Some(leading) => Ok(expr_stmts(span, leading).chain([stmt]).collect()),
+        None => Err(stmt),
+    }
+}
+
+/// Split the statement, and the statements that it splits into, onto the end of `out`.
+/// Returns whether anything was split.
+fn expand_into(stmt: Stmt, out: &mut Vec<Stmt>) -> bool {
+    match expand_stmt(stmt) {
+        Ok(stmts) => {
+            for stmt in stmts {
+                expand_into(stmt, out);
+            }
+            true
+        }
+        Err(stmt) => {
+            out.push(stmt);
+            false
+        }
     }
 }
 
@@ -20,17 +133,7 @@ impl VisitMut for SeqExpandVisitor {
     fn visit_mut_stmts(&mut self, stmts: &mut Vec<Stmt>) {
         let mut new_stmts = Vec::new();
         for stmt in stmts.drain(..) {
-            match stmt {
-                Stmt::Expr(ExprStmt { span, expr }) => match *expr {
-                    Expr::Seq(seq) => {
-                        for expr in seq.exprs {
-                            new_stmts.push(Stmt::Expr(ExprStmt { span, expr }));
-                        }
-                    }
-                    _ => new_stmts.push(Stmt::Expr(ExprStmt { span, expr })),
-                },
-                _ => new_stmts.push(stmt),
-            }
+            self.changed |= expand_into(stmt, &mut new_stmts);
         }
         *stmts = new_stmts;
 
@@ -42,18 +145,11 @@ impl VisitMut for SeqExpandVisitor {
         let mut new_items = Vec::new();
         for item in items.drain(..) {
             match item {
-                ModuleItem::Stmt(stmt) => match stmt {
-                    Stmt::Expr(ExprStmt { span, expr }) => match *expr {
-                        Expr::Seq(seq) => {
-                            for expr in seq.exprs {
-                                new_items
-                                    .push(ModuleItem::Stmt(Stmt::Expr(ExprStmt { span, expr })));
-                            }
-                        }
-                        _ => new_items.push(ModuleItem::Stmt(Stmt::Expr(ExprStmt { span, expr }))),
-                    },
-                    _ => new_items.push(ModuleItem::Stmt(stmt)),
-                },
+                ModuleItem::Stmt(stmt) => {
+                    let mut stmts = Vec::new();
+                    self.changed |= expand_into(stmt, &mut stmts);
+                    new_items.extend(stmts.into_iter().map(ModuleItem::Stmt));
+                }
                 _ => new_items.push(item),
             }
         }
@@ -61,25 +157,103 @@ impl VisitMut for SeqExpandVisitor {
 
         items.visit_mut_children_with(self);
     }
+
+    // A statement on its own, like the body in `if (a) return b(), c;`, needs a block to hold
+    // the statements that it is split into. The statements in a list are already split.
+    fn visit_mut_stmt(&mut self, stmt: &mut Stmt) {
+        let span = stmt.span();
+        let mut stmts = Vec::new();
+        if expand_into(stmt.take(), &mut stmts) {
+            self.changed = true;
+            *stmt = Stmt::Block(BlockStmt { span, stmts });
+        } else if let Some(only) = stmts.pop() {
+            *stmt = only;
+        }
+
+        stmt.visit_mut_children_with(self);
+    }
+
+    // Putting the body of a labeled loop in a block would break `continue label`
+    fn visit_mut_labeled_stmt(&mut self, labeled: &mut LabeledStmt) {
+        labeled.body.visit_mut_children_with(self);
+    }
+
+    // `() => (a(), b)` into `() => { a(); return b; }`
+    fn visit_mut_arrow_expr(&mut self, arrow: &mut ArrowExpr) {
+        if let BlockStmtOrExpr::Expr(body) = &mut *arrow.body {
+            let span = body.span();
+            if let Some(leading) = split_seq(body) {
+                let stmts = expr_stmts(span, leading)
+                    .chain([Stmt::Return(ReturnStmt {
+                        span,
+                        arg: Some(body.take()),
+                    })])
+                    .collect();
+                *arrow.body = BlockStmtOrExpr::BlockStmt(BlockStmt { span, stmts });
+                self.changed = true;
+            }
+        }
+
+        arrow.visit_mut_children_with(self);
+    }
 }
 
 test!(
     Default::default(),
-    |_| as_folder(SeqExpandVisitor),
+    |_| as_folder(SeqExpandVisitor::default()),
     seq,
     "a, b, c" // "a; b; c;"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(SeqExpandVisitor),
+    |_| as_folder(SeqExpandVisitor::default()),
     seq2,
     "Object.defineProperty(), t.a = t.b = t.c = t.d = t.e" // "Object.defineProperty(); t.a = t.b = t.c = t.d = t.e;"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(SeqExpandVisitor),
+    |_| as_folder(SeqExpandVisitor::default()),
     seq3,
     "function a () { Object.defineProperty(), t.a = t.b = t.c = t.d = t.e }" // "function a () { Object.defineProperty(); t.a = t.b = t.c = t.d = t.e; }"
 );
+
+test!(
+    Default::default(),
+    |_| as_folder(SeqExpandVisitor::default()),
+    seq_return,
+    r#"
+    function a() { return this._token || (this._token = new c), this._token; }
+    function b() { if (x) return f(), g(), 1; throw h(), new Error(); }
+    function c() { return void r(1); }
+    function d() { return void 0; }
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(SeqExpandVisitor::default()),
+    seq_tests,
+    r#"
+    if (a(), b) c();
+    else if (d(), e) f();
+    switch (g(), h) { case 1: i(); }
+    for (j(), k = 0; k < 1; l(), k++) m();
+    while (n(), o) p();
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(SeqExpandVisitor::default()),
+    seq_arrow,
+    "var f = (a) => (g(a), a + 1); var h = (a) => a;"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(SeqExpandVisitor::default()),
+    seq_labeled,
+    "outer: for (a(), i = 0; i < 1; i++) { for (;;) { continue outer; } }"
+);
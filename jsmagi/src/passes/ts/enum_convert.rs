@@ -1,16 +1,22 @@
 use swc_atoms::JsWord;
-use swc_common::{Mark, SyntaxContext};
+use swc_common::{Mark, Spanned, SyntaxContext};
 use swc_ecma_ast::{
-    op, AssignExpr, BinExpr, BindingIdent, CallExpr, Callee, Expr, ExprOrSpread, ExprStmt, FnExpr,
-    Ident, Lit, ModuleItem, Pat, PatOrExpr, Stmt, TsEnumDecl, TsEnumMember, TsEnumMemberId,
+    op, AssignExpr, BinExpr, BindingIdent, CallExpr, Callee, Decl, Expr, ExprOrSpread, ExprStmt,
+    FnExpr, Ident, Lit, MemberProp, ModuleItem, Pat, PatOrExpr, Stmt, TsEnumDecl, TsEnumMember,
+    TsEnumMemberId, VarDecl, VarDeclKind, VarDeclarator,
 };
 
 use swc_ecma_transforms_testing::test;
 
-use swc_ecma_utils::member_expr;
+use swc_ecma_utils::{is_valid_ident, member_expr};
 use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
 
 use crate::{
+    access::AccessAnalysis,
+    eval::{track_eval_scopes, EvalGuard},
+    passes::{Pass, PassId},
+    safety::Annotator,
+    trust::Trust,
     util::{extract_or_initializer_with_assign, get_assign_eq_expr, make_empty_object, NiceAccess},
     FromMagiConfig, MagiConfig, RandomName,
 };
@@ -18,14 +24,34 @@ use crate::{
 /// This converts IIFE constructed enums in Javascript to their Typescript equivalent.
 pub struct EnumConvert {
     random_name: RandomName,
+    eval: EvalGuard,
+    access: AccessAnalysis,
+    trust: Trust,
+    strict: bool,
+    annotator: Annotator,
+    changed: bool,
 }
 impl FromMagiConfig for EnumConvert {
     fn from_config(conf: &MagiConfig) -> Self {
         Self {
             random_name: conf.random_name(),
+            eval: EvalGuard::new(conf.eval_safety),
+            access: AccessAnalysis::default(),
+            trust: conf.trust.clone(),
+            strict: conf.strict_semantics,
+            annotator: Annotator::new(conf, PassId::EnumConvert),
+            changed: false,
         }
     }
 }
+impl Pass for EnumConvert {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// Describes the change for eval warnings
+const CONVERT: &str = "converting an enum";
 
 /// Returns `Some(function being called, arguments to the function)`
 fn get_iife(expr: &Expr) -> Option<(&FnExpr, &Vec<ExprOrSpread>)> {
@@ -49,10 +75,15 @@ fn get_iife(expr: &Expr) -> Option<(&FnExpr, &Vec<ExprOrSpread>)> {
 /// ```js
 /// })(p = exports.MyEnum || (exports.MyEnum = {}));
 /// ```
-fn visit_stmt(random_name: &RandomName, stmt: &Stmt) -> Option<Vec<Stmt>> {
+fn visit_stmt(
+    random_name: &RandomName,
+    access: &AccessAnalysis,
+    strict: bool,
+    stmt: &Stmt,
+) -> Option<Vec<Stmt>> {
     let ExprStmt { expr, span } = stmt.as_expr()?;
 
-    let span = span.clone();
+    let span = *span;
 
     let (fn_expr, args) = get_iife(expr)?;
     let func = &fn_expr.function;
@@ -70,7 +101,7 @@ fn visit_stmt(random_name: &RandomName, stmt: &Stmt) -> Option<Vec<Stmt>> {
         return None;
     }
 
-    let (assign_ident, init_access) = extract_or_initializer_with_assign(&*arg)?;
+    let (assign_ident, init_access) = extract_or_initializer_with_assign(arg, access)?;
     let init_access_pat_or_expr: PatOrExpr = init_access.clone().try_into().ok()?;
     let init_access_expr: Expr = init_access.clone().try_into().ok()?;
 
@@ -83,31 +114,65 @@ fn visit_stmt(random_name: &RandomName, stmt: &Stmt) -> Option<Vec<Stmt>> {
 
     let mut res = Vec::new();
 
+    // `x.y = x.y || {}` reads `x.y` again and always writes to it, which a getter or setter could
+    // notice, so in strict mode the argument is kept as it was and its value is used instead.
+    let keep_init = strict && matches!(init_access, NiceAccess::Member(_));
+
     // Note: assumes no side effects from assignment
     // We need to add the init initializer to the beginning of the statements
     // `x = x || {}`, a simplification of what was `x || (x = {})`
-    res.push(Stmt::Expr(ExprStmt {
-        span,
-        expr: Box::new(Expr::Assign(AssignExpr {
+    if !keep_init {
+        res.push(Stmt::Expr(ExprStmt {
             span,
-            left: init_access_pat_or_expr.clone(),
-            op: op!("="),
-            // a || {}
-            right: Box::new(Expr::Bin(BinExpr {
+            expr: Box::new(Expr::Assign(AssignExpr {
                 span,
-                op: op!("||"),
-                left: Box::new(init_access_expr.clone()),
-                right: Box::new(make_empty_object(span)),
+                left: init_access_pat_or_expr.clone(),
+                op: op!("="),
+                // a || {}
+                right: Box::new(Expr::Bin(BinExpr {
+                    span,
+                    op: op!("||"),
+                    left: Box::new(init_access_expr.clone()),
+                    right: Box::new(make_empty_object(span)),
+                })),
             })),
-        })),
-    }));
+        }));
+    }
+
+    let use_v: Expr = if let (Some(assign_ident), true) = (&assign_ident, keep_init) {
+        // `a = x.y || (x.y = {})`
+        res.push(Stmt::Expr(ExprStmt {
+            span,
+            expr: arg.clone(),
+        }));
 
-    let use_v: Expr = if let Some(assign_ident) = assign_ident {
+        assign_ident.clone().into()
+    } else if keep_init {
+        // `let tmp = x.y || (x.y = {})`
+        let new_ctxt = SyntaxContext::empty().apply_mark(Mark::fresh(Mark::root()));
+        let tmp = Ident::new("tmp".into(), span.with_ctxt(new_ctxt));
+        res.push(Stmt::Decl(Decl::Var(Box::new(VarDecl {
+            span,
+            kind: VarDeclKind::Let,
+            declare: false,
+            decls: vec![VarDeclarator {
+                span,
+                name: Pat::Ident(BindingIdent {
+                    id: tmp.clone(),
+                    type_ann: None,
+                }),
+                init: Some(arg.clone()),
+                definite: false,
+            }],
+        }))));
+
+        tmp.into()
+    } else if let Some(assign_ident) = assign_ident {
         // `a = x`
         res.push(Stmt::Expr(ExprStmt {
-            span: span,
+            span,
             expr: Box::new(Expr::Assign(AssignExpr {
-                span: span,
+                span,
                 left: PatOrExpr::Pat(Box::new(Pat::Ident(BindingIdent {
                     id: assign_ident.clone(),
                     type_ann: None,
@@ -128,9 +193,18 @@ fn visit_stmt(random_name: &RandomName, stmt: &Stmt) -> Option<Vec<Stmt>> {
             .and_then(|mem| {
                 mem.obj
                     .as_ident()
-                    .filter(|id| id.sym == JsWord::from("exports"))
-                    .and_then(|_| mem.prop.as_ident().cloned())
-                    .map(|id| id.sym)
+                    .filter(|id| id.sym == *"exports")
+                    .and_then(|_| match &mem.prop {
+                        MemberProp::Ident(id) => Some(id.sym.clone()),
+                        // `exports["Thing"]`
+                        MemberProp::Computed(computed) => match &*computed.expr {
+                            Expr::Lit(Lit::Str(str)) if is_valid_ident(&str.value) => {
+                                Some(str.value.clone())
+                            }
+                            _ => None,
+                        },
+                        MemberProp::PrivateName(_) => None,
+                    })
             })
             .unwrap_or_else(|| JsWord::from(random_name.get("en")));
         let new_ctxt = SyntaxContext::empty().apply_mark(Mark::fresh(Mark::root()));
@@ -205,7 +279,7 @@ fn visit_stmt(random_name: &RandomName, stmt: &Stmt) -> Option<Vec<Stmt>> {
         // TODO: Check whether numbers have repeats?
 
         let member = TsEnumMember {
-            span: span.clone(),
+            span: *span,
             id: TsEnumMemberId::Ident(prop_name.clone()),
             init: Some(Box::new(init.clone().into())),
         };
@@ -239,7 +313,8 @@ fn visit_stmt(random_name: &RandomName, stmt: &Stmt) -> Option<Vec<Stmt>> {
         let field_access = member_expr!(span, Object.assign);
 
         let target: Expr = match init_access {
-            NiceAccess::Ident(_) => use_v.clone().into(),
+            NiceAccess::Ident(_) => use_v.clone(),
+            NiceAccess::Member(_) if keep_init => use_v.clone(),
             NiceAccess::Member(x) => x.into(),
         };
 
@@ -259,13 +334,35 @@ fn visit_stmt(random_name: &RandomName, stmt: &Stmt) -> Option<Vec<Stmt>> {
     Some(res)
 }
 
+impl EnumConvert {
+    /// The statements replacing `stmt` if it is an enum that may be converted
+    fn convert(&mut self, stmt: &Stmt) -> Option<Vec<Stmt>> {
+        // Checked first, since a conversion takes up names from `random_name` even if it is
+        // thrown away
+        if self.eval.forbids() {
+            return None;
+        }
+        let stmts = visit_stmt(&self.random_name, &self.access, self.strict, stmt)?;
+        if !self.eval.allows(stmt.span(), CONVERT) {
+            return None;
+        }
+        self.annotator.annotate(stmt.span());
+        self.changed = true;
+        Some(stmts)
+    }
+}
+
 impl VisitMut for EnumConvert {
     noop_visit_mut_type!();
 
+    track_eval_scopes!(eval, |this, program| {
+        this.access = AccessAnalysis::analyze(program, &this.trust);
+    });
+
     fn visit_mut_stmts(&mut self, stmts: &mut Vec<Stmt>) {
         let mut new_stmts = Vec::new();
         for stmt in stmts.drain(..) {
-            if let Some(stmts) = visit_stmt(&self.random_name, &stmt) {
+            if let Some(stmts) = self.convert(&stmt) {
                 new_stmts.extend(stmts);
             } else {
                 new_stmts.push(stmt);
@@ -281,7 +378,7 @@ impl VisitMut for EnumConvert {
         let mut new_items = Vec::new();
         for item in items.drain(..) {
             if let ModuleItem::Stmt(stmt) = &item {
-                if let Some(stmts) = visit_stmt(&self.random_name, &stmt) {
+                if let Some(stmts) = self.convert(stmt) {
                     new_items.extend(stmts.into_iter().map(ModuleItem::Stmt));
                     continue;
                 }
@@ -312,6 +409,27 @@ fn enum_convert(
 ) -> swc_ecma_visit::Folder<EnumConvert> {
     swc_ecma_visit::as_folder(EnumConvert {
         random_name: RandomName::default(),
+        eval: EvalGuard::default(),
+        access: AccessAnalysis::default(),
+        trust: Trust::default(),
+        strict: false,
+        annotator: Annotator::default(),
+        changed: false,
+    })
+}
+
+#[cfg(test)]
+fn enum_convert_strict(
+    _: &mut swc_ecma_transforms_testing::Tester<'_>,
+) -> swc_ecma_visit::Folder<EnumConvert> {
+    swc_ecma_visit::as_folder(EnumConvert {
+        random_name: RandomName::default(),
+        eval: EvalGuard::default(),
+        access: AccessAnalysis::default(),
+        trust: Trust::default(),
+        strict: true,
+        annotator: Annotator::default(),
+        changed: false,
     })
 }
 
@@ -335,6 +453,13 @@ test!(
     // "exports.Thing = exports.Thing || {}; enum Thing { A = 0, B = 1, C = 2 }\n Object.assign(exports.Thing, Thing);"
 );
 
+test!(
+    TS_SYN,
+    enum_convert,
+    enum_convert_computed,
+    "(function(e1) { e1[e1.A = 0] = \"A\"; e1[e1.B = 1] = \"B\"; })(exports[\"Thing\"] || (exports.Thing = {}));"
+);
+
 test!(
     TS_SYN,
     enum_convert,
@@ -361,3 +486,25 @@ test!(
     // Should not convert this to an enum. Though if anything actually outputs this, it might be desirable.
     "(function (e) { e[0] = \"A\"; e[1] = \"B\"; })(w || (w = {}));" // "(function (e) { e[0] = \"A\"; e[1] = \"B\"; })(w || (w = {}));"
 );
+
+test!(
+    TS_SYN,
+    enum_convert,
+    enum_convert_eval,
+    // The enum left alone in `f` shouldn't take up a name, so `g`'s is still the first one
+    "function f() { (function (e1) { e1[e1.A = 1] = \"A\"; })(w || (w = {})); eval(s); } function g() { (function (e1) { e1[e1.B = 2] = \"B\"; })(v || (v = {})); }"
+);
+
+test!(
+    TS_SYN,
+    enum_convert_strict,
+    enum_convert_strict1,
+    "(function(e1) { e1[e1.A = 0] = \"A\"; })(p = exports.Thing || (exports.Thing = {}));"
+);
+
+test!(
+    TS_SYN,
+    enum_convert_strict,
+    enum_convert_strict2,
+    "(function(e1) { e1[e1.A = 0] = \"A\"; })(exports.Thing || (exports.Thing = {}));"
+);
@@ -4,13 +4,21 @@ use swc_ecma_transforms_testing::test;
 use swc_ecma_visit::as_folder;
 use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
 
-use crate::{FromMagiConfig, MagiConfig};
+use crate::{passes::Pass, FromMagiConfig, MagiConfig};
 
 /// `let a, b, c;` => `let a; let b; let c;`
-pub struct VarDeclExpand;
+#[derive(Default)]
+pub struct VarDeclExpand {
+    changed: bool,
+}
 impl FromMagiConfig for VarDeclExpand {
     fn from_config(_conf: &MagiConfig) -> Self {
-        Self
+        Self::default()
+    }
+}
+impl Pass for VarDeclExpand {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
     }
 }
 
@@ -23,6 +31,9 @@ impl VisitMut for VarDeclExpand {
             match stmt {
                 Stmt::Decl(decl) => match decl {
                     Decl::Var(var) => {
+                        if var.decls.len() > 1 {
+                            self.changed = true;
+                        }
                         for decl in var.decls {
                             new_stmts.push(Stmt::Decl(Decl::Var(Box::new(VarDecl {
                                 span: var.span,
@@ -50,6 +61,9 @@ impl VisitMut for VarDeclExpand {
                 ModuleItem::Stmt(stmt) => match stmt {
                     Stmt::Decl(decl) => match decl {
                         Decl::Var(var) => {
+                            if var.decls.len() > 1 {
+                                self.changed = true;
+                            }
                             for decl in var.decls {
                                 new_items.push(ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(
                                     VarDecl {
@@ -80,7 +94,7 @@ impl VisitMut for VarDeclExpand {
 // TODO: It seems like `Seq` represents this
 test!(
     Default::default(),
-    |_| as_folder(VarDeclExpand),
+    |_| as_folder(VarDeclExpand::default()),
     multivariable,
     r#"let n,o,b,c,d,e;"# // "let n;\nlet o;\nlet b;\nlet c;\nlet d;\nlet e;"
 );
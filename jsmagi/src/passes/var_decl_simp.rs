@@ -30,225 +30,458 @@
 //!
 //! This pass cannot transform all variable declarations
 
-use std::collections::{HashMap, HashSet};
-
-use swc_atoms::JsWord;
-use swc_common::{collections::AHashSet, util::take::Take};
+use swc_common::Spanned;
 use swc_ecma_ast::{
-    BindingIdent, Decl, Expr, ExprOrSpread, ModuleItem, Stmt, VarDecl, VarDeclKind,
+    ArrowExpr, BinaryOp, BlockStmtOrExpr, Decl, Expr, Function, Id, Module, ModuleItem, Prop,
+    PropName, PropOrSpread, Script, Stmt, UnaryOp, VarDeclKind, VarDeclarator,
 };
 use swc_ecma_transforms_testing::test;
-use swc_ecma_utils::collect_decls;
-use swc_ecma_visit::{as_folder, VisitMut, VisitMutWith};
+use swc_ecma_utils::{find_pat_ids, IdentUsageFinder};
+#[cfg(test)]
+use swc_ecma_visit::as_folder;
+use swc_ecma_visit::{noop_visit_type, Visit, VisitMut, VisitMutWith, VisitWith};
 
-use crate::{util::StmtsMut, FromMagiConfig};
+use crate::{
+    eval::EvalGuard,
+    passes::Pass,
+    util::{get_assign_eq_expr, CallFinder, NiceAccess, StmtsMut},
+    FromMagiConfig,
+};
 
-pub struct VarDeclSimp;
+#[derive(Default)]
+pub struct VarDeclSimp {
+    eval: EvalGuard,
+    /// Whether the top level of a module is its own scope, rather than the global scope
+    assume_es_modules: bool,
+    /// Set when the next statements visited are the body of a function or module, along with the
+    /// parameters of the function.  
+    /// A `var` declared there holds `undefined` until something assigns to it, unlike one in a
+    /// loop body or at the top level of a script, where it can already have a value.
+    fresh_scope: Option<Vec<Id>>,
+    changed: bool,
+}
 impl FromMagiConfig for VarDeclSimp {
-    fn from_config(_conf: &crate::MagiConfig) -> Self {
-        Self
+    fn from_config(conf: &crate::MagiConfig) -> Self {
+        Self {
+            eval: EvalGuard::new(conf.eval_safety),
+            assume_es_modules: conf.assume_es_modules,
+            fresh_scope: None,
+            changed: false,
+        }
+    }
+}
+impl Pass for VarDeclSimp {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
     }
 }
 
+// This tracks the eval scopes like `track_eval_scopes!`, but also has to know where the function
+// bodies start.
 impl VisitMut for VarDeclSimp {
+    fn visit_mut_module(&mut self, m: &mut Module) {
+        self.eval.enter(m);
+        // Scripts are parsed as modules too, so this can only be trusted if the user says so
+        self.fresh_scope = self.assume_es_modules.then(Vec::new);
+        m.visit_mut_children_with(self);
+        self.eval.exit();
+    }
+
+    fn visit_mut_script(&mut self, s: &mut Script) {
+        self.eval.enter(s);
+        // Top level variables are globals, which other scripts may have already set
+        self.fresh_scope = None;
+        s.visit_mut_children_with(self);
+        self.eval.exit();
+    }
+
+    fn visit_mut_function(&mut self, f: &mut Function) {
+        self.eval.enter(f);
+        f.decorators.visit_mut_with(self);
+        f.params.visit_mut_with(self);
+        self.fresh_scope = Some(find_pat_ids(&f.params));
+        f.body.visit_mut_with(self);
+        self.fresh_scope = None;
+        self.eval.exit();
+    }
+
+    fn visit_mut_arrow_expr(&mut self, a: &mut ArrowExpr) {
+        self.eval.enter(a);
+        a.params.visit_mut_with(self);
+        if let BlockStmtOrExpr::BlockStmt(_) = &*a.body {
+            self.fresh_scope = Some(find_pat_ids(&a.params));
+        }
+        a.body.visit_mut_with(self);
+        self.fresh_scope = None;
+        self.eval.exit();
+    }
+
     fn visit_mut_stmts(&mut self, stmts: &mut Vec<Stmt>) {
-        var_decl_simp(stmts.into());
+        let fresh_scope = self.fresh_scope.take();
+        if var_decl_simp(&self.eval, stmts.into(), fresh_scope.as_deref()) {
+            self.changed = true;
+        }
 
         stmts.visit_mut_children_with(self);
     }
 
     fn visit_mut_module_items(&mut self, items: &mut Vec<ModuleItem>) {
-        var_decl_simp(items.into());
+        let fresh_scope = self.fresh_scope.take();
+        if var_decl_simp(&self.eval, items.into(), fresh_scope.as_deref()) {
+            self.changed = true;
+        }
 
         items.visit_mut_children_with(self);
     }
-
-    // fn visit_mut_module_items(&mut self, items: &mut Vec<ModuleItem>) {
-    //     for item in items {
-    //         if let ModuleItem::Stmt(stmt) = item {
-    //             visit_mut_stmt(stmt);
-    //         }
-    //     }
-
-    //     items.visit_mut_children_with(self);
-    // }
 }
 
-enum Edit {
-    /// (tracked_variables_idx, new_init)
-    ChangeInit(usize, Box<Expr>),
-    /// (stmts_idx)
-    RemoveStmt(usize),
-    None,
+/// Moving an assignment into the declaration of the variable it assigns to
+struct Merge {
+    /// The index of the statement declaring the variable
+    decl_stmt: usize,
+    /// The index of the declarator in that statement
+    decl_idx: usize,
+    /// The index of the statement with the assignment
+    assign_stmt: usize,
+    init: Box<Expr>,
 }
 
-struct TrackedVariable {
-    stmts_idx: usize,
-    decl_idx: usize,
-    ident: JsWord,
-    init: Option<Box<Expr>>,
+/// Returns `true` if any edits were made.  
+/// `fresh_scope` is the parameters of the function whose body `stmts` is, see
+/// [`VarDeclSimp::fresh_scope`].
+fn var_decl_simp(eval: &EvalGuard, mut stmts: StmtsMut<'_>, fresh_scope: Option<&[Id]>) -> bool {
+    let mut changed = false;
+    // Each merge removes a statement, which can let another variable through, so this keeps going
+    // until there is nothing left to merge.
+    while let Some(merge) = find_merge(&stmts, fresh_scope) {
+        // The variables are all declared in the same scope, so the first one decides it
+        let span = stmts
+            .get(merge.decl_stmt)
+            .map(Spanned::span)
+            .unwrap_or_default();
+        if !eval.allows(span, "merging assignments into variable declarations") {
+            break;
+        }
+
+        let Some(Stmt::Decl(Decl::Var(var))) = stmts.get_mut(merge.decl_stmt) else {
+            unreachable!("the merge should be for a variable declaration");
+        };
+        var.decls[merge.decl_idx].init = Some(merge.init);
+        stmts.remove(merge.assign_stmt);
+        changed = true;
+    }
+
+    changed
 }
-type TrackedVariables = Vec<TrackedVariable>;
 
-fn find_tv_idx(
-    tracked_variables: &TrackedVariables,
-    stmts_idx: usize,
-    decl_idx: usize,
-) -> Option<&TrackedVariable> {
-    // use iterator adaptors
-    tracked_variables
-        .iter()
-        .rev()
-        .find(|tv| tv.stmts_idx == stmts_idx && tv.decl_idx == decl_idx)
-}
-
-fn var_decl_simp(mut stmts: StmtsMut<'_>) -> Option<()> {
-    let mut tracked_variables = TrackedVariables::new();
-
-    let mut edits: Vec<Edit> = Vec::new();
-
-    // let mut iter = stmts.iter_idx();
-    for (i, stmt) in stmts.iter_idx() {
-        match stmt {
-            Stmt::Decl(x) => handle_decl(&mut tracked_variables, i, x)?,
-            Stmt::Expr(x) => handle_expr(&tracked_variables, &mut edits, i, &x.expr)?,
-            _ => {
-                println!("stmt: {:?}", stmt);
-                return None;
-            }
+/// Find the first uninitialized variable whose first assignment can be merged into its
+/// declaration.
+fn find_merge(stmts: &StmtsMut<'_>, fresh_scope: Option<&[Id]>) -> Option<Merge> {
+    for (decl_stmt, stmt) in stmts.iter_idx() {
+        let Stmt::Decl(Decl::Var(var)) = stmt else {
+            continue;
+        };
+        if var.kind == VarDeclKind::Const || var.declare {
+            continue;
         }
 
-        // for now this just simplifies variables that aren't ever set in their initializer
-    }
-
-    // for edit in edits {
-    //     match edit {
-    //         Edit::ChangeInit(idx, init) => {
-    //             let decl = stmts.get_mut
-    //         },
-    //         Edit::RemoveStmt(_) => todo!(),
-    //     }
-    // }
-    for (stmts_idx, stmt) in stmts.iter_mut_idx() {
-        for edit in edits.iter_mut() {
-            match edit {
-                Edit::ChangeInit(idx, init) => {
-                    let var = &tracked_variables[*idx];
-                    if stmts_idx == var.stmts_idx {
-                        let decl = stmt.as_mut_decl().unwrap();
-                        let var_decl = decl.as_mut_var().unwrap();
-                        let decl = &mut var_decl.decls[var.decl_idx];
-                        assert!(decl.init.is_none(), "We don't currently support more complicated initializer changing in this pass so this is a bug");
-                        decl.init = Some(init.clone());
-
-                        *edit = Edit::None;
-                    }
+        for (decl_idx, decl) in var.decls.iter().enumerate() {
+            if decl.init.is_some() {
+                continue;
+            }
+            let Some(name) = decl.name.as_ident() else {
+                continue;
+            };
+            let id = name.to_id();
+
+            // The declarators after this one run between the declaration and the assignment
+            let rest = &var.decls[decl_idx + 1..];
+            if !rest.iter().all(|decl| skips_over_declarator(decl, &id)) {
+                continue;
+            }
+            let mut declared: Vec<Id> = rest
+                .iter()
+                .flat_map(|decl| find_pat_ids::<_, Id>(&decl.name))
+                .collect();
+
+            for (assign_stmt, stmt) in stmts.iter_idx().skip_while(|(i, _)| *i <= decl_stmt) {
+                if let Some(right) = assigned_value(stmt, &id) {
+                    let Some(init) = merged_init(right, &id, var.kind, &declared, || {
+                        fresh_scope.is_some_and(|params| {
+                            is_undefined_until(stmts, &id, params, decl_stmt, decl_idx, assign_stmt)
+                        })
+                    }) else {
+                        break;
+                    };
+                    return Some(Merge {
+                        decl_stmt,
+                        decl_idx,
+                        assign_stmt,
+                        init,
+                    });
                 }
-                Edit::RemoveStmt(idx) => {
-                    if stmts_idx == *idx {
-                        stmt.take();
 
-                        *edit = Edit::None;
-                    }
+                if !skips_over(stmt, &id) {
+                    break;
+                }
+                if let Stmt::Decl(Decl::Var(var)) = stmt {
+                    declared.extend(find_pat_ids::<_, Id>(&var.decls));
                 }
-                Edit::None => {}
             }
         }
     }
 
-    Some(())
-}
-
-fn handle_decl(
-    tracked_variables: &mut TrackedVariables,
-    stmts_idx: usize,
-    decl: &Decl,
-) -> Option<()> {
-    // TODO: we could allow more variable declaration types
-    let var_decl = decl.as_var()?;
-    for (decl_idx, decl) in var_decl.decls.iter().enumerate() {
-        // TODO: just use ?
-        if let Some(ident) = decl.name.as_ident() {
-            tracked_variables.push(TrackedVariable {
-                stmts_idx,
-                decl_idx,
-                ident: ident.sym.clone(),
-                init: decl.init.clone(),
-            });
-        } else {
-            println!("Found non ident");
-            return None;
-        }
+    None
+}
+
+/// If the statement is `id = <expr>`, get the expression
+fn assigned_value<'a>(stmt: &'a Stmt, id: &Id) -> Option<&'a Expr> {
+    let assign = get_assign_eq_expr(&stmt.as_expr()?.expr)?;
+    match NiceAccess::try_from(&assign.left) {
+        Ok(NiceAccess::Ident(ident)) if ident.to_id() == *id => Some(&assign.right),
+        _ => None,
     }
+}
 
-    Some(())
-}
-
-fn handle_expr(
-    tracked_variables: &TrackedVariables,
-    edits: &mut Vec<Edit>,
-    i: usize,
-    expr: &Expr,
-) -> Option<()> {
-    /*match expr {
-        Expr::Call(call) => {
-            // If it is Object.defineProperty on `exports` then we ignore it
-            let callee = call.callee.as_expr()?;
-            let callee = callee.as_member()?;
-
-            if let Some((obj, prop)) = callee.obj.as_ident().zip(callee.prop.as_ident()) {
-                if obj.sym == *"Object" && prop.sym == *"defineProperty" {
-                    let arg = &call.args[0];
-                    let arg = arg.expr.as_ident()?;
-                    if arg.sym != *"exports" {
-                        return None;
-                    }
-                } else {
-                    println!("Found non Object.defineProperty");
-                    return None;
-                }
-            } else {
-                println!("Found non Object.defineProperty (sub)");
-                return None;
-            }
+/// Get the initializer for the variable if `right` can be moved up to its declaration, past
+/// statements that declare `declared`.
+fn merged_init(
+    right: &Expr,
+    id: &Id,
+    kind: VarDeclKind,
+    declared: &[Id],
+    is_undefined: impl FnOnce() -> bool,
+) -> Option<Box<Expr>> {
+    // `x = x || {}` is just `x = {}` if `x` has not been set yet
+    let or_default = right
+        .as_bin()
+        .filter(|bin| bin.op == BinaryOp::LogicalOr)
+        .filter(|bin| matches!(&*bin.left, Expr::Ident(left) if left.to_id() == *id));
+    let value = or_default.map_or(right, |bin| &*bin.right);
+
+    if IdentUsageFinder::find(id, value)
+        || declared
+            .iter()
+            .any(|declared| IdentUsageFinder::find(declared, value))
+    {
+        return None;
+    }
+
+    // A `let` can't be read before its declaration, so a function called by the initializer
+    // that reads the variable would now throw
+    if kind != VarDeclKind::Var && CallFinder::find(value) {
+        return None;
+    }
+
+    match or_default {
+        // A `let` is always `undefined` when it is declared
+        Some(_) if kind == VarDeclKind::Var && !is_undefined() => Some(Box::new(right.clone())),
+        _ => Some(Box::new(value.clone())),
+    }
+}
+
+/// Whether an assignment to `id` can be moved up past the statement.  
+/// This has to both not use `id`, and not do anything that could notice the assignment having
+/// been moved before it.
+fn skips_over(stmt: &Stmt, id: &Id) -> bool {
+    match stmt {
+        Stmt::Empty(_) => true,
+        // Function declarations are hoisted, so they don't do anything where they are written
+        Stmt::Decl(Decl::Fn(_)) => true,
+        Stmt::Decl(Decl::Var(var)) => var.decls.iter().all(|decl| skips_over_declarator(decl, id)),
+        _ => false,
+    }
+}
+
+fn skips_over_declarator(decl: &VarDeclarator, id: &Id) -> bool {
+    !IdentUsageFinder::find(id, &decl.name) && decl.init.as_deref().is_none_or(is_inert)
+}
+
+/// Whether the variable declared in `stmts[decl_stmt]` certainly holds `undefined` when
+/// `stmts[assign_stmt]` runs.  
+/// Nothing between them uses it, but a `var` is hoisted, so this also has to check that nothing
+/// could set it before the declaration.
+fn is_undefined_until(
+    stmts: &StmtsMut<'_>,
+    id: &Id,
+    params: &[Id],
+    decl_stmt: usize,
+    decl_idx: usize,
+    assign_stmt: usize,
+) -> bool {
+    if params.contains(id) {
+        return false;
+    }
+
+    let references = |idx: usize| match stmts {
+        StmtsMut::Stmts(stmts) => IdentUsageFinder::find(id, &stmts[idx]),
+        StmtsMut::Module(items) => IdentUsageFinder::find(id, &items[idx]),
+    };
+    (0..stmts.len()).all(|idx| match idx {
+        _ if idx < decl_stmt => !references(idx),
+        _ if idx == decl_stmt => {
+            let Some(Stmt::Decl(Decl::Var(var))) = stmts.get(idx) else {
+                return false;
+            };
+            var.decls
+                .iter()
+                .enumerate()
+                .all(|(i, decl)| i == decl_idx || !IdentUsageFinder::find(id, decl))
+        }
+        _ if idx == assign_stmt => true,
+        // Statements after the assignment can only use it earlier through hoisted functions
+        _ => !references(idx) || stmts.get(idx).is_some_and(|stmt| !FnDeclFinder::find(stmt)),
+    })
+}
+
+/// Whether evaluating the expression has no side effects and reads no variables, so that it
+/// can be reordered with anything.
+fn is_inert(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(_) | Expr::Fn(_) | Expr::Arrow(_) => true,
+        Expr::Tpl(tpl) => tpl.exprs.is_empty(),
+        Expr::Paren(paren) => is_inert(&paren.expr),
+        Expr::Unary(unary) => {
+            matches!(
+                unary.op,
+                UnaryOp::Void | UnaryOp::Bang | UnaryOp::Minus | UnaryOp::Plus | UnaryOp::Tilde
+            ) && is_inert(&unary.arg)
         }
+        Expr::Array(array) => array.elems.iter().all(|elem| {
+            elem.as_ref()
+                .is_none_or(|elem| elem.spread.is_none() && is_inert(&elem.expr))
+        }),
+        Expr::Object(object) => object.props.iter().all(|prop| match prop {
+            PropOrSpread::Prop(prop) => match &**prop {
+                Prop::KeyValue(kv) => {
+                    !matches!(kv.key, PropName::Computed(_)) && is_inert(&kv.value)
+                }
+                Prop::Method(method) => !matches!(method.key, PropName::Computed(_)),
+                _ => false,
+            },
+            PropOrSpread::Spread(_) => false,
+        }),
+        _ => false,
     }
+}
 
-    todo!()*/
-    None
+/// Finds function declarations, which could be called before the code around them runs
+#[derive(Default)]
+struct FnDeclFinder {
+    found: bool,
+}
+impl FnDeclFinder {
+    fn find<N: VisitWith<Self>>(node: &N) -> bool {
+        let mut finder = Self::default();
+        node.visit_with(&mut finder);
+        finder.found
+    }
+}
+impl Visit for FnDeclFinder {
+    noop_visit_type!();
+
+    fn visit_fn_decl(&mut self, _: &swc_ecma_ast::FnDecl) {
+        self.found = true;
+    }
 }
 
 test!(
     Default::default(),
-    |_| as_folder(VarDeclSimp),
+    |_| as_folder(VarDeclSimp::default()),
     single_variable,
     "let n;" // "let n;"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(VarDeclSimp),
+    |_| as_folder(VarDeclSimp::default()),
     single_variable_with_init,
     "let n = 0;" // "let n = 0;"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(VarDeclSimp),
+    |_| as_folder(VarDeclSimp::default()),
     single_variable_def,
     "let n; n = 0;" // "let n = 0;"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(VarDeclSimp),
+    |_| as_folder(VarDeclSimp::default()),
     single_variable_def_with_init,
     "let n; n = n || {};" // "let n = {};"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(VarDeclSimp),
+    |_| as_folder(VarDeclSimp::default()),
     multiple_variables_def_with_init,
     "let n; let c; n = n || {}; c = c || {};" // "let n = {}; let c = {};"
 );
+
+test!(
+    Default::default(),
+    |_| as_folder(VarDeclSimp {
+        assume_es_modules: true,
+        ..Default::default()
+    }),
+    skip_unrelated_declarations,
+    "var l; var j; var k = 2; function f() {} l = l || {};"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(VarDeclSimp::default()),
+    var_global,
+    "var l; l = l || {};"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(VarDeclSimp::default()),
+    stop_at_read,
+    "var n; console.log(n); n = 0;"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(VarDeclSimp::default()),
+    stop_at_side_effect,
+    "var n; f(); n = 0;"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(VarDeclSimp::default()),
+    stop_at_declared_variable,
+    "var n; var m = 1; n = m;"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(VarDeclSimp::default()),
+    let_call_initializer,
+    "let n; n = f(); var m; m = f();"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(VarDeclSimp::default()),
+    var_in_function,
+    "function f(a) { var n; n = n || {}; var a; a = a || {}; }"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(VarDeclSimp::default()),
+    var_in_loop,
+    "while (true) { var n; n = n || {}; }"
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(VarDeclSimp::default()),
+    var_set_before_declaration,
+    "function f() { g(); var n; n = n || {}; function g() { n = 1; } }"
+);
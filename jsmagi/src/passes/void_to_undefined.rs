@@ -4,14 +4,31 @@ use swc_ecma_transforms_testing::test;
 use swc_ecma_visit::as_folder;
 use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
 
-use crate::{util::make_undefined, FromMagiConfig, MagiConfig};
+use crate::{
+    passes::{Pass, PassId},
+    safety::Annotator,
+    util::make_undefined,
+    FromMagiConfig, MagiConfig,
+};
 
 /// Convert `void 0` to `undefined`  
 /// Minifiers convert the statements because `void 0` is very slightly shorter, however it is less natural to read.
-pub struct VoidToUndefinedVisitor;
+#[derive(Default)]
+pub struct VoidToUndefinedVisitor {
+    annotator: Annotator,
+    changed: bool,
+}
 impl FromMagiConfig for VoidToUndefinedVisitor {
-    fn from_config(_conf: &MagiConfig) -> Self {
-        Self
+    fn from_config(conf: &MagiConfig) -> Self {
+        Self {
+            annotator: Annotator::new(conf, PassId::VoidToUndefined),
+            changed: false,
+        }
+    }
+}
+impl Pass for VoidToUndefinedVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
     }
 }
 
@@ -24,7 +41,9 @@ impl VisitMut for VoidToUndefinedVisitor {
                 // We assume that `void (some literal)` is always `undefined` with no side effects
                 // TODO: There's a larger class of things that are always `undefined` that we could handle here, but this covers the common case
                 if let Expr::Lit(_) = &*unary.arg {
+                    self.annotator.annotate(unary.span);
                     *expr = make_undefined(unary.span);
+                    self.changed = true;
                 }
             }
         }
@@ -35,28 +54,28 @@ impl VisitMut for VoidToUndefinedVisitor {
 
 test!(
     Default::default(),
-    |_| as_folder(VoidToUndefinedVisitor),
+    |_| as_folder(VoidToUndefinedVisitor::default()),
     void_0,
     "void 0" // "undefined"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(VoidToUndefinedVisitor),
+    |_| as_folder(VoidToUndefinedVisitor::default()),
     void_0_in_expr,
     "void 0 + 1" // "undefined + 1"
 );
 
 test!(
     Default::default(),
-    |_| as_folder(VoidToUndefinedVisitor),
+    |_| as_folder(VoidToUndefinedVisitor::default()),
     void_0_in_expr_2,
     "1 + void 0" // "1 + undefined"
 );
 
 // test!(
 //     Default::default(),
-//     |_| as_folder(VoidToUndefinedVisitor),
+//     |_| as_folder(VoidToUndefinedVisitor::default()),
 //     void_0_in_expr_3,
 //     "void (0 + 2)",
 //     "undefined"
@@ -64,7 +83,7 @@ test!(
 
 test!(
     Default::default(),
-    |_| as_folder(VoidToUndefinedVisitor),
+    |_| as_folder(VoidToUndefinedVisitor::default()),
     void_0_in_expr_4,
     "void console.log('hi')" // "void console.log('hi')"
 );
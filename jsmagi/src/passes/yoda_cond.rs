@@ -0,0 +1,230 @@
+//! Put the constant side of a comparison on the right, like how most people write it.
+//! Example:
+//! ```js
+//! undefined !== v;
+//! "object" == typeof e1;
+//! 0 < a.length;
+//! typeof window > "u";
+//! ```
+//! becomes
+//! ```js
+//! v !== undefined;
+//! typeof e1 == "object";
+//! a.length > 0;
+//! typeof window === "undefined";
+//! ```
+
+use swc_ecma_ast::{BinExpr, BinaryOp, Expr, Lit, Module, Script, Str, UnaryExpr, UnaryOp};
+use swc_ecma_transforms_testing::test;
+#[cfg(test)]
+use swc_ecma_visit::as_folder;
+use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
+
+use crate::{
+    access::AccessAnalysis, passes::Pass, trust::Trust, util::NiceAccess, FromMagiConfig,
+    MagiConfig,
+};
+
+/// Swaps `0 === a` into `a === 0` and expands `typeof a > "u"`
+#[derive(Default)]
+pub struct YodaCondVisitor {
+    access: AccessAnalysis,
+    trust: Trust,
+    changed: bool,
+}
+impl FromMagiConfig for YodaCondVisitor {
+    fn from_config(conf: &MagiConfig) -> Self {
+        Self {
+            access: AccessAnalysis::default(),
+            trust: conf.trust.clone(),
+            changed: false,
+        }
+    }
+}
+impl Pass for YodaCondVisitor {
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// The operator to use once the operands are swapped, if it is a comparison
+fn swapped_op(op: BinaryOp) -> Option<BinaryOp> {
+    match op {
+        BinaryOp::EqEq | BinaryOp::NotEq | BinaryOp::EqEqEq | BinaryOp::NotEqEq => Some(op),
+        BinaryOp::Lt => Some(BinaryOp::Gt),
+        BinaryOp::Gt => Some(BinaryOp::Lt),
+        BinaryOp::LtEq => Some(BinaryOp::GtEq),
+        BinaryOp::GtEq => Some(BinaryOp::LtEq),
+        _ => None,
+    }
+}
+
+/// Whether the expression is a constant like `0`, `"object"`, `undefined` or `void 0`
+fn is_const(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(lit) => matches!(
+            lit,
+            Lit::Str(_) | Lit::Num(_) | Lit::Bool(_) | Lit::Null(_) | Lit::BigInt(_)
+        ),
+        Expr::Ident(ident) => &*ident.sym == "undefined",
+        Expr::Unary(UnaryExpr {
+            op: UnaryOp::Void,
+            arg,
+            ..
+        }) => matches!(**arg, Expr::Lit(_)),
+        // `-1`
+        Expr::Unary(UnaryExpr {
+            op: UnaryOp::Minus,
+            arg,
+            ..
+        }) => matches!(**arg, Expr::Lit(Lit::Num(_))),
+        Expr::Paren(paren) => is_const(&paren.expr),
+        _ => false,
+    }
+}
+
+/// Whether evaluating the expression can't run any code, so that it can be moved around
+fn is_plain(expr: &Expr, access: &AccessAnalysis) -> bool {
+    match expr {
+        Expr::This(_) => true,
+        // `typeof a` doesn't throw even if `a` isn't declared
+        Expr::Unary(UnaryExpr {
+            op: UnaryOp::TypeOf,
+            arg,
+            ..
+        }) => is_plain(arg, access),
+        Expr::Paren(paren) => is_plain(&paren.expr, access),
+        expr => is_const(expr) || NiceAccess::try_from((expr, access)).is_ok(),
+    }
+}
+
+/// `0 === a` into `a === 0`, as long as neither side has side effects
+fn swap_yoda(bin: &mut BinExpr, access: &AccessAnalysis) -> Option<()> {
+    let op = swapped_op(bin.op)?;
+    // `0 === 1` stays as it is, so that it isn't swapped back and forth
+    if !is_const(&bin.left) || is_const(&bin.right) || !is_plain(&bin.right, access) {
+        return None;
+    }
+
+    std::mem::swap(&mut bin.left, &mut bin.right);
+    bin.op = op;
+    Some(())
+}
+
+/// `typeof a > "u"` into `typeof a === "undefined"`.
+/// `"undefined"` is the only result of `typeof` which comes after `"u"`.
+fn expand_typeof_undefined(bin: &mut BinExpr) -> Option<()> {
+    let op = match bin.op {
+        BinaryOp::Gt => BinaryOp::EqEqEq,
+        BinaryOp::Lt => BinaryOp::NotEqEq,
+        _ => return None,
+    };
+    if !matches!(
+        &*bin.left,
+        Expr::Unary(UnaryExpr {
+            op: UnaryOp::TypeOf,
+            ..
+        })
+    ) {
+        return None;
+    }
+    let Expr::Lit(Lit::Str(str)) = &mut *bin.right else {
+        return None;
+    };
+    if &*str.value != "u" {
+        return None;
+    }
+
+    *str = Str {
+        span: str.span,
+        value: "undefined".into(),
+        raw: None,
+    };
+    bin.op = op;
+    Some(())
+}
+
+impl VisitMut for YodaCondVisitor {
+    noop_visit_mut_type!();
+
+    fn visit_mut_module(&mut self, m: &mut Module) {
+        self.access = AccessAnalysis::analyze(&*m, &self.trust);
+        m.visit_mut_children_with(self);
+        self.access = AccessAnalysis::default();
+    }
+
+    fn visit_mut_script(&mut self, s: &mut Script) {
+        self.access = AccessAnalysis::analyze(&*s, &self.trust);
+        s.visit_mut_children_with(self);
+        self.access = AccessAnalysis::default();
+    }
+
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        expr.visit_mut_children_with(self);
+
+        if let Expr::Bin(bin) = expr {
+            let swapped = swap_yoda(bin, &self.access).is_some();
+            let expanded = expand_typeof_undefined(bin).is_some();
+            self.changed |= swapped || expanded;
+        }
+    }
+}
+
+test!(
+    Default::default(),
+    |_| as_folder(YodaCondVisitor::default()),
+    yoda_cond_eq,
+    r#"
+    undefined !== v;
+    "object" == typeof e1;
+    0 === a.thing;
+    null != this;
+    void 0 === b;
+    -1 !== a.indexOf(b);
+    0 === 1;
+    a.b === 0;
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(YodaCondVisitor::default()),
+    yoda_cond_relational,
+    r#"
+    0 < a.length;
+    1 >= b;
+    if (2 <= c && 3 > d) {}
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(YodaCondVisitor::default()),
+    yoda_cond_typeof_undefined,
+    r#"
+    typeof window > "u";
+    typeof document < "u";
+    "u" < typeof module;
+    typeof a > "v";
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(YodaCondVisitor::default()),
+    yoda_cond_getter,
+    r#"
+    Object.defineProperty(a, "b", { get: function () { return 0; } });
+    0 === a.b;
+    0 === a.c;
+    "#
+);
+
+test!(
+    Default::default(),
+    |_| as_folder(YodaCondVisitor::default()),
+    yoda_cond_computed,
+    r#"
+    0 === a[b];
+    "#
+);
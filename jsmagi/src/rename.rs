@@ -1,18 +1,60 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-use swc_ecma_ast::{Id, Ident, Module, Script};
+use swc::sourcemap::DecodedMap;
+use swc_atoms::JsWord;
+use swc_common::{SourceMap, SyntaxContext};
+use swc_ecma_ast::{
+    BindingIdent, ClassDecl, ClassExpr, Decl, FnDecl, FnExpr, Id, Ident, ImportDefaultSpecifier,
+    ImportNamedSpecifier, ImportStarAsSpecifier, Module, Program, Script, Stmt,
+};
+use swc_ecma_transforms_base::rename::rename;
+use swc_ecma_visit::{
+    noop_visit_mut_type, noop_visit_type, Visit, VisitMut, VisitMutWith, VisitWith,
+};
 
-use swc_ecma_visit::{noop_visit_mut_type, VisitMut, VisitMutWith};
+use swc_ecma_transforms_testing::test;
 
-use crate::eval::contains_eval;
+use crate::eval::{tainted_scopes, warn, EvalSafety, EvalScopes};
 
 /// Visitation pass for renaming identifiers to other identifiers  
 /// This is meant to allow for hygienic renaming of identifiers, because it allows
 /// keeping ctxt information so that the identifiers are still considered the same
 /// and so then a hygiene pass can ensure the variable names are correct.
+///
+/// When it is run on a whole module or script, variables whose scope uses `eval` or `with` are
+/// left alone, since those can refer to a variable by its name. The skipped scopes are reported
+/// as warnings.
 pub struct RenameIdentPass {
     pub names: HashMap<Id, Ident>,
 }
+impl RenameIdentPass {
+    /// Stop renaming the variables that an `eval` or `with` could use
+    fn skip_eval_scopes<N>(&mut self, node: &N)
+    where
+        N: VisitWith<EvalScopes>,
+    {
+        for scope in tainted_scopes(node) {
+            let skipped: Vec<JsWord> = scope
+                .bindings
+                .iter()
+                .filter_map(|id| self.names.remove_entry(id))
+                .map(|((sym, _), _)| sym)
+                .collect();
+            if !skipped.is_empty() {
+                warn(
+                    scope.span,
+                    &format!(
+                        "not renaming {} because this scope uses `eval` or `with`",
+                        quote_names(&skipped)
+                    ),
+                );
+            }
+        }
+    }
+}
 impl VisitMut for RenameIdentPass {
     noop_visit_mut_type!();
 
@@ -24,18 +66,275 @@ impl VisitMut for RenameIdentPass {
     }
 
     fn visit_mut_module(&mut self, m: &mut Module) {
-        if contains_eval(m, true) {
-            m.visit_mut_children_with(self);
-        } else {
-            todo!()
-        }
+        self.skip_eval_scopes(m);
+        m.visit_mut_children_with(self);
     }
 
     fn visit_mut_script(&mut self, s: &mut Script) {
-        if contains_eval(s, true) {
-            s.visit_mut_children_with(self);
-        } else {
-            todo!()
+        self.skip_eval_scopes(s);
+        s.visit_mut_children_with(self);
+    }
+}
+
+/// Renames bindings back to the names that they had before minification, using the `names`
+/// of the input source map.  
+/// The mapping that starts at a binding's identifier records its original name, which is
+/// what minifiers like terser and esbuild emit. Only the bindings are looked up, and swc's
+/// `rename` then updates every reference to them. Names that conflict with a binding in another
+/// scope are left for the hygiene pass to resolve, while a binding whose original name is
+/// already used in its own scope keeps its minified name.
+///
+/// Variables in a scope that uses `eval` or `with` are handled according to
+/// [`MagiConfig::eval_safety`](crate::MagiConfig::eval_safety), since those can refer to a
+/// variable by its name. The top-level bindings of scripts are kept as well, because they are globals
+/// that other scripts on the page can use.
+pub struct OriginalNames {
+    source_map: Arc<SourceMap>,
+    /// `None` if there is no input source map, or it couldn't be parsed.
+    input_map: Option<DecodedMap>,
+    eval_safety: EvalSafety,
+}
+impl OriginalNames {
+    pub fn new(
+        source_map: Arc<SourceMap>,
+        input_map: Option<&str>,
+        eval_safety: EvalSafety,
+    ) -> Self {
+        let input_map = input_map.and_then(|map| swc::sourcemap::decode_slice(map.as_bytes()).ok());
+        Self {
+            source_map,
+            input_map,
+            eval_safety,
         }
     }
+
+    /// The name that the input source map has for the identifier, if it is different from the
+    /// current one.
+    fn original_name(&self, input_map: &DecodedMap, ident: &Ident) -> Option<JsWord> {
+        let loc = self.source_map.lookup_char_pos(ident.span.lo);
+        let line = u32::try_from(loc.line.checked_sub(1)?).ok()?;
+        let col = u32::try_from(loc.col.0).ok()?;
+
+        // `lookup_token` gives the closest mapping before the position, which could belong to
+        // an entirely different token.
+        let token = input_map.lookup_token(line, col)?;
+        if token.get_dst_line() != line || token.get_dst_col() != col {
+            return None;
+        }
+
+        let name = token.get_name()?;
+        if name == &*ident.sym || Ident::verify_symbol(name).is_err() {
+            return None;
+        }
+
+        Some(JsWord::from(name))
+    }
 }
+impl VisitMut for OriginalNames {
+    noop_visit_mut_type!();
+
+    fn visit_mut_program(&mut self, program: &mut Program) {
+        let Some(input_map) = &self.input_map else {
+            return;
+        };
+
+        let scopes = match self.eval_safety {
+            EvalSafety::Ignore => Vec::new(),
+            EvalSafety::Warn | EvalSafety::Strict => tainted_scopes(&*program),
+        };
+        // The index of the tainted scope that each variable belongs to
+        let tainted: HashMap<Id, usize> = scopes
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, scope)| scope.bindings.iter().map(move |id| (id.clone(), idx)))
+            .collect();
+        // The variables that have an original name in each tainted scope
+        let mut tainted_names: Vec<Vec<JsWord>> = vec![Vec::new(); scopes.len()];
+
+        let globals_ctxt = match program {
+            Program::Script(script) => top_level_ctxt(&script.body),
+            Program::Module(_) => None,
+        };
+
+        let mut bindings = BindingCollector::default();
+        program.visit_with(&mut bindings);
+
+        // Bindings in the same scope share a syntax context, so two of them with the same name
+        // would become the same variable rather than being told apart by the hygiene pass.
+        let mut taken: HashSet<Id> = bindings.idents.iter().map(Ident::to_id).collect();
+        let mut renames = HashMap::default();
+        for ident in bindings.idents {
+            let id = ident.to_id();
+            if Some(id.1) == globals_ctxt || renames.contains_key(&id) {
+                continue;
+            }
+
+            let Some(name) = self.original_name(input_map, &ident) else {
+                continue;
+            };
+            if let Some(&scope_idx) = tainted.get(&id) {
+                tainted_names[scope_idx].push(id.0.clone());
+                if self.eval_safety == EvalSafety::Strict {
+                    continue;
+                }
+            }
+            if taken.insert((name.clone(), id.1)) {
+                taken.remove(&id);
+                renames.insert(id, name);
+            }
+        }
+
+        for (scope, names) in scopes.iter().zip(tainted_names) {
+            if names.is_empty() {
+                continue;
+            }
+
+            let message = if self.eval_safety == EvalSafety::Strict {
+                format!(
+                    "not renaming {} because this scope uses `eval` or `with`",
+                    quote_names(&names)
+                )
+            } else {
+                format!(
+                    "renaming {} in a scope that uses `eval` or `with`",
+                    quote_names(&names)
+                )
+            };
+            warn(scope.span, &message);
+        }
+
+        if !renames.is_empty() {
+            program.visit_mut_with(&mut rename(&renames));
+        }
+    }
+}
+
+/// Formats the names as "`a`, `b`"
+fn quote_names(names: &[JsWord]) -> String {
+    names
+        .iter()
+        .map(|name| format!("`{}`", name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The resolver gives every top-level binding the same syntax context, so this finds it from
+/// the first top-level declaration.
+fn top_level_ctxt(stmts: &[Stmt]) -> Option<SyntaxContext> {
+    stmts.iter().find_map(|stmt| match stmt.as_decl()? {
+        Decl::Class(class) => Some(class.ident.span.ctxt),
+        Decl::Fn(func) => Some(func.ident.span.ctxt),
+        Decl::Var(var) => var
+            .decls
+            .iter()
+            .find_map(|decl| decl.name.as_ident())
+            .map(|ident| ident.id.span.ctxt),
+        _ => None,
+    })
+}
+
+/// Collects the identifiers that declare a binding, in the order that they appear.
+#[derive(Default)]
+struct BindingCollector {
+    idents: Vec<Ident>,
+}
+impl Visit for BindingCollector {
+    noop_visit_type!();
+
+    fn visit_binding_ident(&mut self, i: &BindingIdent) {
+        self.idents.push(i.id.clone());
+    }
+
+    fn visit_fn_decl(&mut self, f: &FnDecl) {
+        self.idents.push(f.ident.clone());
+        f.visit_children_with(self);
+    }
+
+    fn visit_fn_expr(&mut self, f: &FnExpr) {
+        self.idents.extend(f.ident.clone());
+        f.visit_children_with(self);
+    }
+
+    fn visit_class_decl(&mut self, c: &ClassDecl) {
+        self.idents.push(c.ident.clone());
+        c.visit_children_with(self);
+    }
+
+    fn visit_class_expr(&mut self, c: &ClassExpr) {
+        self.idents.extend(c.ident.clone());
+        c.visit_children_with(self);
+    }
+
+    fn visit_import_default_specifier(&mut self, s: &ImportDefaultSpecifier) {
+        self.idents.push(s.local.clone());
+    }
+
+    fn visit_import_named_specifier(&mut self, s: &ImportNamedSpecifier) {
+        self.idents.push(s.local.clone());
+    }
+
+    fn visit_import_star_as_specifier(&mut self, s: &ImportStarAsSpecifier) {
+        self.idents.push(s.local.clone());
+    }
+}
+
+/// Renames every variable called `a` to `renamed`
+#[cfg(test)]
+struct RenameA;
+#[cfg(test)]
+impl VisitMut for RenameA {
+    fn visit_mut_module(&mut self, m: &mut Module) {
+        let mut bindings = BindingCollector::default();
+        m.visit_with(&mut bindings);
+
+        let names = bindings
+            .idents
+            .into_iter()
+            .filter(|ident| &*ident.sym == "a")
+            .map(|ident| {
+                let renamed = Ident::new("renamed".into(), ident.span);
+                (ident.to_id(), renamed)
+            })
+            .collect();
+        m.visit_mut_with(&mut RenameIdentPass { names });
+    }
+}
+
+#[cfg(test)]
+fn tr() -> impl swc_ecma_visit::Fold {
+    use swc_common::{chain, Mark};
+    use swc_ecma_transforms_base::{hygiene::hygiene, resolver};
+    use swc_ecma_visit::as_folder;
+
+    let unresolved_mark = Mark::new();
+    let top_level_mark = Mark::new();
+
+    chain!(
+        resolver(unresolved_mark, top_level_mark, false),
+        as_folder(RenameA),
+        hygiene(),
+    )
+}
+
+test!(
+    Default::default(),
+    |_| tr(),
+    rename_ident_no_eval,
+    "var a = 1; function f(a) { return a + 1; } f(a);"
+);
+
+test!(
+    Default::default(),
+    |_| tr(),
+    rename_ident_eval_scope,
+    // The `eval` can see the `a`s in `g` and at the top level, but not the one in `f`
+    "var a = 1; function f(a) { return a; } function g(b) { var a = b; return eval('a'); }"
+);
+
+test!(
+    Default::default(),
+    |_| tr(),
+    rename_ident_nested_eval,
+    // The `eval` in `h` can see the `a` of `g` as well as its own
+    "var a = 1; function g() { var a = 2; function h(a) { return eval('a'); } return h; }"
+);
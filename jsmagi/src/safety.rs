@@ -0,0 +1,127 @@
+//! How far each pass can be trusted to keep the code doing the same thing, and the codes that
+//! are left in the output to say which pass rewrote something.
+//!
+//! Every pass has a code like `P002`, whose letter is the level of the pass: `S` for
+//! semantics-preserving, `P` for probably-preserving and `D` for destructive, readability-only
+//! rewrites. With `--annotate`, the sites rewritten by a pass that isn't semantics-preserving
+//! get a `/* MCode(P002) */` comment, and `jsmagi explain P002` says what it could have broken.
+
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use swc_common::{
+    comments::{Comment, CommentKind, Comments, SingleThreadedComments},
+    BytePos, Span, DUMMY_SP,
+};
+
+use crate::{passes::PassId, MagiConfig};
+
+/// How much a pass might change what the code does.
+/// The levels are ordered, so that allowing one level also allows the safer ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Safety {
+    /// The code does exactly the same thing as before
+    Preserving,
+    /// The code does the same thing unless it relies on something unusual, like a getter or a
+    /// variable named `undefined`
+    Probable,
+    /// The code is easier to read, but might not behave the same or might not run at all
+    #[default]
+    Readable,
+}
+impl Safety {
+    pub const ALL: &'static [Safety] = &[Safety::Preserving, Safety::Probable, Safety::Readable];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Safety::Preserving => "preserving",
+            Safety::Probable => "probable",
+            Safety::Readable => "readable",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Safety::Preserving => "semantics-preserving",
+            Safety::Probable => "probably semantics-preserving",
+            Safety::Readable => "readability-only, the output might not run the same",
+        }
+    }
+}
+impl fmt::Display for Safety {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+impl FromStr for Safety {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Safety::ALL
+            .iter()
+            .copied()
+            .find(|safety| safety.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Safety::ALL.iter().map(|x| x.name()).collect();
+                format!(
+                    "unknown safety level `{}`, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Leaves a comment with the code of a pass at each site it rewrites, if `--annotate` is on and
+/// the pass isn't semantics-preserving.
+#[derive(Default)]
+pub(crate) struct Annotator {
+    /// Where to add the comments, or `None` if nothing should be annotated
+    comments: Option<SingleThreadedComments>,
+    code: &'static str,
+    /// The sites that have already been given a comment, since the pass is run repeatedly
+    annotated: HashSet<BytePos>,
+}
+impl Annotator {
+    pub(crate) fn new(conf: &MagiConfig, pass: PassId) -> Self {
+        let annotate = conf.annotate && pass.safety(conf.strict_semantics) > Safety::Preserving;
+        Self {
+            comments: annotate.then(|| conf.comments.clone()),
+            code: pass.code(),
+            annotated: HashSet::new(),
+        }
+    }
+
+    /// Note that the code at `span` was rewritten
+    pub(crate) fn annotate(&mut self, span: Span) {
+        let Some(comments) = &self.comments else {
+            return;
+        };
+        if span.is_dummy() || !self.annotated.insert(span.lo) {
+            return;
+        }
+
+        comments.add_leading(
+            span.lo,
+            Comment {
+                kind: CommentKind::Block,
+                span: DUMMY_SP,
+                text: format!(" MCode({}) ", self.code).into(),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Safety;
+
+    #[test]
+    fn test_safety_names() {
+        for safety in Safety::ALL {
+            assert_eq!(safety.name().parse::<Safety>(), Ok(*safety));
+        }
+        assert!(Safety::Preserving < Safety::Probable);
+        assert!(Safety::Probable < Safety::Readable);
+        assert!("safe".parse::<Safety>().is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! Facts about the code that the user knows, but that jsmagi can't work out by itself.
+//! These are usually loaded from a JSON file, for an app that is reversed build after build:
+//! ```json
+//! {
+//!     "pure": ["n.d", "require"],
+//!     "plain": ["e.exports"],
+//!     "proxies": ["store.state"]
+//! }
+//! ```
+//! Paths are matched by the names of the variables, no matter which scope they are declared in,
+//! since minified code often passes the same object around under the same name.
+
+use std::path::Path;
+
+use serde::Deserialize;
+use swc_ecma_ast::{Expr, Lit, MemberProp};
+
+use crate::error::MagiError;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Trust {
+    /// Functions which have no side effects, like `n.d` or `require`.
+    pub pure: Vec<String>,
+    /// Globals and member paths which are plain values without getters or setters, like
+    /// `e.exports`. Their properties are also plain.
+    pub plain: Vec<String>,
+    /// Globals and member paths which are proxies or have getters, so accessing them must never
+    /// be moved, repeated or skipped. Their properties are also treated as proxies.
+    /// This takes precedence over the other lists.
+    pub proxies: Vec<String>,
+}
+impl Trust {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, MagiError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|err| MagiError::io(path, err))?;
+        serde_json::from_str(&text).map_err(|err| MagiError::Config {
+            path: path.to_owned(),
+            message: err.to_string(),
+        })
+    }
+
+    /// Whether the function at `path` was listed as pure
+    pub fn is_pure(&self, path: &[&str]) -> bool {
+        !self.is_proxy(path) && self.pure.iter().any(|entry| matches(entry, path, false))
+    }
+
+    /// Whether the value at `path` was listed as plain, or is a property of a plain value
+    pub fn is_plain(&self, path: &[&str]) -> bool {
+        !self.is_proxy(path) && self.plain.iter().any(|entry| matches(entry, path, true))
+    }
+
+    /// Whether the value at `path` was listed as a proxy, or is a property of one
+    pub fn is_proxy(&self, path: &[&str]) -> bool {
+        self.proxies.iter().any(|entry| matches(entry, path, true))
+    }
+}
+
+/// Whether the dotted `entry` is `path`, or if `prefix` is set then whether it is a part of it
+fn matches(entry: &str, path: &[&str], prefix: bool) -> bool {
+    let entry: Vec<&str> = entry.split('.').collect();
+    if prefix {
+        path.starts_with(&entry)
+    } else {
+        path == entry
+    }
+}
+
+/// The names in a variable followed by constant properties, like `["e", "exports"]` for
+/// `e.exports` or `e["exports"]`
+pub fn expr_path(expr: &Expr) -> Option<Vec<&str>> {
+    match expr {
+        Expr::Ident(ident) => Some(vec![&*ident.sym]),
+        Expr::Member(member) => {
+            let mut path = expr_path(&member.obj)?;
+            match &member.prop {
+                MemberProp::Ident(ident) => path.push(&*ident.sym),
+                MemberProp::Computed(computed) => match &*computed.expr {
+                    Expr::Lit(Lit::Str(str)) => path.push(&*str.value),
+                    _ => return None,
+                },
+                MemberProp::PrivateName(_) => return None,
+            }
+            Some(path)
+        }
+        Expr::Paren(paren) => expr_path(&paren.expr),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Trust;
+
+    #[test]
+    fn test_trust() {
+        let trust: Trust = serde_json::from_str(
+            r#"{ "pure": ["n.d"], "plain": ["e.exports"], "proxies": ["e.exports.store"] }"#,
+        )
+        .unwrap();
+
+        assert!(trust.is_pure(&["n", "d"]));
+        assert!(!trust.is_pure(&["n"]));
+        assert!(!trust.is_pure(&["n", "d", "x"]));
+
+        assert!(trust.is_plain(&["e", "exports"]));
+        assert!(trust.is_plain(&["e", "exports", "a"]));
+        assert!(!trust.is_plain(&["e"]));
+        assert!(!trust.is_plain(&["e", "exports", "store", "a"]));
+        assert!(trust.is_proxy(&["e", "exports", "store", "a"]));
+
+        assert!(serde_json::from_str::<Trust>(r#"{ "pure": [] }"#).is_ok());
+        assert!(serde_json::from_str::<Trust>(r#"{ "safe": [] }"#).is_err());
+    }
+}
@@ -3,15 +3,30 @@ use std::collections::HashMap;
 use swc_atoms::js_word;
 use swc_common::{pass::Either, EqIgnoreSpan, Span, SyntaxContext};
 use swc_ecma_ast::{
-    AssignExpr, AssignOp, BinExpr, BinaryOp, BindingIdent, Expr, Id, Ident, MemberExpr, MemberProp,
-    ModuleItem, ObjectLit, ParenExpr, Pat, PatOrExpr, Stmt,
+    ArrowExpr, AssignExpr, AssignOp, AwaitExpr, BinExpr, BinaryOp, BindingIdent, CallExpr, Expr,
+    Function, Id, Ident, Lit, MemberExpr, MemberProp, ModuleItem, NewExpr, ObjectLit, ParenExpr,
+    Pat, PatOrExpr, Stmt, TaggedTpl, UnaryOp, YieldExpr,
+};
+use swc_ecma_visit::{noop_visit_mut_type, noop_visit_type, Visit, VisitMut, VisitWith};
+
+use crate::{
+    access::{Access, AccessAnalysis, AccessPath},
+    effect::EffectAnalysis,
 };
-use swc_ecma_visit::{noop_visit_mut_type, VisitMut};
 
 pub fn make_undefined(span: Span) -> Expr {
     Expr::Ident(Ident::new(js_word!("undefined"), span))
 }
 
+/// Whether the expression is `undefined` or `void 0`
+pub fn is_undefined(expr: &Expr) -> bool {
+    match expr {
+        Expr::Ident(ident) => &*ident.sym == "undefined",
+        Expr::Unary(unary) if unary.op == UnaryOp::Void => matches!(*unary.arg, Expr::Lit(_)),
+        _ => false,
+    }
+}
+
 pub fn make_empty_object(span: Span) -> Expr {
     Expr::Object(ObjectLit {
         span,
@@ -20,8 +35,9 @@ pub fn make_empty_object(span: Span) -> Expr {
 }
 
 /// For nicely behaved accessors, like an identifier or `a.b` or `a['b']  
-/// Note that this is over-eager, because of custom getter functions and the like.
-/// But you'd need complicated analyses to find those properly, and it is bad coding style.
+/// Note that converting from just an expression is over-eager, because of custom getter functions
+/// and the like. Converting from an expression and an [`AccessAnalysis`] rejects the accessors
+/// that it knows are unsafe.
 #[derive(Debug, Clone)]
 pub enum NiceAccess {
     Ident(Ident),
@@ -32,7 +48,13 @@ impl NiceAccess {
     pub fn is_basically_equiv(&self, other: &NiceAccess) -> bool {
         match (self, other) {
             (NiceAccess::Ident(a), NiceAccess::Ident(b)) => a.to_id() == b.to_id(),
-            (NiceAccess::Member(a), NiceAccess::Member(b)) => a.eq_ignore_span(&b),
+            // `a.b` and `a["b"]` are the same property
+            (NiceAccess::Member(a), NiceAccess::Member(b)) => {
+                match (AccessPath::from_member(a), AccessPath::from_member(b)) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => a.eq_ignore_span(b),
+                }
+            }
             _ => false,
         }
     }
@@ -87,9 +109,14 @@ impl<'a> TryFrom<&'a Expr> for NiceAccess {
                 match &member.prop {
                     MemberProp::Ident(_) => Ok(NiceAccess::Member(member.clone())),
                     // TODO: we can probably support this
-                    MemberProp::PrivateName(_) => return Err(()),
-                    // TODO: We should be able to do a check for if it is a simple-expression
-                    MemberProp::Computed(_) => return Err(()),
+                    MemberProp::PrivateName(_) => Err(()),
+                    // `a["b"]` and `a[0]` are as nice as `a.b`
+                    MemberProp::Computed(computed) => match &*computed.expr {
+                        Expr::Lit(Lit::Str(_) | Lit::Num(_)) => {
+                            Ok(NiceAccess::Member(member.clone()))
+                        }
+                        _ => Err(()),
+                    },
                 }
             }
             _ => Err(()),
@@ -111,16 +138,46 @@ impl<'a> TryFrom<&'a PatOrExpr> for NiceAccess {
     }
 }
 
+impl<'a> TryFrom<(&'a Expr, &'a AccessAnalysis)> for NiceAccess {
+    type Error = ();
+
+    fn try_from((expr, access): (&'a Expr, &'a AccessAnalysis)) -> Result<Self, Self::Error> {
+        if access.get(expr) == Access::Unsafe {
+            return Err(());
+        }
+
+        Self::try_from(expr)
+    }
+}
+impl<'a> TryFrom<(&'a PatOrExpr, &'a AccessAnalysis)> for NiceAccess {
+    type Error = ();
+
+    fn try_from(
+        (pat_or_expr, access): (&'a PatOrExpr, &'a AccessAnalysis),
+    ) -> Result<Self, Self::Error> {
+        match Self::try_from(pat_or_expr)? {
+            NiceAccess::Member(member) => NiceAccess::try_from((&Expr::Member(member), access)),
+            ident => Ok(ident),
+        }
+    }
+}
+
 /// Check if the expression is of the form `a = x || (x = {})` and return both identifiers  
 /// Otherwise, check if the expression is of the form `a || (a = {})` and return the identifier
-pub fn extract_or_initializer_with_assign(expr: &Expr) -> Option<(Option<Ident>, NiceAccess)> {
-    extract_or_assign_initializer(expr)
+pub fn extract_or_initializer_with_assign(
+    expr: &Expr,
+    access: &AccessAnalysis,
+) -> Option<(Option<Ident>, NiceAccess)> {
+    extract_or_assign_initializer(expr, access)
         .map(|(a, b)| (Some(a), b))
-        .or_else(|| Some((None, extract_or_initializer(expr)?)))
+        .or_else(|| Some((None, extract_or_initializer(expr, access)?)))
 }
 
 /// Check if the expression is of the form `a = x || (x = {})` and return both identifiers
-pub fn extract_or_assign_initializer(expr: &Expr) -> Option<(Ident, NiceAccess)> {
+pub fn extract_or_assign_initializer(
+    expr: &Expr,
+    access: &AccessAnalysis,
+) -> Option<(Ident, NiceAccess)> {
     let assign = expr.as_assign()?;
 
     // Get the identifier on the left side of the assignment
@@ -133,7 +190,7 @@ pub fn extract_or_assign_initializer(expr: &Expr) -> Option<(Ident, NiceAccess)>
     let right = assign.right.unwrap_parens();
 
     // Check if the right side is of the form `x || (x = {})`
-    let right_access = extract_or_initializer(right)?;
+    let right_access = extract_or_initializer(right, access)?;
 
     // TODO: Should we do anything special if it is of the weird form `x = x || (x = {})`?
 
@@ -141,7 +198,7 @@ pub fn extract_or_assign_initializer(expr: &Expr) -> Option<(Ident, NiceAccess)>
 }
 
 /// Check if the expression is of the form `x || (x = {})`, returning the expr
-pub fn extract_or_initializer(expr: &Expr) -> Option<NiceAccess> {
+pub fn extract_or_initializer(expr: &Expr, access: &AccessAnalysis) -> Option<NiceAccess> {
     let bin = expr.as_bin()?;
 
     if bin.op != BinaryOp::LogicalOr {
@@ -156,8 +213,8 @@ pub fn extract_or_initializer(expr: &Expr) -> Option<NiceAccess> {
     let right = &assign.left;
     let left = bin.left.as_ref();
 
-    let left = NiceAccess::try_from(left).ok()?;
-    let right = NiceAccess::try_from(right).ok()?;
+    let left = NiceAccess::try_from((left, access)).ok()?;
+    let right = NiceAccess::try_from((right, access)).ok()?;
 
     if left.is_basically_equiv(&right) {
         Some(left)
@@ -195,6 +252,83 @@ pub fn get_assign_eq_expr(expr: &Expr) -> Option<&AssignExpr> {
     }
 }
 
+/// Check if the expression is of the form `x = y` with an identifier, and return `x` and `y`
+pub fn extract_ident_assign(expr: &Expr) -> Option<(&Ident, &Expr)> {
+    let assign = get_assign_eq_expr(expr.unwrap_parens())?;
+    let ident = match &assign.left {
+        PatOrExpr::Pat(pat) => &pat.as_ident()?.id,
+        PatOrExpr::Expr(expr) => expr.as_ident()?,
+    };
+    Some((ident, &assign.right))
+}
+
+/// A test of whether a value is `null` or `undefined`
+#[derive(Debug)]
+pub struct NullishCheck<'a> {
+    /// The value that is tested, like `a` or `t = a.b`
+    pub value: &'a Expr,
+    /// Whether the test passes for `null` and `undefined`, like `==` rather than `!=`
+    pub when_nullish: bool,
+}
+
+/// Check if the expression is of the form `x == null`, or `x === null || x === void 0` the way
+/// Babel and Typescript write it, or the negation of either. When `x` is an assignment like
+/// `(t = a.b)`, the second comparison has to be of `t`.
+pub fn extract_nullish_check(expr: &Expr) -> Option<NullishCheck<'_>> {
+    let bin = expr.unwrap_parens().as_bin()?;
+    let (value, when_nullish) = match bin.op {
+        BinaryOp::EqEq | BinaryOp::NotEq => (
+            compared_to_nullish(expr, bin.op)?.0,
+            bin.op == BinaryOp::EqEq,
+        ),
+        BinaryOp::LogicalOr | BinaryOp::LogicalAnd => {
+            let when_nullish = bin.op == BinaryOp::LogicalOr;
+            let op = if when_nullish {
+                BinaryOp::EqEqEq
+            } else {
+                BinaryOp::NotEqEq
+            };
+            let (value, is_null) = compared_to_nullish(&bin.left, op)?;
+            let (other, other_is_null) = compared_to_nullish(&bin.right, op)?;
+            if is_null == other_is_null {
+                return None;
+            }
+
+            let tested = match extract_ident_assign(value) {
+                Some((ident, _)) => ident,
+                None => value.as_ident()?,
+            };
+            if !matches!(other, Expr::Ident(other) if other.to_id() == tested.to_id()) {
+                return None;
+            }
+            (value, when_nullish)
+        }
+        _ => return None,
+    };
+
+    Some(NullishCheck {
+        value: value.unwrap_parens(),
+        when_nullish,
+    })
+}
+
+/// `x == null` or `void 0 == x` with `op`, returning `x` and whether it is compared to `null`
+fn compared_to_nullish(expr: &Expr, op: BinaryOp) -> Option<(&Expr, bool)> {
+    let bin = expr.unwrap_parens().as_bin().filter(|bin| bin.op == op)?;
+    let is_null = |expr: &Expr| matches!(expr, Expr::Lit(Lit::Null(_)));
+    if is_null(&bin.right) {
+        Some((&bin.left, true))
+    } else if is_null(&bin.left) {
+        Some((&bin.right, true))
+    } else if is_undefined(&bin.right) {
+        Some((&bin.left, false))
+    } else if is_undefined(&bin.left) {
+        Some((&bin.right, false))
+    } else {
+        None
+    }
+}
+
 #[derive(Debug)]
 pub enum Stmts<'a> {
     Stmts(&'a Vec<Stmt>),
@@ -229,7 +363,7 @@ pub enum StmtsMut<'a> {
 }
 impl<'a> StmtsMut<'a> {
     // is this lifetime sane?
-    pub fn iter<'s>(&'s self) -> impl Iterator<Item = &'s Stmt> + DoubleEndedIterator
+    pub fn iter<'s>(&'s self) -> impl DoubleEndedIterator<Item = &'s Stmt>
     where
         'a: 's,
     {
@@ -239,7 +373,7 @@ impl<'a> StmtsMut<'a> {
         }
     }
 
-    pub fn iter_mut<'s>(&'s mut self) -> impl Iterator<Item = &'s mut Stmt> + DoubleEndedIterator
+    pub fn iter_mut<'s>(&'s mut self) -> impl DoubleEndedIterator<Item = &'s mut Stmt>
     where
         'a: 's,
     {
@@ -285,6 +419,33 @@ impl<'a> StmtsMut<'a> {
         }
     }
 
+    /// Get the statement at the given index into the underlying vector, if it is a statement.
+    pub fn get(&self, idx: usize) -> Option<&Stmt> {
+        match self {
+            StmtsMut::Stmts(stmts) => stmts.get(idx),
+            StmtsMut::Module(stmts) => stmts.get(idx)?.as_stmt(),
+        }
+    }
+
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut Stmt> {
+        match self {
+            StmtsMut::Stmts(stmts) => stmts.get_mut(idx),
+            StmtsMut::Module(stmts) => stmts.get_mut(idx)?.as_mut_stmt(),
+        }
+    }
+
+    /// The number of entries in the underlying vector, including module declarations.
+    pub fn len(&self) -> usize {
+        match self {
+            StmtsMut::Stmts(stmts) => stmts.len(),
+            StmtsMut::Module(stmts) => stmts.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn push(&mut self, stmt: Stmt) {
         match self {
             StmtsMut::Stmts(stmts) => stmts.push(stmt),
@@ -302,6 +463,21 @@ impl<'a> StmtsMut<'a> {
             StmtsMut::Module(stmts) => stmts.insert(idx, ModuleItem::Stmt(stmt)),
         }
     }
+
+    /// Remove the entry at the given index.
+    ///
+    /// # Panics
+    /// If `idx >= len`
+    pub fn remove(&mut self, idx: usize) {
+        match self {
+            StmtsMut::Stmts(stmts) => {
+                stmts.remove(idx);
+            }
+            StmtsMut::Module(stmts) => {
+                stmts.remove(idx);
+            }
+        }
+    }
 }
 
 impl<'a> From<&'a mut Vec<Stmt>> for StmtsMut<'a> {
@@ -315,13 +491,15 @@ impl<'a> From<&'a mut Vec<ModuleItem>> for StmtsMut<'a> {
     }
 }
 
-pub fn replace_entries<T, J, I, F>(data: &'_ mut Vec<T>, f: F)
+/// Replace each entry that `f` returns `Some` for with the returned values.
+/// Returns whether any entries were replaced.
+pub fn replace_entries<T, J, I, F>(data: &'_ mut Vec<T>, mut f: F) -> bool
 where
     T: 'static,
     J: Into<T> + 'static,
     I: IntoIterator<Item = J> + 'static,
     <I as IntoIterator>::IntoIter: DoubleEndedIterator,
-    F: Fn(&T) -> Option<I>,
+    F: FnMut(&T) -> Option<I>,
 {
     let mut result: Vec<(usize, I)> = Vec::new();
     {
@@ -332,18 +510,95 @@ where
         }
     }
 
-    let mut offset = 0;
-    for (i, values) in result {
+    let replaced = !result.is_empty();
+    // How many entries have been inserted before the current index.
+    // Each earlier replacement also removed one entry.
+    let mut inserted = 0;
+    for (removed, (i, values)) in result.into_iter().enumerate() {
         let values = values.into_iter().map(Into::into);
         // replace the single entry at `i` with the new values, without using the splice function
 
-        let i = i + offset;
+        let i = i + inserted - removed;
         data.remove(i);
         for value in values.rev() {
             data.insert(i, value);
-            offset += 1;
+            inserted += 1;
+        }
+    }
+
+    replaced
+}
+
+/// Finds anything that could run other code, outside of any nested functions
+#[derive(Default)]
+pub struct CallFinder<'a> {
+    /// Calls to functions which are known to be pure don't count, but reading from a proxy does
+    effects: Option<&'a EffectAnalysis>,
+    found: bool,
+}
+impl CallFinder<'_> {
+    pub fn find<N: VisitWith<CallFinder<'static>>>(node: &N) -> bool {
+        let mut finder = CallFinder::default();
+        node.visit_with(&mut finder);
+        finder.found
+    }
+}
+impl<'a> CallFinder<'a> {
+    /// Like [`CallFinder::find`], but calls to pure functions are allowed
+    pub fn find_impure<N: VisitWith<CallFinder<'a>>>(
+        node: &N,
+        effects: &'a EffectAnalysis,
+    ) -> bool {
+        let mut finder = CallFinder {
+            effects: Some(effects),
+            found: false,
+        };
+        node.visit_with(&mut finder);
+        finder.found
+    }
+}
+impl Visit for CallFinder<'_> {
+    noop_visit_type!();
+
+    fn visit_call_expr(&mut self, call: &CallExpr) {
+        match self.effects {
+            Some(effects) if effects.callee(&call.callee).is_pure() => {
+                call.visit_children_with(self)
+            }
+            _ => self.found = true,
         }
     }
+
+    fn visit_member_expr(&mut self, member: &MemberExpr) {
+        // Reading a property of a proxy runs its code
+        if self
+            .effects
+            .is_some_and(|effects| effects.is_proxy(&member.obj))
+        {
+            self.found = true;
+        }
+        member.visit_children_with(self);
+    }
+
+    fn visit_new_expr(&mut self, _: &NewExpr) {
+        self.found = true;
+    }
+
+    fn visit_tagged_tpl(&mut self, _: &TaggedTpl) {
+        self.found = true;
+    }
+
+    fn visit_await_expr(&mut self, _: &AwaitExpr) {
+        self.found = true;
+    }
+
+    fn visit_yield_expr(&mut self, _: &YieldExpr) {
+        self.found = true;
+    }
+
+    fn visit_function(&mut self, _: &Function) {}
+
+    fn visit_arrow_expr(&mut self, _: &ArrowExpr) {}
 }
 
 // The remapper code is from SWC, and so is under their License.
@@ -381,4 +636,18 @@ mod tests {
         );
         assert_eq!(data, vec![1, 2, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
     }
+
+    #[test]
+    fn test_replace_entries_multiple() {
+        let mut data = vec![1, 2, 3, 4];
+        let replaced = super::replace_entries(&mut data, |x| {
+            if *x == 2 || *x == 4 {
+                Some(vec![*x * 10, *x * 10 + 1])
+            } else {
+                None
+            }
+        });
+        assert!(replaced);
+        assert_eq!(data, vec![1, 20, 21, 3, 40, 41]);
+    }
 }
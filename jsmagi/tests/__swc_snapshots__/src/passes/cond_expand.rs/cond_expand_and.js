@@ -0,0 +1,4 @@
+if (s) {
+    a = s.type;
+    c = s.handler;
+}
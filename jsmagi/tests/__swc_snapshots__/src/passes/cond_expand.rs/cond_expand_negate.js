@@ -0,0 +1,12 @@
+if (a) {
+    b();
+}
+if (a !== b) {
+    c();
+}
+a = b || c();
+function f() {
+    if (x && y) {
+        z();
+    }
+}
@@ -0,0 +1,7 @@
+if (!this.a) {
+    this.a = !0;
+    if (this.b) {
+        this.b.fire(void 0);
+        this.dispose();
+    }
+}
@@ -0,0 +1,21 @@
+if (a) {
+    b();
+} else {
+    c();
+}
+if (a) {
+    b();
+}
+if (!a) {
+    c();
+}
+if (a) {
+    b();
+    c();
+} else {
+    if (d) {
+        e();
+    } else {
+        f();
+    }
+}
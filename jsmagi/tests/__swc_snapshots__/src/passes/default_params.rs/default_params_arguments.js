@@ -0,0 +1,7 @@
+function f(a, b = 5, c) {
+    return a + b + c;
+}
+function g() {
+    var a = arguments.length > 0 ? arguments[0] : undefined;
+    return a;
+}
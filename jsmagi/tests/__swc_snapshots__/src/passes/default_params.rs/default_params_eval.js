@@ -0,0 +1,15 @@
+function f(a = 5) {
+    return a;
+}
+function g(b) {
+    if (b === void 0) {
+        b = 5;
+    }
+    return eval(s);
+}
+var h = (c)=>{
+    if (c === void 0) {
+        c = 5;
+    }
+    return ()=>eval(s);
+};
@@ -0,0 +1,8 @@
+function f(a, b = 5) {
+    if (void 0 === a) a = b;
+    return a + b;
+}
+function g(a = 1, b = a) {}
+var h = (a = {})=>{
+    return a;
+};
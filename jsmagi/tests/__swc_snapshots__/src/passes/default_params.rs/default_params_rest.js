@@ -0,0 +1,9 @@
+function ts(a, ...rest) {
+    return rest;
+}
+function babel(...args) {
+    return args;
+}
+function slice(a, b, ...c) {
+    return c;
+}
@@ -0,0 +1,11 @@
+function f(a = 1, b = {}, c = []) {
+    return a + b + c;
+}
+function g(a = 2, b = 1) {
+    return a + b;
+}
+function h(a, b = foo()) {
+    if (a === void 0) {
+        a = bar();
+    }
+}
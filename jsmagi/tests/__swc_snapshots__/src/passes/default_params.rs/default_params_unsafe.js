@@ -0,0 +1,41 @@
+function usesBody(a) {
+    if (a === undefined) {
+        a = g();
+    }
+    function g() {}
+}
+function usesArguments(a) {
+    if (a === undefined) {
+        a = 1;
+    }
+    return arguments[0];
+}
+function strict(a) {
+    "use strict";
+    if (a === undefined) {
+        a = 1;
+    }
+}
+function* gen(a) {
+    if (a === undefined) {
+        a = 1;
+    }
+}
+function later(a, b) {
+    if (a === undefined) {
+        a = b;
+    }
+}
+function notFirst(a) {
+    f();
+    if (a === undefined) {
+        a = 1;
+    }
+}
+function usesIndex(a) {
+    var rest = [];
+    for(var _i = 1; _i < arguments.length; _i++){
+        rest[_i - 1] = arguments[_i];
+    }
+    return _i;
+}
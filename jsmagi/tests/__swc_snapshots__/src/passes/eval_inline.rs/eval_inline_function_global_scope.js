@@ -0,0 +1,5 @@
+function f(z1) {
+    return function() {
+        return z;
+    };
+}
@@ -0,0 +1,4 @@
+function f() {
+    /* decoded: var a = 1 */ eval('var a = 1');
+    return a;
+}
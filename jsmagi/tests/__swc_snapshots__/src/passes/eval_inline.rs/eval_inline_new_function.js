@@ -0,0 +1,5 @@
+function f(x) {
+    return function(x, y) {
+        return x + y;
+    };
+}
@@ -0,0 +1,5 @@
+function f(code) {
+    eval(code);
+    let eval2 = eval;
+    eval2('a');
+}
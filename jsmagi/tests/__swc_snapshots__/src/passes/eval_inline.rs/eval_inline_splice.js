@@ -0,0 +1,12 @@
+function f(x) {
+    a();
+    b();
+    {
+        let c = 1;
+        d(c);
+    }
+    if (x) {
+        e();
+    }
+    g();
+}
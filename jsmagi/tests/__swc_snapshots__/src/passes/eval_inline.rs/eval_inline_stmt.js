@@ -0,0 +1,6 @@
+function f(a) {
+    {
+        let b = a + 1;
+        console.log(b);
+    }
+}
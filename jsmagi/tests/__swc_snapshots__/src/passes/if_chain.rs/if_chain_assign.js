@@ -0,0 +1,45 @@
+if (a) {
+    x = 1;
+} else if (b) {
+    x = 2;
+} else {
+    x = 3;
+}
+o.x = a ? 1 : b ? 2 : 3;
+y = a ? 1 : 2;
+var v;
+if (a) {
+    if (b) {
+        v = 1;
+    } else {
+        v = 2;
+    }
+} else {
+    v = 3;
+}
+let l;
+if (a) {
+    l = 1;
+} else if (b) {
+    l = 2;
+} else {
+    l = 3;
+}
+let tmp;
+if (a) {
+    tmp = s;
+} else if (b) {
+    tmp = 2;
+} else {
+    tmp = 3;
+}
+let s = tmp;
+let tmp1;
+if (a) {
+    tmp1 = 1;
+} else if (b) {
+    tmp1 = 2;
+} else {
+    tmp1 = 3;
+}
+const c = tmp1;
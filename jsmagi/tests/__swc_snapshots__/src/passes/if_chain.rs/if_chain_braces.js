@@ -0,0 +1,29 @@
+if (a) {
+    f();
+} else if (b) {
+    g();
+} else {
+    h();
+}
+if (a) {
+    f();
+} else if (b) {
+    g();
+} else if (c) {
+    h();
+}
+for(;;){
+    f();
+}
+for(x in y){
+    f();
+}
+for (x of y){
+    f();
+}
+while(a){
+    f();
+}
+do {
+    f();
+}while (a)
@@ -0,0 +1,26 @@
+if (c && undefined === e1.params) {
+    f();
+}
+if ((a || b) && (c ?? d)) {
+    f();
+}
+if (a) {
+    if (b) {
+        f();
+    } else {
+        g();
+    }
+}
+if (a) {
+    if (b) {
+        f();
+    }
+} else {
+    g();
+}
+if (a) {
+    if (b) {
+        f();
+    }
+    g();
+}
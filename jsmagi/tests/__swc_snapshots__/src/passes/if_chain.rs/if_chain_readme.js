@@ -0,0 +1,12 @@
+function f(a) {
+    if (a && a.b) {
+        g();
+    }
+    if (a) {
+        return 1;
+    }
+    if (b) {
+        return 2;
+    }
+    return 3;
+}
@@ -0,0 +1,15 @@
+function f() {
+    if (x) {
+        if (a) {
+            if (b) {
+                return 1;
+            }
+            return 2;
+        }
+        if (c) {
+            return 3;
+        }
+        return 4;
+    }
+    return a ? 1 : 2;
+}
@@ -1,3 +1,2 @@
 exports.Thing = exports.Thing || {};
-let tmp = exports.Thing;
-tmp.a = 'a';
+exports.Thing.a = 'a';
@@ -0,0 +1,2 @@
+exports["Thing"] = exports["Thing"] || {};
+exports["Thing"].a = 'a';
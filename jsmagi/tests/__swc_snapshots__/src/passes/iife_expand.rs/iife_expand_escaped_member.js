@@ -0,0 +1,4 @@
+register(exports);
+exports.Thing = exports.Thing || {};
+let tmp = exports.Thing;
+tmp.a = 'a';
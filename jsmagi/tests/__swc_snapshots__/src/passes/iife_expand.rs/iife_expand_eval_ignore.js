@@ -0,0 +1,2 @@
+var a = 5;
+eval('a');
@@ -0,0 +1,9 @@
+var a = function() {
+    return 5;
+}();
+function f() {
+    var b = 6;
+}
+function g() {
+    eval('a');
+}
@@ -0,0 +1,8 @@
+Object.defineProperty(exports, 'Thing', {
+    get: function() {
+        return thing;
+    }
+});
+(function(e1) {
+    e1.a = 'a';
+})(exports.Thing || (exports.Thing = {}));
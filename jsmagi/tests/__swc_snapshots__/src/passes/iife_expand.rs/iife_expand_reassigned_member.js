@@ -0,0 +1,7 @@
+exports.Thing = exports.Thing || {};
+let tmp = exports.Thing;
+tmp.a = f();
+tmp.b = 'b';
+function reset() {
+    exports.Thing = {};
+}
@@ -0,0 +1,6 @@
+let tmp = exports.Thing || (exports.Thing = {});
+tmp.a = 'a';
+p = exports.Other || (exports.Other = {});
+p.a = 'a';
+w = w || {};
+w.a = 'a';
@@ -0,0 +1,10 @@
+Object.defineProperty(exports, 'Thing', {
+    get: function() {
+        return thing;
+    }
+});
+exports.Thing = exports.Thing || {};
+exports.Thing.a = 'a';
+(function(e1) {
+    e1.a = 'a';
+})(store.Thing || (store.Thing = {}));
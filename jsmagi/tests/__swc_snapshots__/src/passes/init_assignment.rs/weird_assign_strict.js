@@ -0,0 +1,2 @@
+c = n || (n = {});
+c.thing = 'hi';
@@ -0,0 +1,6 @@
+a = String(b);
+c = String(d.e);
+f = String(g) + "h";
+i = "" + "j";
+k = Number(l);
+m = 1;
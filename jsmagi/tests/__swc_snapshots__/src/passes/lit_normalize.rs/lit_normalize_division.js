@@ -0,0 +1,4 @@
+a = Infinity;
+b = -Infinity;
+c = NaN;
+d = 1 / 2;
@@ -0,0 +1,9 @@
+true;
+false;
+true;
+false;
+true;
+!!x;
+![
+    a
+];
@@ -0,0 +1,12 @@
+a = [
+    86400000,
+    1000,
+    0.0025,
+    255,
+    15,
+    5,
+    1e100,
+    1_000,
+    1.5,
+    4294967295
+];
@@ -0,0 +1,7 @@
+function f(String, NaN) {
+    return [
+        "" + a,
+        0 / 0,
+        Number(b)
+    ];
+}
@@ -0,0 +1,7 @@
+a = [
+    "AB",
+    "café",
+    "\u200b",
+    "\x41\t",
+    "\n"
+];
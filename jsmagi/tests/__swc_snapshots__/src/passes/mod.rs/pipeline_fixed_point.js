@@ -0,0 +1,3 @@
+a;
+x = x || {};
+x.j = 5;
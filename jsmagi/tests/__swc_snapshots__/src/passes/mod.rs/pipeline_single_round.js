@@ -0,0 +1,4 @@
+a;
+(function(e) {
+    e.j = 5;
+})(x || (x = {}));
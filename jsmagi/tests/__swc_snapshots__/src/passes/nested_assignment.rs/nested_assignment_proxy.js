@@ -0,0 +1,4 @@
+store.a = store.b = 0;
+a = b = store.c;
+t.a = 1;
+t.b = 1;
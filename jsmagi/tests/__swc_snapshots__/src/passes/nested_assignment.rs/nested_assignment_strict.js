@@ -0,0 +1,6 @@
+a = 1;
+b = 1;
+c = 1;
+t.a = t.b = 4;
+a = t.b = b = 5;
+a = b = c;
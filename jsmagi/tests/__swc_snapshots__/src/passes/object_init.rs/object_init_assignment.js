@@ -0,0 +1,7 @@
+var a;
+a = {
+    x: 1,
+    y: 2
+};
+b = {};
+b.x = 1;
@@ -0,0 +1,11 @@
+var a = {
+    x: 1,
+    y: f()
+};
+a.x = 2;
+a.y = 3;
+var b = {
+    x: 1,
+    [k]: 2
+};
+b.x = 3;
@@ -0,0 +1,8 @@
+var a = {
+    x: 4,
+    "y-z": 2,
+    3: 3,
+    f: function() {
+        return a.x;
+    }
+};
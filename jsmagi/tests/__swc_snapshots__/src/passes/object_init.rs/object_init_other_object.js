@@ -0,0 +1,7 @@
+var a = {
+    x: 2
+};
+var b = {
+    x: 1
+};
+c.x = 3;
@@ -0,0 +1,9 @@
+function add(x, y) {
+    return x + y;
+}
+var a = {
+    x: add(1, 2)
+};
+a.y = String(a.x);
+a.z = Math.max(1, add(2, 3));
+a.w = 2;
@@ -0,0 +1,4 @@
+var a = {
+    blah: "hi",
+    thing: "hi"
+};
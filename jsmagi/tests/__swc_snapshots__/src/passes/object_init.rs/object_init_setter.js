@@ -0,0 +1,7 @@
+var a = {
+    set x (v){}
+};
+a.x = 1;
+var b = {};
+b.__proto__ = a;
+b["__proto__"] = a;
@@ -0,0 +1,9 @@
+function f() {
+    var a = {
+        blah: "hi",
+        thing: "hi"
+    };
+    var b = 2;
+    b = 3;
+    a.other = b;
+}
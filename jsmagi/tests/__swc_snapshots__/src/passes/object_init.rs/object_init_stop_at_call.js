@@ -0,0 +1,5 @@
+var a = {
+    x: 1
+};
+a.y = f();
+a.z = 2;
@@ -0,0 +1,4 @@
+var a = {};
+console.log(a);
+a = a || {};
+a.x = 1;
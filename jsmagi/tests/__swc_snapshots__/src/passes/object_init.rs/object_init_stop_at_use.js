@@ -0,0 +1,5 @@
+var a = {
+    x: 1
+};
+a.y = a.x + 1;
+a.z = 2;
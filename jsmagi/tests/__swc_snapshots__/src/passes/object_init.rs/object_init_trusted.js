@@ -0,0 +1,5 @@
+var a = {
+    x: n.d(1)
+};
+a.y = store.count;
+a.z = 2;
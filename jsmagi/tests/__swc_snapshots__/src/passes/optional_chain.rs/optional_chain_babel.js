@@ -0,0 +1,8 @@
+function f(a) {
+    var x = a.b?.c;
+    var y = a?.c?.d();
+    return [
+        x,
+        y
+    ];
+}
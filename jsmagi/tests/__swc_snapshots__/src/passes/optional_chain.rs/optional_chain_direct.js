@@ -0,0 +1,7 @@
+a = b?.c;
+d = e?.f.g();
+h = i?.[j];
+k = l?.(m);
+n = o ?? p;
+q = r ?? (s || t);
+u = null == v ? void 0 : w.x;
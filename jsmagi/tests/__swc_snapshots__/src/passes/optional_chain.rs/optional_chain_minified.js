@@ -0,0 +1,6 @@
+function f(e) {
+    return e.a?.b.c(e);
+}
+const g = (e)=>{
+    return e.a?.call(e, 1);
+};
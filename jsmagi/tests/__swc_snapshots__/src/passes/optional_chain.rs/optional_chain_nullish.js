@@ -0,0 +1,10 @@
+function f(e, d) {
+    var a = e.x ?? d;
+    var b = e.y ?? d;
+    var c = (e.z || e.w) ?? d;
+    return [
+        a,
+        b,
+        c
+    ];
+}
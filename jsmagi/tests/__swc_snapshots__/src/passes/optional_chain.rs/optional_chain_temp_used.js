@@ -0,0 +1,17 @@
+function f(e) {
+    var t, n;
+    var a = null == (t = e.a) ? void 0 : t.b;
+    use(t);
+    var b = null == (n = e.c) ? void 0 : n(n);
+    return [
+        a,
+        b
+    ];
+}
+function g(t) {
+    return null == (t = t.a) ? void 0 : t.b;
+}
+function h(e) {
+    var t;
+    return null == (t = e.a) ? void 0 : t();
+}
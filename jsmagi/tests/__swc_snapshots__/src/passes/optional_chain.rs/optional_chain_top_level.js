@@ -0,0 +1,2 @@
+var t;
+a = null == (t = b.c) ? void 0 : t.d;
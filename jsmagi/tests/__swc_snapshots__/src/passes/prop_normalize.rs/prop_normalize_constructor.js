@@ -0,0 +1,27 @@
+var ns = {
+    A: function() {
+        this.x = 1;
+    },
+    B: function() {
+        if (!new.target) throw 1;
+    },
+    C: function() {
+        return ()=>{
+            this.y = 2;
+        };
+    },
+    D () {},
+    E () {
+        return this.x;
+    },
+    F () {
+        return function() {
+            this.x = 1;
+        };
+    }
+};
+var G = {
+    f: function() {
+        return G.prototype;
+    }
+};
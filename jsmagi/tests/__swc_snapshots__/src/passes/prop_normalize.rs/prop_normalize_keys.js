@@ -0,0 +1,10 @@
+var o = {
+    a: 1,
+    b: 2,
+    "c-d": 3,
+    ["__proto__"]: 4,
+    "e-f": 5,
+    get g () {
+        return 6;
+    }
+};
@@ -0,0 +1,5 @@
+a.foo = a.bar;
+a["b-c"]();
+a[0];
+a.class.d;
+a?.e;
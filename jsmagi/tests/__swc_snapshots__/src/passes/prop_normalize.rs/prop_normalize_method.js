@@ -0,0 +1,15 @@
+var o = {
+    a () {
+        return 1;
+    },
+    *b () {
+        yield 1;
+    },
+    async c () {},
+    d () {},
+    e: function e() {
+        return e;
+    },
+    __proto__: function() {},
+    f: ()=>1
+};
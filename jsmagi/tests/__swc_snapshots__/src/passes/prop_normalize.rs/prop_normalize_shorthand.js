@@ -0,0 +1,6 @@
+var o = {
+    a,
+    b,
+    c: d,
+    __proto__: __proto__
+};
@@ -0,0 +1,15 @@
+function square(a) {
+    return a * a;
+}
+function hyp(a, b) {
+    return Math.sqrt(/* pure */ square(a) + /* pure */ square(b));
+}
+function log(a) {
+    console.log(a);
+}
+function set(o) {
+    o.x = 1;
+}
+const twice = (x)=>String(x) + x;
+log(/* pure */ hyp(3, /* pure */ twice(4)));
+set({});
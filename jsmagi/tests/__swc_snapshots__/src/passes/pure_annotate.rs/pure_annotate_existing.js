@@ -0,0 +1,5 @@
+function f() {
+    return 1;
+}
+var a = /*#__PURE__*/ f();
+var b = /* pure */ f();
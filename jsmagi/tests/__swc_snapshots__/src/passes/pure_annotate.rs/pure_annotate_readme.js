@@ -0,0 +1,4 @@
+function add(a, b) {
+    return a + b;
+}
+var c = /* pure */ add(1, 2);
@@ -0,0 +1,5 @@
+var f = (a)=>{
+    g(a);
+    return a + 1;
+};
+var h = (a)=>a;
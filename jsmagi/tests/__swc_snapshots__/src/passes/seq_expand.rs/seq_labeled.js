@@ -0,0 +1,5 @@
+outer: for(a(), i = 0; i < 1; i++){
+    for(;;){
+        continue outer;
+    }
+}
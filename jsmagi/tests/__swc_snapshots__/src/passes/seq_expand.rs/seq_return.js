@@ -0,0 +1,20 @@
+function a() {
+    this._token || (this._token = new c);
+    return this._token;
+}
+function b() {
+    if (x) {
+        f();
+        g();
+        return 1;
+    }
+    h();
+    throw new Error();
+}
+function c() {
+    r(1);
+    return;
+}
+function d() {
+    return void 0;
+}
@@ -0,0 +1,14 @@
+a();
+if (b) c();
+else {
+    d();
+    if (e) f();
+}
+g();
+switch(h){
+    case 1:
+        i();
+}
+j();
+for(k = 0; k < 1; l(), k++)m();
+while(n(), o)p();
@@ -0,0 +1,6 @@
+exports["Thing"] = exports["Thing"] || {};
+enum Thing {
+    A = 0,
+    B = 1
+}
+Object.assign(exports["Thing"], Thing);
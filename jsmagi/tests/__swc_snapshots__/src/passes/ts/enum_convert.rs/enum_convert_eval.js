@@ -0,0 +1,13 @@
+function f() {
+    (function(e1) {
+        e1[e1.A = 1] = "A";
+    })(w || (w = {}));
+    eval(s);
+}
+function g() {
+    v = v || {};
+    enum en_$0000 {
+        B = 2
+    }
+    Object.assign(v, en_$0000);
+}
@@ -0,0 +1,5 @@
+p = exports.Thing || (exports.Thing = {});
+enum Thing {
+    A = 0
+}
+Object.assign(p, Thing);
@@ -0,0 +1,5 @@
+let tmp = exports.Thing || (exports.Thing = {});
+enum Thing {
+    A = 0
+}
+Object.assign(tmp, Thing);
@@ -0,0 +1,3 @@
+let n;
+n = f();
+var m = f();
@@ -1,4 +1,2 @@
-let n;
-let c;
-n = n || {};
-c = c || {};
+let n = {};
+let c = {};
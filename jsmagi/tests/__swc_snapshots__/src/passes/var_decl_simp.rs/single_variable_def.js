@@ -1,2 +1 @@
-let n;
-n = 0;
+let n = 0;
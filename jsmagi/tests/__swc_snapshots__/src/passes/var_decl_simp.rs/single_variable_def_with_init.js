@@ -1,2 +1 @@
-let n;
-n = n || {};
+let n = {};
@@ -0,0 +1,4 @@
+var l = {};
+var j;
+var k = 2;
+function f() {}
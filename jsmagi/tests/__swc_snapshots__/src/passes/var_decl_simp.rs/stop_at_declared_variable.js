@@ -0,0 +1,3 @@
+var n;
+var m = 1;
+n = m;
@@ -0,0 +1,3 @@
+var n;
+console.log(n);
+n = 0;
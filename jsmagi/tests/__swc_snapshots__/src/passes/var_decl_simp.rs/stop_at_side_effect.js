@@ -0,0 +1,3 @@
+var n;
+f();
+n = 0;
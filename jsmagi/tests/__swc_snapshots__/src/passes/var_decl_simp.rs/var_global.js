@@ -0,0 +1 @@
+var l = l || {};
@@ -0,0 +1,4 @@
+function f(a) {
+    var n = {};
+    var a = a || {};
+}
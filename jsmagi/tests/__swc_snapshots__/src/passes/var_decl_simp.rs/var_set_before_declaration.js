@@ -0,0 +1,7 @@
+function f() {
+    g();
+    var n = n || {};
+    function g() {
+        n = 1;
+    }
+}
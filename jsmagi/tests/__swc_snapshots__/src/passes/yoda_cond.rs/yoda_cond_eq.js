@@ -0,0 +1,8 @@
+v !== undefined;
+typeof e1 == "object";
+a.thing === 0;
+this != null;
+b === void 0;
+-1 !== a.indexOf(b);
+0 === 1;
+a.b === 0;
@@ -0,0 +1,7 @@
+Object.defineProperty(a, "b", {
+    get: function() {
+        return 0;
+    }
+});
+0 === a.b;
+a.c === 0;
@@ -0,0 +1,3 @@
+a.length > 0;
+b <= 1;
+if (c >= 2 && d < 3) {}
@@ -0,0 +1,4 @@
+typeof window === "undefined";
+typeof document !== "undefined";
+typeof module === "undefined";
+typeof a > "v";
@@ -0,0 +1,8 @@
+var a = 1;
+function f(renamed) {
+    return renamed;
+}
+function g(b) {
+    var a = b;
+    return eval('a');
+}
@@ -0,0 +1,8 @@
+var a = 1;
+function g() {
+    var a = 2;
+    function h(a) {
+        return eval('a');
+    }
+    return h;
+}
@@ -0,0 +1,5 @@
+var renamed = 1;
+function f(renamed) {
+    return renamed + 1;
+}
+f(renamed);